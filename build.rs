@@ -2,6 +2,16 @@ extern crate winres;
 use winres::VersionInfo;
 
 fn main() {
+    // Reported at runtime by the build_info FFI function
+    println!(
+        "cargo:rustc-env=BUILD_TARGET={}",
+        std::env::var("TARGET").unwrap()
+    );
+    println!(
+        "cargo:rustc-env=BUILD_PROFILE={}",
+        std::env::var("PROFILE").unwrap()
+    );
+
     if std::env::var("CARGO_CFG_TARGET_OS").unwrap() != "windows" {
         return;
     }
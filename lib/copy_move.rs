@@ -0,0 +1,474 @@
+use std::{
+    ffi::OsString,
+    fs, io,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::progress::progress_callback;
+use crate::types::{CancelToken, GetPathInfo, RenameError};
+
+/// Move a file or directory by copying it and then deleting the source
+///
+/// Used as the fallback when `std::fs::rename` cannot move across volumes.
+/// The copy is verified before the source is removed; if verification fails,
+/// the partial copy is removed and the source is left untouched. The copy is also
+/// removed when a source file cannot be deleted, e.g. because it is read-only.
+/// A registered progress callback is told about every copied file, and the
+/// copy stops after the current file once `cancel` is cancelled.
+///
+/// ### Parameters
+/// * `from` - Original file or directory path
+/// * `to` - Target path on the other volume
+/// * `cancel` - Token stopping the copy, the partial copy is removed then
+///
+/// ### Return Value
+/// Returns `Ok(())` for success, `Err(RenameError)` for specific error
+pub fn move_by_copy(
+    from: &Path,
+    to: &Path,
+    cancel: Option<&CancelToken>,
+) -> Result<(), RenameError> {
+    let callback = progress_callback();
+    // Sizing the tree up front is only worth it when someone is listening
+    let total = match callback {
+        Some(_) => tree_summary(from).map_or(0, |(_, bytes)| bytes),
+        None => 0,
+    };
+    let mut copied = 0;
+    let mut report = |bytes: u64| {
+        if let Some(callback) = callback {
+            copied += bytes;
+            callback(copied, total);
+        }
+        match cancel {
+            Some(cancel) if cancel.is_cancelled() => Err(io::ErrorKind::Interrupted.into()),
+            _ => Ok(()),
+        }
+    };
+
+    if let Err(e) = copy_recursive(from, to, &mut report) {
+        let _ = remove_any(to);
+        if cancel.is_some_and(CancelToken::is_cancelled) {
+            return Err(RenameError::Cancelled);
+        }
+        return Err(RenameError::from(e));
+    }
+
+    if !same_content(from, to) {
+        let _ = remove_any(to);
+        return Err(RenameError::CopyVerifyFailed);
+    }
+
+    remove_any(from).map_err(|e| {
+        // A file that failed to delete is still intact, so drop the copy to keep
+        // the move all-or-nothing. A directory may be partially deleted already,
+        // then the copy is the only complete version and must stay.
+        if from.is_file() {
+            clear_readonly(to);
+            let _ = fs::remove_file(to);
+        }
+        RenameError::from(e)
+    })
+}
+
+/// Check that the copies a swap may fall back to are allowed and fit on their target volumes
+///
+/// An item is only copied when its target directory is on another volume,
+/// where a rename is refused. Items copied to one volume need room for all of
+/// them at once, since every source is deleted only after its copy is verified.
+///
+/// ### Parameters
+/// * `moves` - `(original, target)` path of every item
+/// * `allow_copy` - The caller enabled `cross_volume_copy`
+///
+/// ### Return Value
+/// * `Ok(u64)` - Bytes that would be copied, `0` when every rename stays on its volume
+/// * `Err(RenameError::CrossesVolumes)` - A copy is needed but `allow_copy` is not set
+/// * `Err(RenameError::InsufficientSpace)` - A target volume lacks the room
+pub(crate) fn check_copy_space(
+    moves: &[(&Path, &Path)],
+    allow_copy: bool,
+) -> Result<u64, RenameError> {
+    check_copy_space_with(
+        moves,
+        allow_copy,
+        GetPathInfo::volume_id,
+        GetPathInfo::free_space,
+    )
+}
+
+/// `check_copy_space` with the volume queries passed in, so tests can simulate other volumes
+///
+/// ### Parameters
+/// * `volume_id` - Identifier of the volume holding a path
+/// * `free_space` - Bytes available in a directory, `None` when unknown
+pub(crate) fn check_copy_space_with(
+    moves: &[(&Path, &Path)],
+    allow_copy: bool,
+    volume_id: impl Fn(&Path) -> Option<u64>,
+    free_space: impl Fn(&Path) -> Option<u64>,
+) -> Result<u64, RenameError> {
+    // (volume, target directory, bytes copied there)
+    let mut targets: Vec<(u64, &Path, u64)> = Vec::new();
+    for (from, to) in moves {
+        let Some(dir) = to.parent() else {
+            continue;
+        };
+        let (Some(source), Some(target)) = (volume_id(from), volume_id(dir)) else {
+            continue;
+        };
+        if source == target {
+            continue;
+        }
+        if !allow_copy {
+            return Err(RenameError::CrossesVolumes(format!(
+                "{} → {}",
+                crate::long_path::strip_extended(from).display(),
+                crate::long_path::strip_extended(dir).display()
+            )));
+        }
+        let bytes = tree_summary(from).map_or(0, |(_, bytes)| bytes);
+        match targets.iter_mut().find(|(volume, _, _)| *volume == target) {
+            Some(entry) => entry.2 += bytes,
+            None => targets.push((target, dir, bytes)),
+        }
+    }
+
+    for (_, dir, bytes) in &targets {
+        // Unknown free space is left to the copy itself to detect
+        if let Some(free) = free_space(dir) {
+            if free < *bytes {
+                return Err(RenameError::InsufficientSpace(format!(
+                    "{} bytes needed in {}, {} available",
+                    bytes,
+                    crate::long_path::strip_extended(dir).display(),
+                    free
+                )));
+            }
+        }
+    }
+    Ok(targets.iter().map(|(_, _, bytes)| bytes).sum())
+}
+
+/// Copy items into a new backup folder below `backup_dir`
+///
+/// Every call creates its own folder named after the current Unix time in
+/// milliseconds, so earlier backups are never overwritten. Items are stored as
+/// `1_<name>`, `2_<name>`, ... in input order, since two of them may share a name.
+/// Directories are copied recursively and every copy is verified.
+///
+/// ### Parameters
+/// * `items` - Files or directories to back up
+/// * `backup_dir` - Directory receiving the backup folder, created when missing
+///
+/// ### Return Value
+/// * `Ok(PathBuf)` - Folder holding the copies
+/// * `Err(RenameError::BackupFailed)` - Nothing usable was backed up, the partial folder is removed
+pub fn backup_items(items: &[&Path], backup_dir: &Path) -> Result<PathBuf, RenameError> {
+    let fail = |e: io::Error| RenameError::BackupFailed(e.to_string());
+
+    for item in items {
+        // Copying a directory into itself would never end
+        if GetPathInfo::path_is_parent(item, backup_dir) {
+            return Err(RenameError::BackupFailed(format!(
+                "Backup directory is inside {}",
+                item.display()
+            )));
+        }
+    }
+
+    fs::create_dir_all(backup_dir).map_err(fail)?;
+    let stamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let mut folder = backup_dir.join(format!("backup_{}", stamp));
+    let mut suffix = 1;
+    loop {
+        match fs::create_dir(&folder) {
+            Ok(()) => break,
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                folder = backup_dir.join(format!("backup_{}_{}", stamp, suffix));
+                suffix += 1;
+            }
+            Err(e) => return Err(fail(e)),
+        }
+    }
+
+    for (index, item) in items.iter().enumerate() {
+        let mut name = OsString::from(format!("{}_", index + 1));
+        name.push(item.file_name().unwrap_or(item.as_os_str()));
+        let target = folder.join(name);
+
+        let result = match copy_recursive(item, &target, &mut |_| Ok(())) {
+            Ok(()) if same_content(item, &target) => Ok(()),
+            Ok(()) => Err(RenameError::BackupFailed(format!(
+                "Copy of {} could not be verified",
+                item.display()
+            ))),
+            Err(e) => Err(fail(e)),
+        };
+        if let Err(e) = result {
+            let _ = fs::remove_dir_all(&folder);
+            return Err(e);
+        }
+    }
+    Ok(folder)
+}
+
+/// Clear the read-only attribute a copied file inherited from its source
+///
+/// Windows refuses to delete read-only files, other platforms only look at
+/// the parent directory
+fn clear_readonly(path: &Path) {
+    if !cfg!(windows) {
+        return;
+    }
+    if let Ok(metadata) = fs::metadata(path) {
+        let mut permissions = metadata.permissions();
+        if permissions.readonly() {
+            #[allow(clippy::permissions_set_readonly_false)]
+            permissions.set_readonly(false);
+            let _ = fs::set_permissions(path, permissions);
+        }
+    }
+}
+
+/// Copy a file, or a directory with all of its contents
+///
+/// Symbolic links and junctions are recreated pointing at the same target
+/// instead of being followed, so a link moves as a link and never drags its
+/// target tree along. `report` receives the size of every copied file, an
+/// error from it stops the copy
+fn copy_recursive(
+    from: &Path,
+    to: &Path,
+    report: &mut dyn FnMut(u64) -> io::Result<()>,
+) -> io::Result<()> {
+    let metadata = fs::symlink_metadata(from)?;
+    // Junctions count as symbolic links here as well
+    if metadata.file_type().is_symlink() {
+        copy_link(from, to)
+    } else if metadata.is_dir() {
+        fs::create_dir(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_recursive(&entry.path(), &to.join(entry.file_name()), report)?;
+        }
+        Ok(())
+    } else {
+        report(fs::copy(from, to)?)
+    }
+}
+
+/// Recreate a symbolic link or junction at `to` with the target of `from`
+#[cfg(windows)]
+fn copy_link(from: &Path, to: &Path) -> io::Result<()> {
+    use std::os::windows::fs::{symlink_dir, symlink_file};
+
+    let target = fs::read_link(from)?;
+    if GetPathInfo::is_junction(from) {
+        create_junction(&target, to)
+    } else if fs::metadata(from).is_ok_and(|m| m.is_dir()) {
+        symlink_dir(&target, to)
+    } else {
+        symlink_file(&target, to)
+    }
+}
+
+#[cfg(unix)]
+fn copy_link(from: &Path, to: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(fs::read_link(from)?, to)
+}
+
+/// Create a junction (mount point reparse point) at `link` pointing at `target`
+///
+/// Unlike a directory symbolic link, a junction needs no privilege. The target
+/// must be an absolute path.
+#[cfg(windows)]
+pub(crate) fn create_junction(target: &Path, link: &Path) -> io::Result<()> {
+    use std::os::windows::ffi::OsStrExt;
+    use winapi::um::fileapi::{CreateFileW, OPEN_EXISTING};
+    use winapi::um::handleapi::{CloseHandle, INVALID_HANDLE_VALUE};
+    use winapi::um::ioapiset::DeviceIoControl;
+    use winapi::um::winnt::GENERIC_WRITE;
+
+    const FILE_FLAG_BACKUP_SEMANTICS: u32 = 0x0200_0000;
+    const FILE_FLAG_OPEN_REPARSE_POINT: u32 = 0x0020_0000;
+    const FSCTL_SET_REPARSE_POINT: u32 = 0x0009_00A4;
+    const IO_REPARSE_TAG_MOUNT_POINT: u32 = 0xA000_0003;
+    // Tag, data length and reserved field, then the four name offsets and lengths
+    const HEADER_LEN: usize = 8;
+    const NAMES_HEADER_LEN: usize = 8;
+
+    let target = crate::long_path::strip_extended(target);
+    if !target.is_absolute() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Junction target must be absolute",
+        ));
+    }
+    // The substitute name is an NT path, the print name is what Explorer shows
+    let print: Vec<u16> = target.as_os_str().encode_wide().collect();
+    let substitute: Vec<u16> = r"\??".encode_utf16().chain(print.iter().copied()).collect();
+
+    let names_len = (substitute.len() + 1 + print.len() + 1) * 2;
+    let data_len = NAMES_HEADER_LEN + names_len;
+    let too_long = || io::Error::new(io::ErrorKind::InvalidInput, "Junction target is too long");
+    let data_len_u16 = u16::try_from(data_len).map_err(|_| too_long())?;
+
+    let mut buffer: Vec<u8> = Vec::with_capacity(HEADER_LEN + data_len);
+    buffer.extend_from_slice(&IO_REPARSE_TAG_MOUNT_POINT.to_le_bytes());
+    buffer.extend_from_slice(&data_len_u16.to_le_bytes());
+    buffer.extend_from_slice(&0u16.to_le_bytes());
+    let substitute_bytes = (substitute.len() * 2) as u16;
+    let print_offset = substitute_bytes + 2;
+    for field in [0, substitute_bytes, print_offset, (print.len() * 2) as u16] {
+        buffer.extend_from_slice(&field.to_le_bytes());
+    }
+    for unit in substitute.iter().chain([&0]).chain(&print).chain([&0]) {
+        buffer.extend_from_slice(&unit.to_le_bytes());
+    }
+
+    fs::create_dir(link)?;
+    let wide: Vec<u16> = crate::long_path::to_extended(link)
+        .as_os_str()
+        .encode_wide()
+        .chain(std::iter::once(0))
+        .collect();
+    let result = unsafe {
+        let handle = CreateFileW(
+            wide.as_ptr(),
+            GENERIC_WRITE,
+            0,
+            std::ptr::null_mut(),
+            OPEN_EXISTING,
+            FILE_FLAG_BACKUP_SEMANTICS | FILE_FLAG_OPEN_REPARSE_POINT,
+            std::ptr::null_mut(),
+        );
+        if handle == INVALID_HANDLE_VALUE {
+            Err(io::Error::last_os_error())
+        } else {
+            let mut returned = 0;
+            let ok = DeviceIoControl(
+                handle,
+                FSCTL_SET_REPARSE_POINT,
+                buffer.as_mut_ptr() as *mut _,
+                buffer.len() as u32,
+                std::ptr::null_mut(),
+                0,
+                &mut returned,
+                std::ptr::null_mut(),
+            ) != 0;
+            let result = if ok {
+                Ok(())
+            } else {
+                Err(io::Error::last_os_error())
+            };
+            CloseHandle(handle);
+            result
+        }
+    };
+    if result.is_err() {
+        let _ = fs::remove_dir(link);
+    }
+    result
+}
+
+/// Compare entry count and total size of two trees
+fn same_content(path1: &Path, path2: &Path) -> bool {
+    match (tree_summary(path1), tree_summary(path2)) {
+        (Ok(summary1), Ok(summary2)) => summary1 == summary2,
+        _ => false,
+    }
+}
+
+/// Collect `(number of entries, total bytes)` of a file or directory tree
+///
+/// Links count as one entry and are not followed
+fn tree_summary(path: &Path) -> io::Result<(u64, u64)> {
+    let metadata = fs::symlink_metadata(path)?;
+    if !metadata.is_dir() {
+        return Ok((1, metadata.len()));
+    }
+
+    let mut summary = (1, 0);
+    for entry in fs::read_dir(path)? {
+        let (count, bytes) = tree_summary(&entry?.path())?;
+        summary.0 += count;
+        summary.1 += bytes;
+    }
+    Ok(summary)
+}
+
+/// Count every file and directory below `dir`, not including `dir` itself
+pub fn count_entries(dir: &Path) -> io::Result<u64> {
+    tree_summary(dir).map(|(count, _)| count - 1)
+}
+
+/// Discard a file or a directory tree the user may want back
+///
+/// On Windows the item goes to the Recycle Bin unless `permanently` is set.
+/// Volumes without a Recycle Bin (network shares, some removable drives)
+/// delete it permanently either way.
+///
+/// ### Parameters
+/// * `path` - Item to discard
+/// * `permanently` - Skip the Recycle Bin
+pub(crate) fn discard(path: &Path, permanently: bool) -> io::Result<()> {
+    if permanently || !cfg!(windows) {
+        remove_any(path)
+    } else {
+        recycle(path)
+    }
+}
+
+/// Move an item to the Recycle Bin without any dialog
+#[cfg(windows)]
+fn recycle(path: &Path) -> io::Result<()> {
+    use std::os::windows::ffi::OsStrExt;
+    use winapi::um::shellapi::{
+        SHFileOperationW, FOF_ALLOWUNDO, FOF_NOCONFIRMATION, FOF_NOERRORUI, FOF_SILENT, FO_DELETE,
+        SHFILEOPSTRUCTW,
+    };
+
+    // The shell neither understands the extended-length prefix nor relative paths
+    let path = crate::long_path::strip_extended(&std::path::absolute(path)?);
+    // A list of paths, terminated by an empty one
+    let from: Vec<u16> = path.as_os_str().encode_wide().chain([0, 0]).collect();
+    let mut operation = SHFILEOPSTRUCTW {
+        hwnd: std::ptr::null_mut(),
+        wFunc: FO_DELETE as u32,
+        pFrom: from.as_ptr(),
+        pTo: std::ptr::null(),
+        fFlags: FOF_ALLOWUNDO | FOF_NOCONFIRMATION | FOF_NOERRORUI | FOF_SILENT,
+        fAnyOperationsAborted: 0,
+        hNameMappings: std::ptr::null_mut(),
+        lpszProgressTitle: std::ptr::null(),
+    };
+    let result = unsafe { SHFileOperationW(&mut operation) };
+    if result != 0 || operation.fAnyOperationsAborted != 0 {
+        // SHFileOperation returns its own codes, not Win32 errors
+        return Err(io::Error::other(format!(
+            "Moving {} to the Recycle Bin failed ({:#x})",
+            path.display(),
+            result
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(not(windows))]
+fn recycle(path: &Path) -> io::Result<()> {
+    remove_any(path)
+}
+
+/// Remove a file or a directory tree
+fn remove_any(path: &Path) -> io::Result<()> {
+    if path.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
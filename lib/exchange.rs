@@ -1,9 +1,24 @@
 use std::{
-    env,
+    cell::RefCell,
+    env, fs,
     path::{Path, PathBuf},
+    sync::OnceLock,
 };
 
-use crate::types::{GetPathInfo, NameExchange, RenameError, DEBUG_MODE};
+use crate::copy_move::{backup_items, check_copy_space, count_entries, discard};
+use crate::last_error;
+use crate::log::{log_debug, log_error, log_info};
+use crate::long_path::{has_trimmed_name, strip_extended, to_extended};
+use crate::operation_log;
+use crate::types::{
+    ExchangeOptions, ExchangeOutcome, FileInfos, GetPathInfo, NameCase, NameExchange, RenameError,
+};
+use crate::unicode::to_nfc;
+
+thread_local! {
+    /// Last successful exchange on this thread, used by `undo_last_exchange`
+    static LAST_EXCHANGE: RefCell<Option<NameExchange>> = const { RefCell::new(None) };
+}
 
 /// Swap names of two files or directories
 ///
@@ -12,36 +27,423 @@ use crate::types::{GetPathInfo, NameExchange, RenameError, DEBUG_MODE};
 /// * `path2` - Second file or directory path
 ///
 /// ### Return Value
-/// * `Ok(())` - Successfully swapped
+/// * `Ok(ExchangeOutcome)` - Successfully swapped, with the new paths of `path1` and `path2`
 /// * `Err(RenameError)` - Error information
-pub fn exchange_paths(path1: PathBuf, path2: PathBuf) -> Result<(), RenameError> {
-    let base_dir = resolve_base_dir()?;
+pub fn exchange_paths(path1: PathBuf, path2: PathBuf) -> Result<ExchangeOutcome, RenameError> {
+    exchange_paths_with(path1, path2, &ExchangeOptions::default())
+}
+
+/// Swap names of two files or directories with optional behavior
+///
+/// ### Parameters
+/// * `path1` - First file or directory path
+/// * `path2` - Second file or directory path
+/// * `options` - Optional behavior of the exchange
+///
+/// ### Return Value
+/// * `Ok(ExchangeOutcome)` - Successfully swapped, with the new paths of `path1` and `path2`
+/// * `Err(RenameError)` - Error information
+pub fn exchange_paths_with(
+    path1: PathBuf,
+    path2: PathBuf,
+    options: &ExchangeOptions,
+) -> Result<ExchangeOutcome, RenameError> {
+    let base_dir = resolve_base_dir_with(options)?;
+    exchange_paths_in(path1, path2, &base_dir, options)
+}
 
-    let (exists1, path1) = resolve_path(&path1, &base_dir)?;
-    let (exists2, path2) = resolve_path(&path2, &base_dir)?;
-    if DEBUG_MODE {
-        dbg!(exists1, &path1, exists2, &path2);
+/// Swap names of two files or directories, resolving relative paths against `base_dir`
+///
+/// Batch callers resolve the base directory once and reuse it for every pair
+///
+/// ### Parameters
+/// * `path1` - First file or directory path
+/// * `path2` - Second file or directory path
+/// * `base_dir` - Base directory for relative paths
+/// * `options` - Optional behavior of the exchange
+///
+/// ### Return Value
+/// * `Ok(ExchangeOutcome)` - Successfully swapped, with the new paths of `path1` and `path2`
+/// * `Err(RenameError)` - Error information
+pub fn exchange_paths_in(
+    path1: PathBuf,
+    path2: PathBuf,
+    base_dir: &Path,
+    options: &ExchangeOptions,
+) -> Result<ExchangeOutcome, RenameError> {
+    let exchange_info = prepare_exchange_in(path1, path2, base_dir, options)?;
+    if options.dry_run {
+        log_info!("Dry run, nothing renamed");
+        return Ok(ExchangeOutcome {
+            new_path1: strip_extended(&exchange_info.f1.exchange.new_path),
+            new_path2: strip_extended(&exchange_info.f2.exchange.new_path),
+            entries: count_moved_entries(&exchange_info),
+        });
     }
-    if !exists1 || !exists2 {
-        if !exists1 {
-            eprintln!("{}", path1.display());
+    if let Some(backup_dir) = &options.backup_dir {
+        let folder = backup_items(
+            &[
+                &exchange_info.f1.exchange.original_path,
+                &exchange_info.f2.exchange.original_path,
+            ],
+            &base_dir.join(backup_dir),
+        )?;
+        log_info!("Backed up to {}", folder.display());
+    }
+    commit_exchange(exchange_info)
+}
+
+/// Execute a prepared plan and record it for `undo_last_exchange`
+///
+/// ### Parameters
+/// * `exchange_info` - Plan produced by `prepare_exchange`
+///
+/// ### Return Value
+/// * `Ok(ExchangeOutcome)` - New paths of the first and second item
+/// * `Err(RenameError)` - Error information
+pub fn commit_exchange(exchange_info: NameExchange) -> Result<ExchangeOutcome, RenameError> {
+    operation_log::run(planned_renames(&exchange_info), || {
+        commit_steps(exchange_info)
+    })
+}
+
+/// `(original, new)` path of both items of a plan
+fn planned_renames(exchange_info: &NameExchange) -> Vec<(PathBuf, PathBuf)> {
+    [&exchange_info.f1, &exchange_info.f2]
+        .iter()
+        .map(|info| {
+            (
+                info.exchange.original_path.clone(),
+                info.exchange.new_path.clone(),
+            )
+        })
+        .collect()
+}
+
+fn commit_steps(exchange_info: NameExchange) -> Result<ExchangeOutcome, RenameError> {
+    // A plan may be kept for a while (confirmation, backup), recheck what it was built on
+    GetPathInfo {
+        path1: exchange_info.f1.exchange.original_path.clone(),
+        path2: exchange_info.f2.exchange.original_path.clone(),
+    }
+    .refresh((exchange_info.f1.is_file, exchange_info.f2.is_file))?;
+
+    // Count before renaming, while the recorded paths are still valid
+    let entries = count_moved_entries(&exchange_info);
+
+    // Moved aside rather than deleted, so a failing swap can put them back
+    let set_aside = set_aside(&exchange_info)?;
+    if let Err(e) = run_exchange(&exchange_info) {
+        restore_set_aside(&exchange_info.options, &set_aside);
+        return Err(e);
+    }
+    for (path, aside) in &set_aside {
+        log_info!("Deleting existing target {}", path.display());
+        match discard(aside, exchange_info.options.delete_permanently) {
+            Ok(()) => operation_log::record(format!(
+                "Deleted existing target {}",
+                strip_extended(path).display()
+            )),
+            // The swap itself is done, only the old target is left over
+            Err(e) => log_error!(
+                "Failed to delete existing target {}, it is left at {}: {}",
+                path.display(),
+                aside.display(),
+                e
+            ),
+        }
+    }
+
+    if exchange_info.options.preserve_timestamps {
+        for (info, other) in [
+            (&exchange_info.f1, &exchange_info.f2),
+            (&exchange_info.f2, &exchange_info.f1),
+        ] {
+            let current = current_location(info, other);
+            if let Err(e) = NameExchange::restore_times(&current, &info.packed_info) {
+                log_error!(
+                    "Failed to restore timestamps of {}: {}",
+                    current.display(),
+                    e
+                );
+            }
+        }
+    }
+
+    let verified = if exchange_info.options.verify {
+        verify_on_disk(&exchange_info)
+    } else {
+        Ok(())
+    };
+
+    let outcome = ExchangeOutcome {
+        new_path1: strip_extended(&exchange_info.f1.exchange.new_path),
+        new_path2: strip_extended(&exchange_info.f2.exchange.new_path),
+        entries,
+    };
+    // The renames did happen even when verification fails, keep them undoable
+    LAST_EXCHANGE.with(|last| *last.borrow_mut() = Some(exchange_info));
+    verified.map(|_| outcome)
+}
+
+/// Temporary name an unrelated item at a target name is moved to during a swap
+pub(crate) fn aside_path(path: &Path) -> PathBuf {
+    let dir = path.parent().unwrap_or(Path::new(""));
+    NameExchange::make_name(dir, "", "", "", false, None, NameCase::Keep).0
+}
+
+/// Move the unrelated items at the target names to temporary names
+///
+/// ### Return Value
+/// * `Ok(Vec)` - `(original, temporary)` path of every item moved
+/// * `Err(RenameError)` - A move failed, the items moved before it are back in place
+fn set_aside(exchange_info: &NameExchange) -> Result<Vec<(PathBuf, PathBuf)>, RenameError> {
+    let mut moved = Vec::new();
+    for path in &exchange_info.overwritten {
+        let aside = aside_path(path);
+        log_info!("Moving existing target {} aside", path.display());
+        if let Err(e) = NameExchange::handle_rename(&exchange_info.options, path, &aside) {
+            restore_set_aside(&exchange_info.options, &moved);
+            return Err(e);
+        }
+        moved.push((path.clone(), aside));
+    }
+    Ok(moved)
+}
+
+/// Put the items moved by `set_aside` back under their names after a failed swap
+fn restore_set_aside(options: &ExchangeOptions, moved: &[(PathBuf, PathBuf)]) {
+    let moved: Vec<(&Path, &Path)> = moved
+        .iter()
+        .map(|(path, aside)| (path.as_path(), aside.as_path()))
+        .collect();
+    if !moved.is_empty() {
+        NameExchange::rollback(options, &moved);
+    }
+}
+
+/// Confirm a finished exchange by listing the affected directories again
+///
+/// Names are compared exactly, so a case-only rename is checked as well
+///
+/// ### Parameters
+/// * `exchange_info` - Plan that was just executed
+///
+/// ### Return Value
+/// * `Ok(())` - Both new names are listed and the old names are gone
+/// * `Err(RenameError::VerifyFailed)` - Listing disagrees with the reported success
+fn verify_on_disk(exchange_info: &NameExchange) -> Result<(), RenameError> {
+    let new_paths = [
+        current_location(&exchange_info.f1, &exchange_info.f2),
+        current_location(&exchange_info.f2, &exchange_info.f1),
+    ];
+    let listed = |path: &Path| match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => fs::read_dir(parent)
+            .is_ok_and(|entries| entries.flatten().any(|entry| entry.file_name() == name)),
+        _ => false,
+    };
+
+    for new_path in &new_paths {
+        if !listed(new_path) {
+            log_error!("Missing after swap: {}", new_path.display());
+            return Err(RenameError::VerifyFailed(format!(
+                "{} is missing",
+                new_path.display()
+            )));
+        }
+    }
+    for info in [&exchange_info.f1, &exchange_info.f2] {
+        let original = &info.exchange.original_path;
+        // In a plain swap an old name is reused by the other item
+        if !new_paths.contains(original) && listed(original) {
+            log_error!("Still present after swap: {}", original.display());
+            return Err(RenameError::VerifyFailed(format!(
+                "{} still exists",
+                original.display()
+            )));
         }
-        if !exists2 {
-            eprintln!("{}", path2.display());
+    }
+    Ok(())
+}
+
+/// Count the entries inside two swapped directories
+///
+/// Only applies when both items are directories. When one directory contains
+/// the other, the inner one is already included in the outer count.
+///
+/// ### Parameters
+/// * `exchange_info` - Plan produced by `prepare_exchange`
+///
+/// ### Return Value
+/// Total number of recursive entries, `0` if either item is a file or
+/// `count_entries` was not set
+fn count_moved_entries(exchange_info: &NameExchange) -> u64 {
+    if !exchange_info.options.count_entries || exchange_info.f1.is_file || exchange_info.f2.is_file
+    {
+        return 0;
+    }
+
+    let original_paths = GetPathInfo {
+        path1: exchange_info.f1.exchange.original_path.clone(),
+        path2: exchange_info.f2.exchange.original_path.clone(),
+    };
+    // Renaming a link moves nothing inside the directory it points at
+    let count = |info: &FileInfos| {
+        if info.packed_info.is_symlink || info.packed_info.is_junction {
+            0
+        } else {
+            count_entries(&info.exchange.original_path).unwrap_or_default()
         }
+    };
+    match original_paths.if_root() {
+        1 => count(&exchange_info.f1),
+        2 => count(&exchange_info.f2),
+        _ => count(&exchange_info.f1) + count(&exchange_info.f2),
+    }
+}
+
+/// Reverse the last successful exchange performed on this thread
+///
+/// Undo is another swap of the items at their current locations, giving each
+/// one back its recorded original name. Names chosen by the caller or changed
+/// in letter case are not applied again. The record is consumed once the undo
+/// succeeds, so a second call has nothing to undo
+///
+/// ### Return Value
+/// * `Ok(())` - Successfully restored the original names
+/// * `Err(RenameError::NotExists)` - Nothing to undo, or the items have since moved
+/// * `Err(RenameError)` - Other error information
+pub fn undo_last_exchange() -> Result<(), RenameError> {
+    last_error::begin();
+    let last = LAST_EXCHANGE
+        .with(|last| last.borrow_mut().take())
+        .ok_or_else(|| {
+            last_error::set_detail("No swap to undo on this thread");
+            RenameError::NotExists
+        })?;
+
+    let result = undo_exchange(&last);
+    if result.is_err() {
+        // Kept for another attempt, e.g. once a locked item is released
+        LAST_EXCHANGE.with(|kept| *kept.borrow_mut() = Some(last));
+    }
+    result
+}
+
+fn undo_exchange(last: &NameExchange) -> Result<(), RenameError> {
+    let current1 = current_location(&last.f1, &last.f2);
+    let current2 = current_location(&last.f2, &last.f1);
+    if !current1.exists() || !current2.exists() {
+        last_error::set_detail(format!(
+            "Swapped items have moved since: {}, {}",
+            strip_extended(&current1).display(),
+            strip_extended(&current2).display()
+        ));
         return Err(RenameError::NotExists);
     }
 
-    if path1 == path2 {
+    let original_name = |info: &FileInfos| {
+        info.exchange
+            .original_path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default()
+    };
+    let options = ExchangeOptions {
+        target_names: Some((original_name(&last.f1), original_name(&last.f2))),
+        name_case: NameCase::Keep,
+        ..last.options.clone()
+    };
+    let base_dir = resolve_base_dir_with(&options)?;
+    let exchange_info = prepare_exchange_in(current1, current2, &base_dir, &options)?;
+    operation_log::run(planned_renames(&exchange_info), || {
+        run_exchange(&exchange_info)
+    })
+}
+
+/// Locate an item after an exchange, accounting for a renamed ancestor
+///
+/// When one item contained the other, the inner item's recorded `new_path` still
+/// points into the ancestor's old name and has to be rebased onto its new name
+fn current_location(info: &FileInfos, other: &FileInfos) -> PathBuf {
+    match info
+        .exchange
+        .new_path
+        .strip_prefix(&other.exchange.original_path)
+    {
+        Ok(relative) if !relative.as_os_str().is_empty() => other.exchange.new_path.join(relative),
+        _ => info.exchange.new_path.clone(),
+    }
+}
+
+/// Run every validation step and compute target names without touching the disk
+///
+/// ### Parameters
+/// * `path1` - First file or directory path
+/// * `path2` - Second file or directory path
+///
+/// ### Return Value
+/// * `Ok(NameExchange)` - Fully populated exchange plan
+/// * `Err(RenameError)` - Validation failure (missing file, conflict, etc.)
+pub fn prepare_exchange(path1: PathBuf, path2: PathBuf) -> Result<NameExchange, RenameError> {
+    let base_dir = resolve_base_dir()?;
+    prepare_exchange_in(path1, path2, &base_dir, &ExchangeOptions::default())
+}
+
+/// Same as `prepare_exchange`, resolving relative paths against `base_dir`
+/// and applying `options`
+pub fn prepare_exchange_in(
+    path1: PathBuf,
+    path2: PathBuf,
+    base_dir: &Path,
+    options: &ExchangeOptions,
+) -> Result<NameExchange, RenameError> {
+    last_error::begin();
+    let (exists1, path1) = resolve_path(&path1, base_dir)?;
+    let (exists2, path2) = resolve_path(&path2, base_dir)?;
+    let (exists1, path1) = follow_link(options, exists1, path1);
+    let (exists2, path2) = follow_link(options, exists2, path2);
+    // Before the existence check, a root is never renamable whether or not it exists
+    GetPathInfo::validate_not_root(&path1)?;
+    GetPathInfo::validate_not_root(&path2)?;
+    log_debug!(
+        "Resolved paths: {} (exists: {}), {} (exists: {})",
+        path1.display(),
+        exists1,
+        path2.display(),
+        exists2
+    );
+    if !exists1 || !exists2 {
+        for (exists, path) in [(exists1, &path1), (exists2, &path2)] {
+            if !exists {
+                log_error!("Path does not exist: {}", path.display());
+                last_error::set_detail(strip_extended(path).display().to_string());
+            }
+        }
+        return Err(RenameError::NotExists);
+    }
+
+    let original_paths = GetPathInfo { path1, path2 };
+    // Text comparison misses hard links and alternative spellings of one item
+    if is_same_path(&original_paths.path1, &original_paths.path2) || original_paths.are_same_file()
+    {
+        last_error::set_detail(format!(
+            "{}, {}",
+            strip_extended(&original_paths.path1).display(),
+            strip_extended(&original_paths.path2).display()
+        ));
         return Err(RenameError::SamePath);
     }
 
+    if let Some(stem) = &options.temp_stem {
+        GetPathInfo::validate_stem(stem)?;
+    }
+
     let mut exchange_info = NameExchange::new();
+    exchange_info.options = options.clone();
     exchange_info.f1.is_exist = true;
     exchange_info.f2.is_exist = true;
 
-    let original_paths = GetPathInfo { path1, path2 };
-
     (exchange_info.f1.is_file, exchange_info.f2.is_file) = original_paths.if_file();
     (exchange_info.f1.packed_info, exchange_info.f2.packed_info) =
         original_paths.metadata_collect(exchange_info.f1.is_file, exchange_info.f2.is_file);
@@ -49,6 +451,42 @@ pub fn exchange_paths(path1: PathBuf, path2: PathBuf) -> Result<(), RenameError>
     exchange_info.f1.exchange.original_path = original_paths.path1.clone();
     exchange_info.f2.exchange.original_path = original_paths.path2.clone();
 
+    if options.refuse_linked_pair
+        && (links_to(&exchange_info.f1, &original_paths.path2)
+            || links_to(&exchange_info.f2, &original_paths.path1))
+    {
+        log_error!(
+            "One path is a link to the other: {}, {}",
+            original_paths.path1.display(),
+            original_paths.path2.display()
+        );
+        return Err(RenameError::SamePath);
+    }
+
+    for item in [&exchange_info.f1, &exchange_info.f2] {
+        check_cloud_only(options, item)?;
+    }
+
+    // Renaming the outer folder moves the inner item along, the hardest case to undo by hand
+    let (outer, inner) = match original_paths.if_root() {
+        1 => (Some(&original_paths.path1), &original_paths.path2),
+        2 => (Some(&original_paths.path2), &original_paths.path1),
+        _ => (None, &original_paths.path1),
+    };
+    if let Some(outer) = outer {
+        let detail = format!(
+            "{} contains {}",
+            strip_extended(outer).display(),
+            strip_extended(inner).display()
+        );
+        if options.refuse_nested {
+            log_error!("Refusing nested items: {}", detail);
+            return Err(RenameError::NestedItems(detail));
+        }
+        log_info!("Nested items, the tree is restructured: {}", detail);
+        exchange_info.nested = true;
+    }
+
     (
         exchange_info.f1.exchange.pre_path,
         exchange_info.f1.exchange.new_path,
@@ -56,6 +494,10 @@ pub fn exchange_paths(path1: PathBuf, path2: PathBuf) -> Result<(), RenameError>
         &exchange_info.f1.packed_info.parent_dir,
         &exchange_info.f2.packed_info.name,
         &exchange_info.f1.packed_info.ext,
+        &exchange_info.f2.packed_info.ext,
+        options.swap_extensions,
+        options.temp_stem.as_deref(),
+        options.name_case,
     );
     (
         exchange_info.f2.exchange.pre_path,
@@ -64,44 +506,349 @@ pub fn exchange_paths(path1: PathBuf, path2: PathBuf) -> Result<(), RenameError>
         &exchange_info.f2.packed_info.parent_dir,
         &exchange_info.f1.packed_info.name,
         &exchange_info.f2.packed_info.ext,
+        &exchange_info.f1.packed_info.ext,
+        options.swap_extensions,
+        options.temp_stem.as_deref(),
+        options.name_case,
     );
 
+    // Names chosen by the caller replace the swapped ones, the temporary names stay
+    if let Some((name1, name2)) = &options.target_names {
+        for (info, name) in [
+            (&mut exchange_info.f1, name1),
+            (&mut exchange_info.f2, name2),
+        ] {
+            GetPathInfo::validate_target_name(name)?;
+            info.exchange.new_path = info.packed_info.parent_dir.join(to_nfc(name));
+        }
+    } else if options.name_case != NameCase::Keep {
+        // Case mapping can change the characters of a name (`ß` uppercases to
+        // `SS`), so check the result like a chosen name
+        for info in [&exchange_info.f1, &exchange_info.f2] {
+            if let Some(name) = info.exchange.new_path.file_name() {
+                GetPathInfo::validate_target_name(&name.to_string_lossy())?;
+            }
+        }
+    }
+
+    // Chosen names, or a case transform merging `A.txt` and `a.TXT` in one directory
+    if is_same_path(
+        &exchange_info.f1.exchange.new_path,
+        &exchange_info.f2.exchange.new_path,
+    ) {
+        last_error::set_detail(
+            strip_extended(&exchange_info.f1.exchange.new_path)
+                .display()
+                .to_string(),
+        );
+        return Err(RenameError::AlreadyExists);
+    }
+
+    // Same final name on both sides, the temporary renames would only churn the disk.
+    // Compared exactly, a name differing only in case still needs the swap
+    if exchange_info.f1.exchange.new_path == exchange_info.f1.exchange.original_path
+        && exchange_info.f2.exchange.new_path == exchange_info.f2.exchange.original_path
+    {
+        return Err(RenameError::NothingToDo);
+    }
+
+    // Reject names Windows cannot create before touching the disk, so a
+    // half-swap cannot happen when only the second rename would fail
+    if cfg!(windows) {
+        GetPathInfo::validate_target(&exchange_info.f1.exchange.new_path)?;
+        GetPathInfo::validate_target(&exchange_info.f2.exchange.new_path)?;
+    }
+
+    // An existing name only conflicts when it is not one of the items, with the
+    // case rules of the target directory: `a.txt` is `A.txt` itself unless that
+    // directory is case-sensitive
     let is_conflict = |new_path: &PathBuf| {
-        new_path.exists()
-            && *new_path != exchange_info.f1.exchange.original_path
-            && *new_path != exchange_info.f2.exchange.original_path
+        // A link is there even when it points nowhere, renaming would replace it
+        fs::symlink_metadata(to_extended(new_path)).is_ok()
+            && !is_same_path(new_path, &exchange_info.f1.exchange.original_path)
+            && !is_same_path(new_path, &exchange_info.f2.exchange.original_path)
     };
 
-    if is_conflict(&exchange_info.f1.exchange.new_path)
-        || is_conflict(&exchange_info.f2.exchange.new_path)
-    {
-        return Err(RenameError::AlreadyExists);
+    let mut overwritten = Vec::new();
+    for new_path in [
+        &exchange_info.f1.exchange.new_path,
+        &exchange_info.f2.exchange.new_path,
+    ] {
+        if !is_conflict(new_path) {
+            continue;
+        }
+        // Nested items would have the conflict inside one of them
+        // A link is deleted as itself, never with the folder it points at
+        let is_dir = fs::symlink_metadata(to_extended(new_path)).is_ok_and(|meta| meta.is_dir());
+        let allowed = options.force && (!is_dir || options.force_dirs);
+        if !allowed
+            || GetPathInfo::path_is_parent(new_path, &original_paths.path1)
+            || GetPathInfo::path_is_parent(new_path, &original_paths.path2)
+        {
+            return Err(RenameError::AlreadyExists);
+        }
+        overwritten.push(new_path.clone());
     }
+    exchange_info.overwritten = overwritten;
+
+    // A half-copied directory would have to be rolled back, so refuse up front
+    exchange_info.copy_bytes = check_copy_space(
+        &[
+            (
+                &exchange_info.f1.exchange.original_path,
+                &exchange_info.f1.exchange.new_path,
+            ),
+            (
+                &exchange_info.f2.exchange.original_path,
+                &exchange_info.f2.exchange.new_path,
+            ),
+        ],
+        options.cross_volume_copy,
+    )?;
+
+    Ok(exchange_info)
+}
 
-    let mode = original_paths.if_root();
+/// Refuse a cloud-only placeholder when the options ask for it
+///
+/// A rename on the same volume works on the placeholder without downloading,
+/// only the copy fallback across volumes reads the content
+///
+/// ### Return Value
+/// * `Ok(())` - Item can be renamed
+/// * `Err(RenameError::CloudFile)` - Item is cloud-only and `refuse_cloud_only` is set
+pub(crate) fn check_cloud_only(
+    options: &ExchangeOptions,
+    info: &FileInfos,
+) -> Result<(), RenameError> {
+    if !info.packed_info.is_cloud_only {
+        return Ok(());
+    }
+    let path = &info.exchange.original_path;
+    if options.refuse_cloud_only {
+        log_error!("Refusing cloud-only item: {}", path.display());
+        return Err(RenameError::CloudFile(format!(
+            "Only available online: {}",
+            path.display()
+        )));
+    }
+    log_info!("{} is cloud-only, renaming the placeholder", path.display());
+    Ok(())
+}
+
+/// Replace a symbolic link or junction by the item it points at with `follow_symlinks`
+///
+/// ### Return Value
+/// `(exists, path)` of the item to rename, the target of a dangling link does not exist
+fn follow_link(options: &ExchangeOptions, exists: bool, path: PathBuf) -> (bool, PathBuf) {
+    if !options.follow_symlinks {
+        return (exists, path);
+    }
+    let is_link = fs::symlink_metadata(to_extended(&path))
+        .is_ok_and(|m| m.file_type().is_symlink())
+        || GetPathInfo::is_junction(&path);
+    if !is_link {
+        return (exists, path);
+    }
+    match fs::canonicalize(to_extended(&path)) {
+        Ok(target) => {
+            let target = strip_extended(&target);
+            log_debug!("Following {} to {}", path.display(), target.display());
+            (true, target)
+        }
+        Err(_) => (false, path),
+    }
+}
+
+/// Check whether an item is a symbolic link or junction resolving to `other`
+fn links_to(info: &FileInfos, other: &Path) -> bool {
+    if !(info.packed_info.is_symlink || info.packed_info.is_junction) {
+        return false;
+    }
+    match info.exchange.original_path.canonicalize() {
+        Ok(target) => is_same_path(&strip_extended(&target), &strip_extended(other)),
+        Err(_) => false,
+    }
+}
+
+/// Execute a prepared exchange plan, choosing rename order by nesting relationship
+///
+/// ### Parameters
+/// * `exchange_info` - Plan produced by `prepare_exchange`
+///
+/// ### Return Value
+/// * `Ok(())` - Successfully swapped
+/// * `Err(RenameError)` - Error information
+pub fn run_exchange(exchange_info: &NameExchange) -> Result<(), RenameError> {
+    let (_, is_nested, file1_first) = rename_order(exchange_info);
+    exchange_info.rename_each(is_nested, file1_first)
+}
+
+/// Choose how `rename_each` runs for a plan
+///
+/// ### Return Value
+/// `(mode, is_nested, file1_first)`, where `mode` is the containment found by
+/// `if_root` (`0` for siblings) and the others are the arguments of `rename_each`
+pub(crate) fn rename_order(exchange_info: &NameExchange) -> (u8, bool, bool) {
+    let original_paths = GetPathInfo {
+        path1: exchange_info.f1.exchange.original_path.clone(),
+        path2: exchange_info.f2.exchange.original_path.clone(),
+    };
+    // Siblings can never contain each other, skip the containment check for them
+    let mode = if original_paths.if_same_dir() {
+        0
+    } else {
+        original_paths.if_root()
+    };
 
-    match (exchange_info.f1.is_file, exchange_info.f2.is_file) {
-        (true, true) => NameExchange::rename_each(&exchange_info, false, true),
+    let (is_nested, file1_first) = match (exchange_info.f1.is_file, exchange_info.f2.is_file) {
+        (true, true) => (false, true),
         (false, false) => match mode {
-            1 => NameExchange::rename_each(&exchange_info, true, false),
-            2 => NameExchange::rename_each(&exchange_info, true, true),
-            _ => NameExchange::rename_each(&exchange_info, false, true),
+            1 => (true, false),
+            2 => (true, true),
+            _ => (false, true),
         },
-        (true, false) => {
-            if mode == 2 {
-                NameExchange::rename_each(&exchange_info, true, true)
+        (true, false) => (mode == 2, true),
+        (false, true) => (mode == 1, false),
+    };
+    (mode, is_nested, file1_first)
+}
+
+/// Check whether two resolved paths refer to the same location
+///
+/// Windows file systems are case-insensitive, so `C:\A.txt` and `c:\a.txt`
+/// are treated as the same file there, unless the directory holding the
+/// differing component is flagged case-sensitive
+pub(crate) fn is_same_path(path1: &Path, path2: &Path) -> bool {
+    #[cfg(windows)]
+    {
+        paths_match(path1, path2, GetPathInfo::is_case_sensitive_dir)
+    }
+
+    #[cfg(not(windows))]
+    {
+        path1 == path2
+    }
+}
+
+/// Compare two paths component by component, each with the case rules of its directory
+///
+/// Directories can be flagged case-sensitive one by one, so `dir\A.txt` and
+/// `dir\a.txt` are two files in such a directory but one file elsewhere.
+/// Drive prefixes and roots are always compared ignoring case.
+///
+/// ### Parameters
+/// * `path1` - First resolved path
+/// * `path2` - Second resolved path
+/// * `case_sensitive` - Whether names in a directory are case-sensitive, only
+///   asked for directories where the compared components differ in case
+///
+/// ### Return Value
+/// Both paths name the same location
+#[cfg_attr(not(windows), allow(dead_code))]
+pub(crate) fn paths_match(
+    path1: &Path,
+    path2: &Path,
+    case_sensitive: impl Fn(&Path) -> bool,
+) -> bool {
+    let components1: Vec<_> = path1.components().collect();
+    let components2: Vec<_> = path2.components().collect();
+    if components1.len() != components2.len() {
+        return false;
+    }
+
+    let mut dir = PathBuf::new();
+    for (a, b) in components1.iter().zip(&components2) {
+        if a != b {
+            let a = to_nfc(&a.as_os_str().to_string_lossy());
+            let b = to_nfc(&b.as_os_str().to_string_lossy());
+            let same = if dir.file_name().is_some() && case_sensitive(&dir) {
+                a == b
             } else {
-                NameExchange::rename_each(&exchange_info, false, true)
+                a.to_lowercase() == b.to_lowercase()
+            };
+            if !same {
+                return false;
             }
         }
-        (false, true) => {
-            if mode == 1 {
-                NameExchange::rename_each(&exchange_info, true, false)
-            } else {
-                NameExchange::rename_each(&exchange_info, false, false)
+        dir.push(a);
+    }
+    true
+}
+
+/// Expand `%VAR%` references to environment variables
+///
+/// Only variables that actually exist are expanded, so a real file name that
+/// contains a percent sign (e.g. `100%.txt` or `%NOT_SET%.txt`) is left untouched
+///
+/// ### Parameters
+/// * `path` - Path text possibly containing `%VAR%` references
+///
+/// ### Return Value
+/// Path text with every known variable replaced by its value
+pub fn expand_env_vars(path: &str) -> String {
+    let mut result = String::with_capacity(path.len());
+    let mut rest = path;
+
+    while let Some(start) = rest.find('%') {
+        let after = &rest[start + 1..];
+        let Some(len) = after.find('%') else {
+            break;
+        };
+
+        let name = &after[..len];
+        match env::var(name) {
+            Ok(value) if !name.is_empty() => {
+                result.push_str(&rest[..start]);
+                result.push_str(&value);
+                rest = &after[len + 1..];
+            }
+            _ => {
+                // Keep the first '%' literally, the second one may open a real variable
+                result.push_str(&rest[..start + 1]);
+                rest = after;
+            }
+        }
+    }
+
+    result.push_str(rest);
+    result
+}
+
+/// Collapse every run of repeated separators into a single one
+///
+/// ### Parameters
+/// * `path` - Path text whose separators are already unified to `sep`
+/// * `sep` - Separator character
+/// * `keep_unc` - Keep a leading double separator (UNC `\\server\share` or `\\?\` prefix)
+///
+/// ### Return Value
+/// Path text without repeated separators
+pub fn collapse_separators(path: &str, sep: char, keep_unc: bool) -> String {
+    let mut result = String::with_capacity(path.len());
+    let mut chars = path.chars().peekable();
+
+    if keep_unc && path.starts_with(sep) && path[sep.len_utf8()..].starts_with(sep) {
+        result.push(sep);
+        result.push(sep);
+        while chars.peek() == Some(&sep) {
+            chars.next();
+        }
+    }
+
+    let mut last_was_sep = false;
+    for c in chars {
+        if c == sep {
+            if !last_was_sep {
+                result.push(c);
             }
+            last_was_sep = true;
+        } else {
+            result.push(c);
+            last_was_sep = false;
         }
     }
+    result
 }
 
 /// Resolve base directory path
@@ -109,21 +856,38 @@ pub fn exchange_paths(path1: PathBuf, path2: PathBuf) -> Result<(), RenameError>
 /// ### Return Value
 /// * `Ok(PathBuf)` - Base directory path
 /// * `Err(RenameError)` - Resolution failure
-fn resolve_base_dir() -> Result<PathBuf, RenameError> {
+pub fn resolve_base_dir() -> Result<PathBuf, RenameError> {
     // Prefer current working directory over executable directory
     if let Ok(cwd) = env::current_dir() {
         return Ok(cwd);
     }
 
-    if let Ok(exe) = env::current_exe() {
-        if let Some(parent) = exe.parent() {
-            return Ok(parent.to_path_buf());
-        }
+    exe_dir()
+        .map(Path::to_path_buf)
+        .ok_or_else(|| RenameError::Unknown("Failed to resolve working directory".to_string()))
+}
+
+/// Resolve the base directory requested by `options`, see `ExchangeOptions::base_dir`
+///
+/// ### Return Value
+/// * `Ok(PathBuf)` - `options.base_dir` when given, otherwise `resolve_base_dir`
+/// * `Err(RenameError)` - Resolution failure
+pub fn resolve_base_dir_with(options: &ExchangeOptions) -> Result<PathBuf, RenameError> {
+    match &options.base_dir {
+        Some(base_dir) => Ok(base_dir.clone()),
+        None => resolve_base_dir(),
     }
+}
 
-    Err(RenameError::Unknown(
-        "Failed to resolve working directory".to_string(),
-    ))
+/// Directory of the running executable, looked up once per process
+///
+/// The executable cannot move while running, so batch callers can hit this
+/// fallback for every pair without repeating the lookup
+fn exe_dir() -> Option<&'static Path> {
+    static EXE_DIR: OnceLock<Option<PathBuf>> = OnceLock::new();
+    EXE_DIR
+        .get_or_init(|| Some(env::current_exe().ok()?.parent()?.to_path_buf()))
+        .as_deref()
 }
 
 /// Resolve and normalize path
@@ -139,6 +903,12 @@ pub fn resolve_path(path: &Path, base_dir: &Path) -> Result<(bool, PathBuf), Ren
     if *path == *"" {
         return Ok((false, path.to_path_buf()));
     }
+    // Only reachable through the Rust interface, a C string ends at its first NUL.
+    // The system would truncate or reject such a path, never rename what was meant
+    let lossy = path.to_string_lossy();
+    if lossy.contains('\0') {
+        return Err(RenameError::EmbeddedNul(lossy.replace('\0', "\\0")));
+    }
 
     let mut path = path.to_path_buf();
 
@@ -156,22 +926,19 @@ pub fn resolve_path(path: &Path, base_dir: &Path) -> Result<(bool, PathBuf), Ren
                     ))
                 })?
                 .replace("/", "\\");
-            PathBuf::from(temp)
+            let temp = expand_env_vars(&temp);
+            PathBuf::from(collapse_separators(&temp, '\\', true))
         };
 
         let is_absolute = {
             let mut components = path.components();
             if let Some(Component::Prefix(prefix_component)) = components.next() {
                 let has_root_dir = matches!(components.next(), Some(Component::RootDir));
-                if DEBUG_MODE {
-                    dbg!(has_root_dir);
-                }
+                log_debug!("Path has root dir: {}", has_root_dir);
                 if !has_root_dir {
                     false
                 } else {
-                    if DEBUG_MODE {
-                        dbg!(prefix_component.kind());
-                    }
+                    log_debug!("Path prefix: {:?}", prefix_component.kind());
 
                     matches!(
                         prefix_component.kind(),
@@ -232,7 +999,8 @@ pub fn resolve_path(path: &Path, base_dir: &Path) -> Result<(bool, PathBuf), Ren
                     ))
                 })?
                 .replace("\\", "/");
-            PathBuf::from(temp)
+            let temp = expand_env_vars(&temp);
+            PathBuf::from(collapse_separators(&temp, '/', false))
         };
 
         if !path.is_absolute() {
@@ -264,15 +1032,29 @@ pub fn resolve_path(path: &Path, base_dir: &Path) -> Result<(bool, PathBuf), Ren
             }
         }
     }
-    if DEBUG_MODE {
-        println!("Checked Path: {}", &path.display());
+    log_debug!("Checked Path: {}", path.display());
+
+    // Without the prefix Windows would look up the name with its trailing dots
+    // and spaces trimmed, which can be a different item
+    if cfg!(windows) && has_trimmed_name(&path.to_string_lossy()) {
+        path = to_extended(&path);
+    }
+
+    // A decomposed spelling of a composed name on disk finds nothing as typed
+    if fs::symlink_metadata(&path).is_err() {
+        let composed = PathBuf::from(to_nfc(&path.to_string_lossy()));
+        if composed != path && fs::symlink_metadata(&composed).is_ok() {
+            log_debug!("Using composed form {}", composed.display());
+            path = composed;
+        }
     }
 
-    let canonical = path.canonicalize();
+    // A link is kept as is, so the swap renames the link and not its target
+    let canonical = GetPathInfo::canonicalize_entry(&path);
     match canonical {
-        Ok(x) => Ok((x.exists(), x)),
+        Ok(x) => Ok((fs::symlink_metadata(&x).is_ok(), x)),
         Err(e) => {
-            eprintln!("{}", e);
+            log_debug!("Canonicalize failed for {}: {}", path.display(), e);
             Ok((path.exists(), path))
         }
     }
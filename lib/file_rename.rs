@@ -1,7 +1,18 @@
 use std::path::{Path, PathBuf};
 
+use std::time::Duration;
+
+use crate::last_error;
+use crate::log::{log_debug, log_error, log_info};
+use crate::long_path::{strip_extended, to_extended};
+use crate::operation_log;
 use crate::types::*;
 
+/// Delay before the first retry of a locked rename, doubled on each further attempt
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(100);
+/// Longest delay between two attempts, so a long lock does not stall a batch
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(2);
+
 /// Main rename logic integration
 impl NameExchange {
     /// Initialize structure for storing all information
@@ -15,6 +26,10 @@ impl NameExchange {
             f2: FileInfos {
                 ..Default::default()
             },
+            options: ExchangeOptions::default(),
+            overwritten: Vec::new(),
+            copy_bytes: 0,
+            nested: false,
         }
     }
 
@@ -26,6 +41,11 @@ impl NameExchange {
     /// * `dir` - Directory path where file is located
     /// * `other_name` - Target filename (without extension)
     /// * `ext` - File extension (including leading dot ".")
+    /// * `other_ext` - Extension of the other item, used instead of `ext` with `swap_ext`
+    /// * `swap_ext` - Give the final path the other item's extension, so the whole
+    ///   file name moves instead of only the stem
+    /// * `stem` - Caller supplied temporary stem, `None` to generate a random one
+    /// * `case` - Letter case of the final name, the temporary name is left alone
     ///
     /// ### Return Value
     /// Returns tuple `(temporary file path, final file path)`
@@ -33,25 +53,36 @@ impl NameExchange {
         dir: &Path,
         other_name: impl ToString,
         ext: impl ToString,
+        other_ext: impl ToString,
+        swap_ext: bool,
+        stem: Option<&str>,
+        case: NameCase,
     ) -> (PathBuf, PathBuf) {
         let other_name = other_name.to_string();
         let ext = ext.to_string();
+        let final_ext = if swap_ext {
+            other_ext.to_string()
+        } else {
+            ext.clone()
+        };
         let mut final_path = dir.to_path_buf();
 
         // Generate unique temporary filename, avoid conflicts with existing files
-        let base_temp = crate::types::GUID;
-        let mut temp_path = dir.join(format!("{}{}", base_temp, ext));
-        let mut counter = 0u64;
-        while temp_path.exists() {
+        let mut temp_path = match stem {
+            Some(stem) => dir.join(format!("{}{}", stem, ext)),
+            None => dir.join(format!("{}{}", Self::temp_stem(), ext)),
+        };
+        let mut counter = 1;
+        while to_extended(&temp_path).exists() {
+            temp_path = match stem {
+                // Keep the supplied prefix so scanner exclusions still match
+                Some(stem) => dir.join(format!("{}_{}{}", stem, counter, ext)),
+                None => dir.join(format!("{}{}", Self::temp_stem(), ext)),
+            };
             counter += 1;
-            temp_path = dir.join(format!("{}_{}{}", base_temp, counter, ext));
         }
 
-        let final_component = if ext.is_empty() {
-            other_name
-        } else {
-            format!("{}{}", other_name, ext)
-        };
+        let final_component = case.apply(&other_name, &final_ext);
 
         if !final_component.is_empty() {
             final_path.push(final_component);
@@ -60,9 +91,31 @@ impl NameExchange {
         (temp_path, final_path)
     }
 
+    /// Generate a random temporary file stem
+    ///
+    /// Combines the current timestamp with a randomly seeded hash, so the stem
+    /// differs on every call and cannot be predicted by an existing file name
+    fn temp_stem() -> String {
+        use std::{
+            collections::hash_map::RandomState,
+            hash::{BuildHasher, Hasher},
+            time::{SystemTime, UNIX_EPOCH},
+        };
+
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or_default();
+        let random = RandomState::new().build_hasher().finish();
+        format!("{:X}{:016X}", nanos, random)
+    }
+
     /// Rename execution part
     ///
-    /// Execute rename operation based on file type and nesting relationship
+    /// Execute rename operation based on file type and nesting relationship.
+    /// A symbolic link or junction is renamed itself, the rename never
+    /// dereferences it, and the copy fallback across volumes recreates it
+    /// rather than copying its target
     ///
     /// ### Parameters
     /// * `is_nested` - Whether there is a nesting relationship (such as parent-child directories)
@@ -71,68 +124,376 @@ impl NameExchange {
     /// ### Return Value
     /// Returns `Ok(())` for success, `Err(RenameError)` for corresponding failure reason
     pub fn rename_each(&self, is_nested: bool, file1_first: bool) -> Result<(), RenameError> {
-        // Prepare path variables according to rename order
-        let mut path1 = self.f2.exchange.original_path.clone();
-        let mut final_name1 = self.f2.exchange.new_path.clone();
-        let mut path2 = self.f1.exchange.original_path.clone();
-        let mut final_name2 = self.f1.exchange.new_path.clone();
-        let mut tmp_name2 = self.f1.exchange.pre_path.clone();
-        if file1_first {
-            path1 = self.f1.exchange.original_path.clone();
-            final_name1 = self.f1.exchange.new_path.clone();
-            path2 = self.f2.exchange.original_path.clone();
-            final_name2 = self.f2.exchange.new_path.clone();
-            tmp_name2 = self.f2.exchange.pre_path.clone();
+        let options = &self.options;
+        if is_nested {
+            operation_log::record("Nested items, renamed in place without a temporary name:");
+        } else {
+            operation_log::record("Swapped through a temporary name:");
         }
 
-        if is_nested {
-            // If there is a nesting relationship (parent-child directories or files),
-            // rename directly in order
-            // Do not use temporary files, as using temporary files in nesting relationships
-            // may cause path issues
-            Self::handle_rename(&path1, &final_name1)?;
-            if let Err(e) = Self::handle_rename(&path2, &final_name2) {
-                // Rollback step 1
-                let _ = Self::handle_rename(&final_name1, &path1);
+        let steps = self.rename_steps(is_nested, file1_first);
+        for (index, (from, to)) in steps.iter().enumerate() {
+            if let Err(e) = Self::handle_rename(options, from, to) {
+                // Undo the completed steps in reverse order
+                let done: Vec<(&Path, &Path)> = steps[..index]
+                    .iter()
+                    .map(|(from, to)| (from.as_path(), to.as_path()))
+                    .collect();
+                if !done.is_empty() {
+                    Self::rollback(options, &done);
+                }
+                if !is_nested && index > 0 {
+                    let (original, temp) = &steps[0];
+                    Self::report_stranded(temp, original);
+                }
                 return Err(e);
             }
-            Ok(())
+        }
+        Ok(())
+    }
+
+    /// Renames `rename_each` performs, in execution order
+    ///
+    /// With a nesting relationship (parent-child directories) both items are
+    /// renamed directly in order, a temporary name inside the moving tree could
+    /// get lost. Otherwise the item renamed second is first moved to its
+    /// temporary name, the other item takes its final name, and the temporary
+    /// name is renamed to the final one.
+    ///
+    /// ### Parameters
+    /// * `is_nested` - Whether there is a nesting relationship
+    /// * `file1_first` - Whether the first file takes its final name first
+    ///
+    /// ### Return Value
+    /// `(from, to)` of every rename
+    pub(crate) fn rename_steps(
+        &self,
+        is_nested: bool,
+        file1_first: bool,
+    ) -> Vec<(PathBuf, PathBuf)> {
+        let (first, second) = if file1_first {
+            (&self.f1.exchange, &self.f2.exchange)
         } else {
-            // No nesting relationship: use temporary files for safe swapping
-            // 1. Rename the second file to temporary file
-            // 2. Rename the first file to final name
-            // 3. Rename the temporary file to final name
-            Self::handle_rename(&path2, &tmp_name2)?;
-
-            if let Err(e) = Self::handle_rename(&path1, &final_name1) {
-                // Rollback step 1: restore path2
-                let _ = Self::handle_rename(&tmp_name2, &path2);
-                return Err(e);
-            }
+            (&self.f2.exchange, &self.f1.exchange)
+        };
 
-            if let Err(e) = Self::handle_rename(&tmp_name2, &final_name2) {
-                // Rollback steps 1 & 2: restore both files
-                let _ = Self::handle_rename(&final_name1, &path1);
-                let _ = Self::handle_rename(&tmp_name2, &path2);
-                return Err(e);
+        if is_nested {
+            vec![
+                (first.original_path.clone(), first.new_path.clone()),
+                (second.original_path.clone(), second.new_path.clone()),
+            ]
+        } else {
+            vec![
+                (second.original_path.clone(), second.pre_path.clone()),
+                (first.original_path.clone(), first.new_path.clone()),
+                (second.pre_path.clone(), second.new_path.clone()),
+            ]
+        }
+    }
+
+    /// Undo already completed rename steps in reverse order
+    ///
+    /// A failed restore is reported instead of silently ignored, so the user
+    /// knows where the file was left
+    ///
+    /// ### Parameters
+    /// * `options` - Retry and temporary name settings
+    /// * `steps` - Completed `(from, to)` renames, in execution order
+    pub(crate) fn rollback(options: &ExchangeOptions, steps: &[(&Path, &Path)]) {
+        // After a cancellation the completed steps still have to be undone
+        let options = &ExchangeOptions {
+            cancel: None,
+            ..options.clone()
+        };
+        operation_log::record("Rolling back:");
+        for (from, to) in steps.iter().rev() {
+            if let Err(e) = Self::handle_rename(options, to, from) {
+                log_error!(
+                    "Rollback failed, {} is left at {}: {}",
+                    from.display(),
+                    to.display(),
+                    e
+                );
             }
+        }
+    }
 
-            Ok(())
+    /// Report a temporary file that could not be moved back to its original name
+    ///
+    /// ### Parameters
+    /// * `temp` - Temporary path used during the swap
+    /// * `original` - Path the item had before the swap
+    pub(crate) fn report_stranded(temp: &Path, original: &Path) {
+        if temp.exists() {
+            log_error!(
+                "Manual recovery needed: {} is stranded under the temporary name {}",
+                original.display(),
+                temp.display()
+            );
         }
     }
 
+    /// Reapply recorded timestamps to an item at its new location
+    ///
+    /// ### Parameters
+    /// * `path` - Current path of the renamed file or directory
+    /// * `info` - Metadata captured before the swap
+    ///
+    /// ### Return Value
+    /// Returns `Ok(())` for success, `Err(RenameError)` for specific error
+    pub fn restore_times(path: &Path, info: &MetadataCollection) -> Result<(), RenameError> {
+        let mut times = std::fs::FileTimes::new();
+        if let Some(modified) = info.modified {
+            times = times.set_modified(modified);
+        }
+        if let Some(accessed) = info.accessed {
+            times = times.set_accessed(accessed);
+        }
+
+        #[cfg(windows)]
+        let file = {
+            use std::os::windows::fs::{FileTimesExt, OpenOptionsExt};
+
+            const FILE_WRITE_ATTRIBUTES: u32 = 0x0100;
+            // Required to open a directory handle
+            const FILE_FLAG_BACKUP_SEMANTICS: u32 = 0x0200_0000;
+
+            if let Some(created) = info.created {
+                times = times.set_created(created);
+            }
+            std::fs::OpenOptions::new()
+                .access_mode(FILE_WRITE_ATTRIBUTES)
+                .custom_flags(FILE_FLAG_BACKUP_SEMANTICS)
+                .open(to_extended(path))?
+        };
+
+        #[cfg(not(windows))]
+        let file = std::fs::File::open(path)?;
+
+        file.set_times(times).map_err(RenameError::from)
+    }
+
     /// Handle single rename operation and process possible errors
     ///
     /// ### Parameters
+    /// * `options` - Retry and temporary name settings
     /// * `from` - Original file path
     /// * `to` - Target file path
     ///
     /// ### Return Value
     /// Returns `Ok(())` for success, `Err(RenameError)` for specific error
-    fn handle_rename(from: &Path, to: &Path) -> Result<(), RenameError> {
-        match std::fs::rename(from, to) {
-            Ok(_) => Ok(()),
-            Err(e) => Err(RenameError::from(e)),
+    pub(crate) fn handle_rename(
+        options: &ExchangeOptions,
+        from: &Path,
+        to: &Path,
+    ) -> Result<(), RenameError> {
+        // Paths near MAX_PATH only work with the extended-length prefix
+        let from = &to_extended(from);
+        let to = &to_extended(to);
+
+        if options.create_parents {
+            if let Some(parent) = to.parent().filter(|parent| !parent.exists()) {
+                log_info!("Creating missing directory {}", parent.display());
+                std::fs::create_dir_all(parent).inspect_err(|e| {
+                    last_error::set_detail(format!("Creating {}: {}", parent.display(), e))
+                })?;
+            }
+        }
+
+        match Self::rename_path(options, from, to) {
+            // The read-only attribute blocks the copy fallback and some directory
+            // renames on Windows; on other platforms it is unrelated to the error
+            Err(RenameError::PermissionDenied) if cfg!(windows) && Self::is_readonly(from) => {
+                Self::rename_writable(options, from, to)
+            }
+            result => result,
+        }
+    }
+
+    /// Rename a path, routing case-only changes through a temporary name
+    fn rename_path(options: &ExchangeOptions, from: &Path, to: &Path) -> Result<(), RenameError> {
+        // A case-only rename can be a no-op on case-insensitive volumes,
+        // so hop through a temporary name to make the new casing land
+        if Self::is_case_only(from, to) {
+            let parent = from.parent().unwrap_or(Path::new(""));
+            let (temp, _) = Self::make_name(
+                parent,
+                "",
+                "",
+                "",
+                false,
+                options.temp_stem.as_deref(),
+                NameCase::Keep,
+            );
+            Self::raw_rename(options, from, &temp)?;
+            return Self::raw_rename(options, &temp, to).inspect_err(|_| {
+                let _ = Self::raw_rename(options, &temp, from);
+                Self::report_stranded(&temp, from);
+            });
+        }
+
+        Self::raw_rename(options, from, to)
+    }
+
+    /// Retry a rename with the read-only attribute cleared
+    ///
+    /// The attribute is restored wherever the item ends up, even if the retry fails
+    fn rename_writable(
+        options: &ExchangeOptions,
+        from: &Path,
+        to: &Path,
+    ) -> Result<(), RenameError> {
+        Self::set_readonly(from, false)?;
+        let result = Self::rename_path(options, from, to);
+
+        let landed = if result.is_ok() { to } else { from };
+        if let Err(e) = Self::set_readonly(landed, true) {
+            log_error!(
+                "Failed to restore the read-only attribute of {}: {}",
+                landed.display(),
+                e
+            );
+        }
+        result
+    }
+
+    /// Check if a path carries the read-only attribute
+    fn is_readonly(path: &Path) -> bool {
+        std::fs::metadata(path)
+            .map(|metadata| metadata.permissions().readonly())
+            .unwrap_or(false)
+    }
+
+    /// Set or clear the read-only attribute of a path
+    fn set_readonly(path: &Path, readonly: bool) -> Result<(), RenameError> {
+        let mut permissions = std::fs::metadata(path)?.permissions();
+        // Only reached on Windows, where this toggles the attribute instead of
+        // making the file world-writable
+        #[allow(clippy::permissions_set_readonly_false)]
+        permissions.set_readonly(readonly);
+        std::fs::set_permissions(path, permissions).map_err(RenameError::from)
+    }
+
+    /// Check if two paths differ only by letter case
+    pub(crate) fn is_case_only(from: &Path, to: &Path) -> bool {
+        from != to && from.to_string_lossy().to_lowercase() == to.to_string_lossy().to_lowercase()
+    }
+
+    /// Rename once, falling back to copy then delete across volumes
+    ///
+    /// A transient lock (antivirus scan, thumbnail indexer) is retried with
+    /// exponential backoff up to `options.rename_attempts` times in total,
+    /// capped at `MAX_RENAME_ATTEMPTS` and `MAX_RETRY_DELAY` between attempts
+    fn raw_rename(options: &ExchangeOptions, from: &Path, to: &Path) -> Result<(), RenameError> {
+        let attempts = options.rename_attempts.min(MAX_RENAME_ATTEMPTS);
+        let mut delay = RETRY_BASE_DELAY;
+        let mut attempt = 1;
+        loop {
+            match std::fs::rename(from, to) {
+                Ok(_) => {
+                    operation_log::record_rename(from, to, false);
+                    return Ok(());
+                }
+                // Rename cannot move between volumes, fall back to copy then delete
+                Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => {
+                    // Normally refused while preparing, unless the volumes could not be told apart
+                    if !options.cross_volume_copy {
+                        let detail = format!(
+                            "{} → {}",
+                            strip_extended(from).display(),
+                            strip_extended(to).display()
+                        );
+                        last_error::set_detail(format!("Renaming {}: {}", detail, e));
+                        return Err(RenameError::CrossesVolumes(detail));
+                    }
+                    crate::copy_move::move_by_copy(from, to, options.cancel.as_deref())
+                        .inspect_err(|err| {
+                            last_error::set_detail(format!(
+                                "Moving {} to {} across volumes: {}",
+                                strip_extended(from).display(),
+                                strip_extended(to).display(),
+                                err
+                            ))
+                        })?;
+                    operation_log::record_rename(from, to, true);
+                    return Ok(());
+                }
+                Err(e) if attempt < attempts && Self::is_transient(&e) => {
+                    log_debug!(
+                        "Rename of {} failed ({}), retrying in {:?}",
+                        from.display(),
+                        e,
+                        delay
+                    );
+                    std::thread::sleep(delay);
+                    delay = (delay * 2).min(MAX_RETRY_DELAY);
+                    attempt += 1;
+                }
+                Err(e) => {
+                    let mut detail = format!(
+                        "Renaming {} to {}: {}",
+                        strip_extended(from).display(),
+                        strip_extended(to).display(),
+                        e
+                    );
+                    if e.kind() == std::io::ErrorKind::PermissionDenied {
+                        let holders = crate::lock_info::locking_processes(from);
+                        if !holders.is_empty() {
+                            log_error!("{} is locked by {}", from.display(), holders.join(", "));
+                            detail.push_str(&format!(", locked by {}", holders.join(", ")));
+                        }
+                    }
+                    last_error::set_detail(detail);
+                    return Err(RenameError::from(e));
+                }
+            }
+        }
+    }
+
+    /// Check if a rename error is likely caused by another process briefly holding the file
+    ///
+    /// Other denials, such as a missing permission or a read-only item, fail the
+    /// same way on every attempt and are not retried
+    pub(crate) fn is_transient(err: &std::io::Error) -> bool {
+        // ERROR_SHARING_VIOLATION and ERROR_LOCK_VIOLATION
+        const SHARING_VIOLATION: i32 = 32;
+        const LOCK_VIOLATION: i32 = 33;
+
+        cfg!(windows) && matches!(err.raw_os_error(), Some(SHARING_VIOLATION | LOCK_VIOLATION))
+    }
+}
+
+impl NameCase {
+    /// Join a stem and extension into a file name in this letter case
+    ///
+    /// Words of a titlecased stem start after any character that is neither a
+    /// letter, a digit nor an apostrophe, so `don't_stop` becomes `Don't_Stop`
+    ///
+    /// ### Parameters
+    /// * `stem` - Name without extension
+    /// * `ext` - Extension including the leading dot, may be empty
+    ///
+    /// ### Return Value
+    /// File name in this case
+    pub(crate) fn apply(self, stem: &str, ext: &str) -> String {
+        match self {
+            NameCase::Keep => format!("{}{}", stem, ext),
+            NameCase::Lower => format!("{}{}", stem, ext).to_lowercase(),
+            NameCase::Upper => format!("{}{}", stem, ext).to_uppercase(),
+            NameCase::Title => {
+                let mut name = String::with_capacity(stem.len() + ext.len());
+                let mut word_start = true;
+                for c in stem.chars() {
+                    if word_start {
+                        name.extend(c.to_uppercase());
+                    } else {
+                        name.extend(c.to_lowercase());
+                    }
+                    word_start = !(c.is_alphanumeric() || c == '\'');
+                }
+                name.push_str(&ext.to_lowercase());
+                name
+            }
         }
     }
 }
@@ -0,0 +1,73 @@
+use std::{
+    cell::RefCell,
+    ffi::{c_char, CString},
+    ptr,
+};
+
+use crate::types::RenameError;
+
+thread_local! {
+    /// Message of the last failure reported through the FFI on this thread
+    static MESSAGE: RefCell<Option<CString>> = const { RefCell::new(None) };
+    /// Context recorded where the running operation failed, e.g. the `io::Error`
+    /// text and the paths involved, waiting for the error to be reported
+    static DETAIL: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Drop the context of an earlier operation, call when a new operation starts
+///
+/// Failures swallowed on purpose (e.g. a batch reporting through a callback)
+/// must not leak their context into the next reported error
+pub(crate) fn begin() {
+    DETAIL.with(|detail| detail.borrow_mut().take());
+}
+
+/// Record where the running operation failed
+///
+/// Only the first context is kept, so a failing rollback does not hide the
+/// failure that caused it
+///
+/// ### Parameters
+/// * `context` - Description of the failing step, with the paths involved
+pub(crate) fn set_detail(context: impl Into<String>) {
+    DETAIL.with(|detail| {
+        detail.borrow_mut().get_or_insert_with(|| context.into());
+    });
+}
+
+/// Context recorded for the running or last failing operation, without consuming it
+pub(crate) fn detail() -> Option<String> {
+    DETAIL.with(|detail| detail.borrow().clone())
+}
+
+/// Store an error returned through the FFI as the last error of this thread
+///
+/// ### Parameters
+/// * `err` - Error returned to the caller
+///
+/// ### Return Value
+/// Code of the error returned to the caller
+pub(crate) fn report(err: &RenameError) -> i32 {
+    let message = match DETAIL.with(|detail| detail.borrow_mut().take()) {
+        Some(detail) => format!("{}: {}", err, detail),
+        None => err.to_string(),
+    };
+    let message = CString::new(message.replace('\0', " ")).ok();
+    MESSAGE.with(|last| *last.borrow_mut() = message);
+    err.to_code()
+}
+
+/// Forget the last error of this thread
+pub(crate) fn clear() {
+    begin();
+    MESSAGE.with(|last| last.borrow_mut().take());
+}
+
+/// Pointer to the last error message of this thread
+///
+/// ### Return Value
+/// NUL-terminated UTF-8 message, null when nothing failed since the start or
+/// the last `clear`. Valid until the next failure or `clear` on this thread
+pub(crate) fn message_ptr() -> *const c_char {
+    MESSAGE.with(|last| last.borrow().as_ref().map_or(ptr::null(), |m| m.as_ptr()))
+}
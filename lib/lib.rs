@@ -1,13 +1,40 @@
 use std::ffi::{c_char, CStr};
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
+mod copy_move;
 mod exchange;
 mod file_rename;
+mod last_error;
+mod lock_info;
+mod log;
+mod long_path;
+mod operation_log;
 mod path_checkout;
+mod pattern;
+mod plan;
+mod progress;
+mod rotate;
 mod types;
+mod unicode;
 
-use crate::exchange::{exchange_paths, resolve_path};
-use crate::types::RenameError;
+use crate::exchange::{
+    commit_exchange, exchange_paths, exchange_paths_in, exchange_paths_with, prepare_exchange,
+    prepare_exchange_in, resolve_base_dir, resolve_base_dir_with, resolve_path, undo_last_exchange,
+};
+use crate::log::{log_error, log_info};
+pub use crate::log::{LogCallback, LogLevel};
+use crate::long_path::strip_extended;
+pub use crate::progress::ProgressCallback;
+use crate::rotate::rotate_paths_in;
+pub use crate::types::{
+    CancelToken, ExchangeOptions, ExchangeOutcome, ExchangePlan, MetadataCollection, NameCase,
+    Pair, PatternPairs, PlannedStep, RenameError, StepKind, DEFAULT_RENAME_ATTEMPTS,
+    FLAG_CREATE_PARENTS, FLAG_DELETE_PERMANENTLY, FLAG_DRY_RUN, FLAG_FOLLOW_SYMLINKS, FLAG_FORCE,
+    FLAG_FORCE_DIRS, FLAG_LOWERCASE, FLAG_PRESERVE_TIMESTAMPS, FLAG_REFUSE_CLOUD_ONLY,
+    FLAG_REFUSE_CROSS_VOLUME, FLAG_REFUSE_LINKED_PAIR, FLAG_REFUSE_NESTED, FLAG_SWAP_EXTENSIONS,
+    FLAG_TITLECASE, FLAG_UPPERCASE, FLAG_VERIFY, MAX_RENAME_ATTEMPTS,
+};
 
 #[no_mangle]
 /// # Safety
@@ -24,130 +51,2743 @@ use crate::types::RenameError;
 /// * `3` - Target file already exists
 /// * `4` - Two paths refer to the same file
 /// * `5` - Invalid path (e.g. non-UTF-8)
+/// * `6` - Null pointer passed for `path1` or `path2`
+/// * `8` - Cross-volume copy fallback could not verify the copied data
+/// * `9` - Target name is a Windows reserved device name (`CON`, `NUL`, `COM1`, ...)
+/// * `10` - Nothing to do, both names are already equal (e.g. `a.txt` and `a.log`)
+/// * `11` - Backup requested through `exchange_backup` failed, nothing was renamed
+/// * `12` - Verification requested with `FLAG_VERIFY` found the swap not reflected on disk
+/// * `13` - Cancelled through `cancel_token_cancel`, the original names were restored
+/// * `14` - Target name has characters Windows forbids (`<>:"/\\|?*`) or is too long
+/// * `15` - Cloud-only item refused with `FLAG_REFUSE_CLOUD_ONLY`, or the cloud provider
+///   (e.g. OneDrive) blocked the operation
+/// * `16` - One of the paths is a drive or volume root (e.g. `C:\`), which cannot be renamed
+/// * `17` - A path passed to the Rust interface contains a NUL character
+/// * `18` - A rename would copy across volumes and the target volume lacks the space,
+///   nothing was renamed
+/// * `19` - `flags` has bits this version of the library does not know, or more than
+///   one letter case flag
+/// * `20` - A target is on another volume and `FLAG_REFUSE_CROSS_VOLUME` was given
+/// * `21` - One item is a folder containing the other and `FLAG_REFUSE_NESTED` was given
+/// * `22` - An item was replaced by a file or directory of the other type after the swap
+///   was planned, nothing was renamed
 /// * `255` - Unknown error
+///
+/// Paths are read up to their first NUL byte and must be UTF-8. Hosts holding
+/// native Windows paths, which may not be valid UTF-8, should call `exchange_w`
+/// instead of converting them
+///
+/// Same as `exchange_ex` without flags
 pub unsafe extern "C" fn exchange(path1: *const c_char, path2: *const c_char) -> i32 {
-    unsafe { convert_inputs(path1, path2) }
-        .and_then(|(path1, path2)| exchange_paths(path1, path2))
+    unsafe { exchange_ex(path1, path2, 0) }
+}
+
+#[no_mangle]
+/// # Safety
+/// C interface function for swapping names of two files or directories with option flags
+///
+/// ### Parameters
+/// * `path1` - First file or directory path (C string pointer)
+/// * `path2` - Second file or directory path (C string pointer)
+/// * `flags` - Bitwise OR of option flags:
+///   * `FLAG_PRESERVE_TIMESTAMPS` (`0x1`) - Restore the original created/modified/accessed times
+///   * `FLAG_REFUSE_LINKED_PAIR` (`0x2`) - Return `4` when one path is a symbolic link or
+///     junction pointing at the other, instead of renaming the link
+///   * `FLAG_VERIFY` (`0x4`) - List the directories again after the swap, return `12` when
+///     the new names are missing or the old ones remain
+///   * `FLAG_CREATE_PARENTS` (`0x8`) - Create a missing parent directory of a target before
+///     renaming into it, instead of failing
+///   * `FLAG_FORCE` (`0x10`) - Delete an unrelated file already using a target name instead of
+///     returning `3`
+///   * `FLAG_FORCE_DIRS` (`0x20`) - Together with `FLAG_FORCE`, also delete an unrelated
+///     directory already using a target name
+///   * `FLAG_DELETE_PERMANENTLY` (`0x40`) - Delete what `FLAG_FORCE` removes permanently
+///     instead of moving it to the Recycle Bin
+///   * `FLAG_REFUSE_CLOUD_ONLY` (`0x80`) - Return `15` instead of touching an item whose
+///     content is only stored online, which a copy across volumes would download
+///   * `FLAG_SWAP_EXTENSIONS` (`0x100`) - Swap whole file names including the extensions,
+///     instead of swapping the stems and leaving each extension in place
+///   * `FLAG_FOLLOW_SYMLINKS` (`0x200`) - Swap the names of the items symbolic links and
+///     junctions point at, instead of the names of the links
+///   * `FLAG_DRY_RUN` (`0x400`) - Run every check and return the code the swap would
+///     return, without renaming anything
+///   * `FLAG_REFUSE_CROSS_VOLUME` (`0x800`) - Return `20` instead of moving an item whose
+///     target is on another volume by copying it and deleting the original
+///   * `FLAG_LOWERCASE` (`0x1000`), `FLAG_UPPERCASE` (`0x2000`), `FLAG_TITLECASE` (`0x4000`) -
+///     Change the letter case of both new names, only one of the three can be given.
+///     Titlecasing lowercases the extension
+///   * `FLAG_REFUSE_NESTED` (`0x8000`) - Return `21` instead of swapping a folder with an
+///     item inside it, which moves the inner item along with the renamed folder
+///
+/// ### Return Value
+/// Same codes as `exchange`, `19` for bits not listed above or several letter case flags
+pub unsafe extern "C" fn exchange_ex(
+    path1: *const c_char,
+    path2: *const c_char,
+    flags: u32,
+) -> i32 {
+    ExchangeOptions::try_from_flags(flags)
+        .and_then(|options| {
+            let (path1, path2) = unsafe { convert_inputs(path1, path2) }?;
+            exchange_paths_with(path1, path2, &options)
+        })
         .map(|_| {
-            println!("Success");
+            log_info!("Success");
             0
         })
         .unwrap_or_else(|err| {
-            eprintln!("{}", err);
-            err.to_code()
+            log_error!("{}", err);
+            last_error::report(&err)
         })
 }
 
-/// Rust interface function for swapping names of two files or directories
+#[no_mangle]
+/// # Safety
+/// C interface function for copying two files or directories into a backup
+/// folder and then swapping their names
 ///
 /// ### Parameters
-/// * `path1` - First file or directory path
-/// * `path2` - Second file or directory path
+/// * `path1` - First file or directory path (C string pointer)
+/// * `path2` - Second file or directory path (C string pointer)
+/// * `backup_dir` - Directory receiving a new timestamped folder with the copies (C string pointer)
+/// * `flags` - Same option flags as `exchange_ex`
 ///
 /// ### Return Value
-/// * `Ok(())` - Success
-/// * `Err(RenameError)` - Error information
-pub fn exchange_rs(path1: &Path, path2: &Path) -> Result<(), types::RenameError> {
-    match exchange_paths(path1.to_path_buf(), path2.to_path_buf()) {
-        Ok(_) => {
-            println!("Success");
-            Ok(())
-        }
-        Err(err) => {
-            eprintln!("{}", err);
-            Err(err)
-        }
+/// Same codes as `exchange`, `11` when the backup failed and the swap was not attempted
+pub unsafe extern "C" fn exchange_backup(
+    path1: *const c_char,
+    path2: *const c_char,
+    backup_dir: *const c_char,
+    flags: u32,
+) -> i32 {
+    let backup_dir = match unsafe { ptr_to_path(backup_dir) } {
+        Ok(dir) => dir,
+        Err(err) => return last_error::report(&err),
+    };
+    ExchangeOptions::try_from_flags(flags)
+        .and_then(|options| {
+            let options = ExchangeOptions {
+                backup_dir: Some(backup_dir),
+                ..options
+            };
+            let (path1, path2) = unsafe { convert_inputs(path1, path2) }?;
+            exchange_paths_with(path1, path2, &options)
+        })
+        .map(|_| {
+            log_info!("Success");
+            0
+        })
+        .unwrap_or_else(|err| {
+            log_error!("{}", err);
+            last_error::report(&err)
+        })
+}
+
+#[no_mangle]
+/// C interface function for creating a token that cancels `exchange_cancellable`
+///
+/// ### Return Value
+/// Opaque handle, released with `cancel_token_free`
+pub extern "C" fn cancel_token_new() -> *const CancelToken {
+    Arc::into_raw(Arc::new(CancelToken::default()))
+}
+
+#[no_mangle]
+/// # Safety
+/// C interface function for cancelling the exchange running with a token,
+/// callable from any thread. Does nothing for a null handle.
+///
+/// ### Parameters
+/// * `token` - Handle from `cancel_token_new` that was not freed yet
+pub unsafe extern "C" fn cancel_token_cancel(token: *const CancelToken) {
+    if let Some(token) = unsafe { token.as_ref() } {
+        token.cancel();
     }
 }
 
-/// Resolve and normalize path
+#[no_mangle]
+/// # Safety
+/// C interface function for releasing a token. An exchange still using it keeps
+/// its own reference. Does nothing for a null handle.
 ///
 /// ### Parameters
-/// * `path` - Original path
-/// * `base_dir` - Base directory path
+/// * `token` - Handle from `cancel_token_new`, not used again afterwards
+pub unsafe extern "C" fn cancel_token_free(token: *const CancelToken) {
+    if !token.is_null() {
+        drop(unsafe { Arc::from_raw(token) });
+    }
+}
+
+#[no_mangle]
+/// # Safety
+/// C interface function for swapping names of two files or directories, stoppable
+/// from another thread while a move across volumes copies data
+///
+/// ### Parameters
+/// * `path1` - First file or directory path (C string pointer)
+/// * `path2` - Second file or directory path (C string pointer)
+/// * `flags` - Same option flags as `exchange_ex`
+/// * `token` - Handle from `cancel_token_new`, or null for no cancellation
 ///
 /// ### Return Value
-/// * `Ok((bool, PathBuf))` - Tuple of (is_path_exists, normalized_path)
-/// * `Err(RenameError)` - Path resolution failure
-pub fn resolve_path_rs(
-    path: &Path,
-    base_dir: &Path,
-) -> Result<(bool, PathBuf), types::RenameError> {
-    resolve_path(path, base_dir)
+/// Same codes as `exchange`, `13` when cancelled after everything was moved back
+pub unsafe extern "C" fn exchange_cancellable(
+    path1: *const c_char,
+    path2: *const c_char,
+    flags: u32,
+    token: *const CancelToken,
+) -> i32 {
+    let cancel = (!token.is_null()).then(|| unsafe {
+        // Take a reference of our own, the caller may free its handle meanwhile
+        Arc::increment_strong_count(token);
+        Arc::from_raw(token)
+    });
+    ExchangeOptions::try_from_flags(flags)
+        .and_then(|options| {
+            let options = ExchangeOptions { cancel, ..options };
+            let (path1, path2) = unsafe { convert_inputs(path1, path2) }?;
+            exchange_paths_with(path1, path2, &options)
+        })
+        .map(|_| {
+            log_info!("Success");
+            0
+        })
+        .unwrap_or_else(|err| {
+            log_error!("{}", err);
+            last_error::report(&err)
+        })
 }
 
-unsafe fn convert_inputs(
+#[no_mangle]
+/// # Safety
+/// C interface function for renaming two files or directories to names of the
+/// caller's choice, with the same temporary names and rollback as a swap
+///
+/// ### Parameters
+/// * `path1` - First file or directory path (C string pointer)
+/// * `path2` - Second file or directory path (C string pointer)
+/// * `name1` - New file name of `path1`, without directory (C string pointer)
+/// * `name2` - New file name of `path2`, without directory (C string pointer)
+/// * `flags` - Same option flags as `exchange_ex`
+///
+/// ### Return Value
+/// Same codes as `exchange_ex`, `14` when a name is not a single legal file name
+pub unsafe extern "C" fn exchange_named(
     path1: *const c_char,
     path2: *const c_char,
-) -> Result<(PathBuf, PathBuf), RenameError> {
-    let path1 = ptr_to_path(path1)?;
-    let path2 = ptr_to_path(path2)?;
-    Ok((path1, path2))
+    name1: *const c_char,
+    name2: *const c_char,
+    flags: u32,
+) -> i32 {
+    ExchangeOptions::try_from_flags(flags)
+        .and_then(|options| {
+            let (path1, path2) = unsafe { convert_inputs(path1, path2) }?;
+            let name1 = unsafe { ptr_to_name(name1) }?;
+            let name2 = unsafe { ptr_to_name(name2) }?;
+            exchange_named_rs(&path1, &path2, &name1, &name2, &options)
+        })
+        .map(|_| {
+            log_info!("Success");
+            0
+        })
+        .unwrap_or_else(|err| {
+            log_error!("{}", err);
+            last_error::report(&err)
+        })
 }
 
-unsafe fn ptr_to_path(ptr: *const c_char) -> Result<PathBuf, RenameError> {
-    if ptr.is_null() {
-        return Err(RenameError::NotExists);
+#[cfg(windows)]
+#[no_mangle]
+/// # Safety
+/// C interface function for swapping names of two files or directories,
+/// taking native Windows UTF-16 paths
+///
+/// ### Parameters
+/// * `path1` - First file or directory path (NUL-terminated UTF-16 pointer)
+/// * `path2` - Second file or directory path (NUL-terminated UTF-16 pointer)
+///
+/// ### Return Value
+/// Same codes as `exchange`
+pub unsafe extern "C" fn exchange_w(path1: *const u16, path2: *const u16) -> i32 {
+    let inputs = unsafe { wide_ptr_to_path(path1) }
+        .and_then(|path1| unsafe { wide_ptr_to_path(path2) }.map(|path2| (path1, path2)));
+    match inputs {
+        Ok((path1, path2)) => exchange_impl(path1, path2),
+        Err(err) => last_error::report(&err),
     }
+}
 
-    let c_str = CStr::from_ptr(ptr);
-    let raw = c_str.to_string_lossy();
-    let sanitized = sanitize_input(raw.as_ref());
-
-    if sanitized.is_empty() {
-        return Err(RenameError::NotExists);
+#[no_mangle]
+/// # Safety
+/// C interface function for swapping names of many pairs in one call
+///
+/// Every pair is processed even if an earlier one fails, and the status code of
+/// each pair (same codes as `exchange`) is written to the matching slot of `results`.
+///
+/// ### Parameters
+/// * `pairs` - Array of `len` path pairs
+/// * `len` - Number of pairs
+/// * `results` - Caller-allocated array of `len` status codes
+///
+/// ### Return Value
+/// * `0` - All pairs swapped successfully
+/// * `n > 0` - Number of pairs that failed
+/// * `-1` - `pairs` or `results` is null
+pub unsafe extern "C" fn exchange_many(pairs: *const Pair, len: usize, results: *mut i32) -> i32 {
+    if len == 0 {
+        return 0;
+    }
+    if pairs.is_null() || results.is_null() {
+        return -1;
     }
 
-    Ok(PathBuf::from(sanitized))
-}
+    let pairs = std::slice::from_raw_parts(pairs, len);
+    let results = std::slice::from_raw_parts_mut(results, len);
 
-fn sanitize_input(input: &str) -> String {
-    input
-        .trim()
-        .trim_matches(|c| c == '"' || c == '\'')
-        .to_string()
+    let base_dir = match resolve_base_dir() {
+        Ok(dir) => dir,
+        Err(err) => {
+            results.fill(last_error::report(&err));
+            return len as i32;
+        }
+    };
+
+    let mut failed = 0;
+    for (pair, result) in pairs.iter().zip(results.iter_mut()) {
+        *result = match unsafe { convert_inputs(pair.path1, pair.path2) } {
+            Ok((path1, path2)) => {
+                match exchange_paths_in(path1, path2, &base_dir, &ExchangeOptions::default()) {
+                    Ok(_) => 0,
+                    Err(err) => {
+                        log_error!("{}", err);
+                        last_error::report(&err)
+                    }
+                }
+            }
+            Err(err) => last_error::report(&err),
+        };
+        if *result != 0 {
+            failed += 1;
+        }
+    }
+    failed
 }
 
-#[cfg(test)]
-mod tests {
-    use std::{
-        fs::{self, remove_file},
-        path::PathBuf,
+#[no_mangle]
+/// # Safety
+/// C interface function for cyclically shifting names among several items
+///
+/// Item `i` takes the name of item `i + 1` and the last item takes the name of the
+/// first (A→B→C→A). Two items behave exactly like `exchange`.
+///
+/// ### Parameters
+/// * `paths` - Array of `len` C string pointers, in rotation order
+/// * `len` - Number of paths, at least 2
+///
+/// ### Return Value
+/// Same codes as `exchange`, `5` if fewer than two paths are given
+pub unsafe extern "C" fn exchange_rotate(paths: *const *const c_char, len: usize) -> i32 {
+    if paths.is_null() {
+        return last_error::report(&RenameError::NullPointer);
+    }
+    if len < 2 {
+        let err = RenameError::InvalidPath("Rotation needs at least two items".to_string());
+        return last_error::report(&err);
+    }
+
+    let pointers = std::slice::from_raw_parts(paths, len);
+    let paths = match pointers
+        .iter()
+        .map(|&ptr| unsafe { ptr_to_path(ptr) })
+        .collect::<Result<Vec<_>, _>>()
+    {
+        Ok(paths) => paths,
+        Err(err) => return last_error::report(&err),
     };
 
-    fn clear_olds() -> (PathBuf, PathBuf) {
-        let current_exe = std::env::current_exe().unwrap();
-        let base_dir = current_exe.parent().unwrap();
-        let _ = std::env::set_current_dir(base_dir);
+    if let [path1, path2] = paths.as_slice() {
+        return exchange_impl(path1.clone(), path2.clone());
+    }
 
-        let file1 = "1.ext1";
-        let file2 = "2.ext2";
-        let file3 = "1.ext2";
+    resolve_base_dir()
+        .and_then(|base_dir| rotate_paths_in(paths, &base_dir, &ExchangeOptions::default()))
+        .map(|_| {
+            log_info!("Success");
+            0
+        })
+        .unwrap_or_else(|err| {
+            log_error!("{}", err);
+            last_error::report(&err)
+        })
+}
 
-        let exchanged_file1 = "2.ext1";
-        let exchanged_file2 = "1.ext2";
+#[no_mangle]
+/// C interface function for reversing the last successful swap on the calling thread
+///
+/// ### Return Value
+/// * `0` - Success
+/// * `1` - Nothing to undo, or the swapped items have since moved
+/// * Other codes - Same as `exchange`
+pub extern "C" fn undo_last() -> i32 {
+    match undo_last_exchange() {
+        Ok(_) => 0,
+        Err(err) => {
+            log_error!("{}", err);
+            last_error::report(&err)
+        }
+    }
+}
 
-        let _ = remove_file(exchanged_file1);
-        let _ = remove_file(exchanged_file2);
-        let _ = remove_file(file3);
-        let _ = fs::File::create(file3);
-        let _ = fs::File::create(file1);
-        let _ = fs::File::create(file2);
+#[no_mangle]
+/// # Safety
+/// C interface function for reading what the last exchange on the calling thread did
+///
+/// Lines are separated by `\n`: first `old → new` for every item, or the error
+/// when it failed, then the branch taken and every rename performed, including
+/// temporary names, rollbacks and copies across volumes. A swap rejected before
+/// renaming anything leaves the previous summary in place
+///
+/// ### Parameters
+/// * `buffer` - Buffer receiving the summary as a NUL-terminated UTF-8 string
+/// * `len` - Size of `buffer` in bytes, including the trailing NUL
+///
+/// ### Return Value
+/// * `0` - Success, the summary is empty before the first exchange
+/// * `6` - `buffer` is null
+/// * `7` - Buffer is too small, nothing is written
+pub unsafe extern "C" fn last_operation_log(buffer: *mut c_char, len: usize) -> i32 {
+    if buffer.is_null() {
+        return last_error::report(&RenameError::NullPointer);
+    }
+    let summary = operation_log::last_operation_log();
+    if !fits_buffer(buffer, len, &summary) {
+        return last_error::report(&RenameError::BufferTooSmall);
+    }
+    unsafe { write_buffer(buffer, &summary) };
+    0
+}
 
-        (PathBuf::from(file1), PathBuf::from(file2))
+#[no_mangle]
+/// # Safety
+/// C interface function for listing every step a swap would take, without renaming anything
+///
+/// Writes the plan as one JSON object, e.g.
+/// `{"mode":0,"nested":false,"file1_first":true,"new_path1":"C:\\b.txt","new_path2":"C:\\a.txt",
+/// "steps":[{"action":"rename","from":"C:\\b.txt","to":"C:\\7F3A….txt"},…]}`.
+/// `mode` is the containment of the items (`1` the first contains the second,
+/// `2` the reverse), `nested` the branch renaming in place without a temporary
+/// name. Each step's `action` is `backup`, `delete`, `create_dir`, `rename`,
+/// `case_rename` or `copy_across_volumes`; `delete` and `create_dir` have no `to`.
+/// Temporary names are examples, a real swap picks fresh ones
+///
+/// ### Parameters
+/// * `path1` - First file or directory path (C string pointer)
+/// * `path2` - Second file or directory path (C string pointer)
+/// * `flags` - Option flags as for `exchange_ex`
+/// * `buffer` - Buffer receiving the plan as a NUL-terminated UTF-8 string
+/// * `len` - Size of `buffer` in bytes, including the trailing NUL
+///
+/// ### Return Value
+/// Same codes as `exchange_ex`, plus:
+/// * `6` - `buffer` is null
+/// * `7` - Buffer is too small, nothing is written
+pub unsafe extern "C" fn exchange_plan(
+    path1: *const c_char,
+    path2: *const c_char,
+    flags: u32,
+    buffer: *mut c_char,
+    len: usize,
+) -> i32 {
+    if buffer.is_null() {
+        return last_error::report(&RenameError::NullPointer);
     }
+    ExchangeOptions::try_from_flags(flags)
+        .and_then(|options| {
+            let (path1, path2) = unsafe { convert_inputs(path1, path2) }?;
+            exchange_plan_rs(&path1, &path2, &options)
+        })
+        .and_then(|plan| {
+            let json = plan.to_json();
+            if !fits_buffer(buffer, len, &json) {
+                return Err(RenameError::BufferTooSmall);
+            }
+            unsafe { write_buffer(buffer, &json) };
+            Ok(0)
+        })
+        .unwrap_or_else(|err| last_error::report(&err))
+}
 
-    #[test]
-    fn it_works() {
-        let (file1, file2) = clear_olds();
+#[no_mangle]
+/// C interface function for reading why the last failing call on the calling thread failed
+///
+/// Complements the numeric codes with the underlying system error and the
+/// paths involved, e.g. `Permission denied: Renaming C:\a.txt to C:\b.txt:
+/// Access is denied. (os error 5)`. A successful call keeps the message of an
+/// earlier failure, use `clear_last_error` to reset it
+///
+/// ### Return Value
+/// NUL-terminated UTF-8 message, null when nothing failed yet. Owned by the
+/// library and valid until the next failing call or `clear_last_error` on
+/// the same thread, never freed by the caller
+pub extern "C" fn last_error_message() -> *const c_char {
+    last_error::message_ptr()
+}
 
-        match super::exchange_rs(&file1, &file2) {
-            Ok(_) => {
-                println!("Success");
-            }
-            Err(e) => {
-                eprintln!("{}", e);
+#[no_mangle]
+/// C interface function for forgetting the last error of the calling thread
+pub extern "C" fn clear_last_error() {
+    last_error::clear();
+}
+
+#[no_mangle]
+/// C interface function for receiving library log messages
+///
+/// Logging is silent by default. Passing a null callback disables it again.
+///
+/// ### Parameters
+/// * `callback` - Function called with the level and a NUL-terminated UTF-8 message
+pub extern "C" fn register_log_callback(callback: Option<LogCallback>) {
+    log::set_log_callback(callback);
+}
+
+#[no_mangle]
+/// C interface function for querying the version of the library
+///
+/// ### Return Value
+/// Static NUL-terminated crate version such as `1.0.0`, never freed by the caller
+pub extern "C" fn lib_version() -> *const c_char {
+    concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr() as *const c_char
+}
+
+#[no_mangle]
+/// C interface function for querying how the library was built
+///
+/// ### Return Value
+/// Static NUL-terminated target triple and build profile separated by a space,
+/// such as `x86_64-pc-windows-msvc release`, never freed by the caller
+pub extern "C" fn build_info() -> *const c_char {
+    concat!(env!("BUILD_TARGET"), " ", env!("BUILD_PROFILE"), "\0").as_ptr() as *const c_char
+}
+
+#[no_mangle]
+/// C interface function for receiving progress of cross-volume copies
+///
+/// Only the copy fallback used when a rename crosses volumes reports progress.
+/// Nothing is measured while no callback is registered. Passing a null
+/// callback disables it again.
+///
+/// ### Parameters
+/// * `callback` - Function called with the bytes copied so far and the total bytes
+pub extern "C" fn register_progress_callback(callback: Option<ProgressCallback>) {
+    progress::set_progress_callback(callback);
+}
+
+#[no_mangle]
+/// # Safety
+/// C interface function for swapping names of two files or directories,
+/// reporting how many entries the swapped directories contained
+///
+/// ### Parameters
+/// * `path1` - First file or directory path (C string pointer)
+/// * `path2` - Second file or directory path (C string pointer)
+/// * `entries` - Receives the total recursive entry count of both directories
+///   (`0` unless both paths are directories), may be null to skip
+///
+/// ### Return Value
+/// Same codes as `exchange`
+pub unsafe extern "C" fn exchange_counted(
+    path1: *const c_char,
+    path2: *const c_char,
+    entries: *mut u64,
+) -> i32 {
+    // Only walked when the caller asked for the count
+    let options = ExchangeOptions {
+        count_entries: !entries.is_null(),
+        ..Default::default()
+    };
+    unsafe { convert_inputs(path1, path2) }
+        .and_then(|(path1, path2)| exchange_paths_with(path1, path2, &options))
+        .map(|outcome| {
+            if !entries.is_null() {
+                unsafe { *entries = outcome.entries };
             }
-        };
+            log_info!("Success");
+            0
+        })
+        .unwrap_or_else(|err| {
+            log_error!("{}", err);
+            last_error::report(&err)
+        })
+}
+
+/// Shared swap pipeline behind every FFI entry point, returning the final code
+fn exchange_impl(path1: PathBuf, path2: PathBuf) -> i32 {
+    exchange_paths(path1, path2)
+        .map(|_| {
+            log_info!("Success");
+            0
+        })
+        .unwrap_or_else(|err| {
+            log_error!("{}", err);
+            last_error::report(&err)
+        })
+}
+
+#[no_mangle]
+/// # Safety
+/// C interface function for previewing a swap without touching the disk
+///
+/// Runs every validation step of `exchange` and writes the two resulting paths
+/// into caller-allocated buffers as NUL-terminated UTF-8 strings.
+///
+/// ### Parameters
+/// * `path1` - First file or directory path (C string pointer)
+/// * `path2` - Second file or directory path (C string pointer)
+/// * `out1` - Buffer receiving the new path of `path1`, may be null to skip
+/// * `out1_len` - Size of `out1` in bytes, including the trailing NUL
+/// * `out2` - Buffer receiving the new path of `path2`, may be null to skip
+/// * `out2_len` - Size of `out2` in bytes, including the trailing NUL
+///
+/// ### Return Value
+/// Same codes as `exchange`, plus:
+/// * `7` - An output buffer is too small; neither buffer is written
+pub unsafe extern "C" fn preview(
+    path1: *const c_char,
+    path2: *const c_char,
+    out1: *mut c_char,
+    out1_len: usize,
+    out2: *mut c_char,
+    out2_len: usize,
+) -> i32 {
+    unsafe { convert_inputs(path1, path2) }
+        .and_then(|(path1, path2)| preview_paths(path1, path2))
+        .and_then(|(new1, new2)| unsafe {
+            let new1 = new1.to_string_lossy();
+            let new2 = new2.to_string_lossy();
+            if !fits_buffer(out1, out1_len, &new1) || !fits_buffer(out2, out2_len, &new2) {
+                return Err(RenameError::BufferTooSmall);
+            }
+            write_buffer(out1, &new1);
+            write_buffer(out2, &new2);
+            Ok(0)
+        })
+        .unwrap_or_else(|err| last_error::report(&err))
+}
+
+#[no_mangle]
+/// # Safety
+/// C interface function for swapping names of two files or directories,
+/// reporting the resulting paths
+///
+/// On success the two new paths are written into caller-allocated buffers as
+/// NUL-terminated UTF-8 strings. Buffer sizes are checked before anything is
+/// renamed, so a too-small buffer never leaves a swap the caller cannot report.
+///
+/// ### Parameters
+/// * `path1` - First file or directory path (C string pointer)
+/// * `path2` - Second file or directory path (C string pointer)
+/// * `out1` - Buffer receiving the new path of `path1`, may be null to skip
+/// * `out1_len` - Size of `out1` in bytes, including the trailing NUL
+/// * `out2` - Buffer receiving the new path of `path2`, may be null to skip
+/// * `out2_len` - Size of `out2` in bytes, including the trailing NUL
+///
+/// ### Return Value
+/// Same codes as `exchange`, plus:
+/// * `7` - An output buffer is too small; nothing is renamed and neither buffer is written
+pub unsafe extern "C" fn exchange_result(
+    path1: *const c_char,
+    path2: *const c_char,
+    out1: *mut c_char,
+    out1_len: usize,
+    out2: *mut c_char,
+    out2_len: usize,
+) -> i32 {
+    unsafe { convert_inputs(path1, path2) }
+        .and_then(|(path1, path2)| prepare_exchange(path1, path2))
+        .and_then(|exchange_info| {
+            let new1 = strip_extended(&exchange_info.f1.exchange.new_path);
+            let new1 = new1.to_string_lossy();
+            let new2 = strip_extended(&exchange_info.f2.exchange.new_path);
+            let new2 = new2.to_string_lossy();
+            if !fits_buffer(out1, out1_len, &new1) || !fits_buffer(out2, out2_len, &new2) {
+                return Err(RenameError::BufferTooSmall);
+            }
+            commit_exchange(exchange_info)?;
+            unsafe {
+                write_buffer(out1, &new1);
+                write_buffer(out2, &new2);
+            }
+            Ok(0)
+        })
+        .unwrap_or_else(|err| {
+            log_error!("{}", err);
+            last_error::report(&err)
+        })
+}
+
+/// Rust interface function for swapping names of two files or directories
+///
+/// ### Parameters
+/// * `path1` - First file or directory path
+/// * `path2` - Second file or directory path
+///
+/// ### Return Value
+/// * `Ok(())` - Success
+/// * `Err(RenameError)` - Error information
+pub fn exchange_rs(path1: &Path, path2: &Path) -> Result<(), types::RenameError> {
+    match exchange_paths(path1.to_path_buf(), path2.to_path_buf()) {
+        Ok(_) => {
+            log_info!("Success");
+            Ok(())
+        }
+        Err(err) => {
+            log_error!("{}", err);
+            Err(err)
+        }
+    }
+}
+
+/// Rust interface function for swapping names of two files or directories,
+/// reporting the new paths and moved entry count
+///
+/// ### Parameters
+/// * `path1` - First file or directory path
+/// * `path2` - Second file or directory path
+///
+/// ### Return Value
+/// * `Ok(ExchangeOutcome)` - Success
+/// * `Err(RenameError)` - Error information
+pub fn exchange_outcome_rs(path1: &Path, path2: &Path) -> Result<ExchangeOutcome, RenameError> {
+    let options = ExchangeOptions {
+        count_entries: true,
+        ..Default::default()
+    };
+    exchange_paths_with(path1.to_path_buf(), path2.to_path_buf(), &options)
+}
+
+/// Rust interface function for swapping names of two files or directories with optional behavior
+///
+/// ### Parameters
+/// * `path1` - First file or directory path
+/// * `path2` - Second file or directory path
+/// * `options` - Optional behavior of the exchange
+///
+/// ### Return Value
+/// * `Ok(ExchangeOutcome)` - Success
+/// * `Err(RenameError)` - Error information
+pub fn exchange_with_rs(
+    path1: &Path,
+    path2: &Path,
+    options: &ExchangeOptions,
+) -> Result<ExchangeOutcome, RenameError> {
+    exchange_paths_with(path1.to_path_buf(), path2.to_path_buf(), options)
+}
+
+/// Rust interface function for renaming two files or directories to names of the
+/// caller's choice, with the same temporary names and rollback as a swap
+///
+/// Each item stays in its directory. A name may be the current name of the
+/// other item, so `a`, `b` renamed to `b`, `c` works like a partial swap
+///
+/// ### Parameters
+/// * `path1` - First file or directory path
+/// * `path2` - Second file or directory path
+/// * `name1` - New file name of `path1`, extension included
+/// * `name2` - New file name of `path2`, extension included
+/// * `options` - Optional behavior of the exchange, `target_names` is replaced
+///
+/// ### Return Value
+/// * `Ok(ExchangeOutcome)` - Success
+/// * `Err(RenameError)` - Error information, `IllegalName` for a name that is not a
+///   single legal file name
+pub fn exchange_named_rs(
+    path1: &Path,
+    path2: &Path,
+    name1: &str,
+    name2: &str,
+    options: &ExchangeOptions,
+) -> Result<ExchangeOutcome, RenameError> {
+    let options = ExchangeOptions {
+        target_names: Some((name1.to_string(), name2.to_string())),
+        ..options.clone()
+    };
+    exchange_paths_with(path1.to_path_buf(), path2.to_path_buf(), &options)
+}
+
+/// Rust interface function for cyclically shifting names among three or more items
+///
+/// ### Parameters
+/// * `paths` - Items in rotation order, item `i` takes the name of item `i + 1`
+///
+/// ### Return Value
+/// * `Ok(Vec<PathBuf>)` - New path of every item, in input order
+/// * `Err(RenameError)` - Error information
+pub fn rotate_rs(paths: &[PathBuf]) -> Result<Vec<PathBuf>, RenameError> {
+    let base_dir = resolve_base_dir()?;
+    rotate_paths_in(paths.to_vec(), &base_dir, &ExchangeOptions::default())
+}
+
+/// Rust interface function for swapping names of many pairs in one call
+///
+/// Every pair is processed even if an earlier one fails. Once `options.cancel`
+/// is cancelled the remaining pairs fail with `Cancelled` without being touched
+///
+/// ### Parameters
+/// * `pairs` - Path pairs in processing order
+/// * `options` - Optional behavior applied to every pair
+/// * `on_result` - Called with the index and result of every pair as soon as it is done
+///
+/// ### Return Value
+/// Number of pairs that failed
+pub fn exchange_many_rs(
+    pairs: &[(PathBuf, PathBuf)],
+    options: &ExchangeOptions,
+    mut on_result: impl FnMut(usize, &Result<ExchangeOutcome, RenameError>),
+) -> usize {
+    let base_dir = resolve_base_dir_with(options);
+    let mut failed = 0;
+    for (index, (path1, path2)) in pairs.iter().enumerate() {
+        let cancelled = options
+            .cancel
+            .as_ref()
+            .is_some_and(|cancel| cancel.is_cancelled());
+        let result = match &base_dir {
+            _ if cancelled => Err(RenameError::Cancelled),
+            Ok(base_dir) => exchange_paths_in(path1.clone(), path2.clone(), base_dir, options),
+            Err(err) => Err(err.clone()),
+        };
+        if let Err(err) = &result {
+            log_error!("{}", err);
+            failed += 1;
+        }
+        on_result(index, &result);
+    }
+    failed
+}
+
+/// Rust interface function for pairing the items matched by two wildcard patterns
+///
+/// `*` and `?` are allowed in the file name of each pattern. Items are paired
+/// when the wildcards match the same text, so `subs\*.en.srt` and `subs\*.zh.srt`
+/// pair every English subtitle with the Chinese one of the same video. Letter
+/// case is ignored where the file system ignores it. An item matched by both
+/// patterns is listed as unmatched on both sides instead of being paired. Pass
+/// the pairs to `exchange_many_rs` to swap them
+///
+/// ### Parameters
+/// * `pattern1` - Pattern of the first items, relative patterns are resolved like paths
+/// * `pattern2` - Pattern of the second items
+///
+/// ### Return Value
+/// * `Ok(PatternPairs)` - Pairs and the items of either pattern left without a partner
+/// * `Err(RenameError)` - `InvalidPath` for a wildcard in a directory name,
+///   `NotExists` for a missing directory
+pub fn pair_by_pattern_rs(pattern1: &Path, pattern2: &Path) -> Result<PatternPairs, RenameError> {
+    let base_dir = resolve_base_dir()?;
+    pattern::pair_by_pattern(pattern1, pattern2, &base_dir)
+}
+
+/// Rust interface function for reading what the last exchange on the calling thread did
+///
+/// ### Return Value
+/// Multi-line summary as described for `last_operation_log`, empty before the first exchange
+pub fn last_operation_log_rs() -> String {
+    operation_log::last_operation_log()
+}
+
+/// Rust interface function for reversing the last successful swap on the calling thread
+///
+/// ### Return Value
+/// * `Ok(())` - Success
+/// * `Err(RenameError::NotExists)` - Nothing to undo, or the swapped items have since moved
+/// * `Err(RenameError)` - Other error information
+pub fn undo_last_rs() -> Result<(), RenameError> {
+    undo_last_exchange()
+}
+
+/// Rust interface function for reading the name/extension split used by the swap
+///
+/// ### Parameters
+/// * `path` - File or directory path
+///
+/// ### Return Value
+/// `MetadataCollection` with the name, extension (`ext` keeps compound extensions
+/// like `.tar.gz` whole, `full_ext` holds everything after the first dot) and parent directory
+pub fn metadata_rs(path: &Path) -> MetadataCollection {
+    types::GetPathInfo::get_info(path, path.is_file())
+}
+
+/// Rust interface function for splitting a name into the stem and extension a swap exchanges
+///
+/// Follows the rules of the swap itself: directories keep their whole name,
+/// compound extensions like `.tar.gz` stay whole and a leading dot belongs to the stem
+///
+/// ### Parameters
+/// * `path` - File or directory path, only its last component is read
+/// * `is_file` - Path points to a file, directories are never split
+///
+/// ### Return Value
+/// `(stem, extension)`, e.g. `("a", ".tar.gz")`, `(".gitignore", "")` or `("docs.v2", "")`
+/// for a directory. The extension keeps its leading dot
+pub fn split_name_rs(path: &Path, is_file: bool) -> (String, String) {
+    types::GetPathInfo::split_name(path, is_file)
+}
+
+/// Resolve and normalize path
+///
+/// ### Parameters
+/// * `path` - Original path
+/// * `base_dir` - Base directory path
+///
+/// ### Return Value
+/// * `Ok((bool, PathBuf))` - Tuple of (is_path_exists, normalized_path)
+/// * `Err(RenameError)` - Path resolution failure
+pub fn resolve_path_rs(
+    path: &Path,
+    base_dir: &Path,
+) -> Result<(bool, PathBuf), types::RenameError> {
+    resolve_path(path, base_dir)
+}
+
+/// Rust interface function for reading where the last failing call on the calling thread failed
+///
+/// Errors like `PermissionDenied` do not say which item was refused, a swap of
+/// nested items or a rotation renames several. The detail names the failing
+/// step with its paths and the system error, as `last_error_message` does for C callers
+///
+/// ### Return Value
+/// e.g. `Renaming C:\a.txt to C:\b.txt: Access is denied. (os error 5)`, `None` when
+/// the failure was not tied to a particular rename. Cleared when the next call starts
+pub fn last_error_detail_rs() -> Option<String> {
+    last_error::detail()
+}
+
+/// Rust interface function for previewing a swap without touching the disk
+///
+/// ### Parameters
+/// * `path1` - First file or directory path
+/// * `path2` - Second file or directory path
+///
+/// ### Return Value
+/// * `Ok((PathBuf, PathBuf))` - New paths of `path1` and `path2` after the swap
+/// * `Err(RenameError)` - Same validation errors as `exchange_rs`
+pub fn preview_rs(path1: &Path, path2: &Path) -> Result<(PathBuf, PathBuf), RenameError> {
+    preview_paths(path1.to_path_buf(), path2.to_path_buf())
+}
+
+/// Rust interface function for measuring what a swap would copy across volumes
+///
+/// A rename the system refuses across volumes falls back to copying, which can
+/// take long for large directories. Lets a caller warn before starting such a swap
+///
+/// ### Parameters
+/// * `path1` - First file or directory path
+/// * `path2` - Second file or directory path
+/// * `options` - Optional behavior of the exchange
+///
+/// ### Return Value
+/// * `Ok(u64)` - Bytes that would be copied, `0` when both renames stay on their volumes
+/// * `Err(RenameError)` - Same validation errors as `exchange_with_rs`, including
+///   `InsufficientSpace`
+pub fn copy_size_rs(
+    path1: &Path,
+    path2: &Path,
+    options: &ExchangeOptions,
+) -> Result<u64, RenameError> {
+    let base_dir = resolve_base_dir_with(options)?;
+    prepare_exchange_in(path1.to_path_buf(), path2.to_path_buf(), &base_dir, options)
+        .map(|exchange_info| exchange_info.copy_bytes)
+}
+
+/// Rust interface function for listing every step a swap would take, without renaming anything
+///
+/// Goes beyond `preview_rs`: the backups, deletions with `force`, each rename
+/// including the hop through the temporary name, case-only renames and copies
+/// across volumes are listed in execution order, along with the branch of the
+/// rename engine selected for the inputs
+///
+/// ### Parameters
+/// * `path1` - First file or directory path
+/// * `path2` - Second file or directory path
+/// * `options` - Optional behavior of the exchange
+///
+/// ### Return Value
+/// * `Ok(ExchangePlan)` - Steps of the swap, `to_json` formats them for scripts
+/// * `Err(RenameError)` - Same validation errors as `exchange_with_rs`
+pub fn exchange_plan_rs(
+    path1: &Path,
+    path2: &Path,
+    options: &ExchangeOptions,
+) -> Result<ExchangePlan, RenameError> {
+    let base_dir = resolve_base_dir_with(options)?;
+    prepare_exchange_in(path1.to_path_buf(), path2.to_path_buf(), &base_dir, options)
+        .map(|exchange_info| plan::plan_steps(&exchange_info, &base_dir))
+}
+
+/// Rust interface function for quoting a string as JSON
+///
+/// The escaping `ExchangePlan::to_json` uses, for callers writing JSON of their own
+///
+/// ### Parameters
+/// * `value` - Text to quote, e.g. a path
+///
+/// ### Return Value
+/// The quoted text, e.g. `"C:\\a \"b\".txt"` for `C:\a "b".txt`
+pub fn json_string_rs(value: &str) -> String {
+    plan::json_string(value)
+}
+
+/// Rust interface function for checking whether one item is inside the other
+///
+/// Swapping a folder with an item inside it renames the folder around the
+/// item, which restructures the tree. Lets a caller warn before starting such a swap
+///
+/// ### Parameters
+/// * `path1` - First file or directory path
+/// * `path2` - Second file or directory path
+///
+/// ### Return Value
+/// * `Ok(bool)` - One item is a folder containing the other
+/// * `Err(RenameError)` - Same validation errors as `exchange_rs`
+pub fn is_nested_rs(path1: &Path, path2: &Path) -> Result<bool, RenameError> {
+    prepare_exchange(path1.to_path_buf(), path2.to_path_buf())
+        .map(|exchange_info| exchange_info.nested)
+}
+
+fn preview_paths(path1: PathBuf, path2: PathBuf) -> Result<(PathBuf, PathBuf), RenameError> {
+    let exchange_info = prepare_exchange(path1, path2)?;
+    Ok((
+        strip_extended(&exchange_info.f1.exchange.new_path),
+        strip_extended(&exchange_info.f2.exchange.new_path),
+    ))
+}
+
+fn fits_buffer(buf: *mut c_char, len: usize, value: &str) -> bool {
+    buf.is_null() || value.len() < len
+}
+
+unsafe fn write_buffer(buf: *mut c_char, value: &str) {
+    if buf.is_null() {
+        return;
+    }
+    std::ptr::copy_nonoverlapping(value.as_ptr() as *const c_char, buf, value.len());
+    *buf.add(value.len()) = 0;
+}
+
+unsafe fn convert_inputs(
+    path1: *const c_char,
+    path2: *const c_char,
+) -> Result<(PathBuf, PathBuf), RenameError> {
+    let path1 = ptr_to_path(path1)?;
+    let path2 = ptr_to_path(path2)?;
+    Ok((path1, path2))
+}
+
+unsafe fn ptr_to_path(ptr: *const c_char) -> Result<PathBuf, RenameError> {
+    if ptr.is_null() {
+        return Err(RenameError::NullPointer);
+    }
+
+    let c_str = CStr::from_ptr(ptr);
+    // Reject non-UTF-8 input instead of lossy conversion, which would silently
+    // produce a path that can never match the real file
+    let raw = c_str.to_str().map_err(|_| {
+        RenameError::InvalidPath(format!(
+            "Path contains invalid UTF-8: {}",
+            c_str.to_string_lossy()
+        ))
+    })?;
+    let sanitized = sanitize_input(raw);
+
+    if sanitized.is_empty() {
+        return Err(RenameError::NotExists);
+    }
+
+    Ok(PathBuf::from(sanitized))
+}
+
+/// Read a file name from a C string, unlike paths without trimming or unquoting
+unsafe fn ptr_to_name(ptr: *const c_char) -> Result<String, RenameError> {
+    if ptr.is_null() {
+        return Err(RenameError::NullPointer);
+    }
+    let c_str = CStr::from_ptr(ptr);
+    c_str.to_str().map(str::to_string).map_err(|_| {
+        RenameError::InvalidPath(format!(
+            "Name contains invalid UTF-8: {}",
+            c_str.to_string_lossy()
+        ))
+    })
+}
+
+#[cfg(windows)]
+unsafe fn wide_ptr_to_path(ptr: *const u16) -> Result<PathBuf, RenameError> {
+    use std::{ffi::OsString, os::windows::ffi::OsStringExt};
+
+    if ptr.is_null() {
+        return Err(RenameError::NullPointer);
+    }
+
+    let mut len = 0;
+    while *ptr.add(len) != 0 {
+        len += 1;
+    }
+    let raw = OsString::from_wide(std::slice::from_raw_parts(ptr, len));
+
+    // Keep the native string untouched when it is not representable as UTF-8
+    match raw.to_str() {
+        Some(text) => {
+            let sanitized = sanitize_input(text);
+            if sanitized.is_empty() {
+                return Err(RenameError::NotExists);
+            }
+            Ok(PathBuf::from(sanitized))
+        }
+        None => Ok(PathBuf::from(raw)),
+    }
+}
+
+/// Trim whitespace and a single matched pair of surrounding quotes
+///
+/// Only the shell-style quoting added by drag-and-drop or copy-as-path is removed;
+/// quotes that are part of a real file name are kept
+fn sanitize_input(input: &str) -> String {
+    let trimmed = input.trim();
+    for quote in ['"', '\''] {
+        if trimmed.len() >= 2 && trimmed.starts_with(quote) && trimmed.ends_with(quote) {
+            return trimmed[1..trimmed.len() - 1].to_string();
+        }
+    }
+    trimmed.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::{self, remove_file},
+        path::PathBuf,
+    };
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("name_exchanger_{}", name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn swaps_files_in_one_dir() {
+        let dir = test_dir("files_same_dir");
+        fs::write(dir.join("1.ext1"), "1").unwrap();
+        fs::write(dir.join("2.ext2"), "2").unwrap();
+
+        super::exchange_rs(&dir.join("1.ext1"), &dir.join("2.ext2")).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("2.ext1")).unwrap(), "1");
+        assert_eq!(fs::read_to_string(dir.join("1.ext2")).unwrap(), "2");
+        assert!(!dir.join("1.ext1").exists());
+        assert!(!dir.join("2.ext2").exists());
+    }
+
+    #[test]
+    fn swaps_files_across_dirs() {
+        let dir = test_dir("files_cross_dir");
+        fs::create_dir_all(dir.join("x")).unwrap();
+        fs::create_dir_all(dir.join("y")).unwrap();
+        fs::write(dir.join("x").join("a.txt"), "a").unwrap();
+        fs::write(dir.join("y").join("b.txt"), "b").unwrap();
+
+        super::exchange_rs(&dir.join("x").join("a.txt"), &dir.join("y").join("b.txt")).unwrap();
+        // Names are swapped, each item stays in its own directory
+        assert_eq!(
+            fs::read_to_string(dir.join("x").join("b.txt")).unwrap(),
+            "a"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("y").join("a.txt")).unwrap(),
+            "b"
+        );
+    }
+
+    #[test]
+    fn swaps_dirs_with_their_contents() {
+        let dir = test_dir("dirs");
+        fs::create_dir_all(dir.join("first")).unwrap();
+        fs::create_dir_all(dir.join("second")).unwrap();
+        fs::write(dir.join("first").join("inner.txt"), "1").unwrap();
+        fs::write(dir.join("second").join("inner.txt"), "2").unwrap();
+
+        super::exchange_rs(&dir.join("first"), &dir.join("second")).unwrap();
+        let read = |name: &str| fs::read_to_string(dir.join(name).join("inner.txt")).unwrap();
+        assert_eq!(read("second"), "1");
+        assert_eq!(read("first"), "2");
+    }
+
+    #[test]
+    fn swaps_file_with_dir() {
+        let dir = test_dir("file_dir");
+        fs::write(dir.join("a.txt"), "a").unwrap();
+        fs::create_dir_all(dir.join("folder")).unwrap();
+        fs::write(dir.join("folder").join("inner.txt"), "f").unwrap();
+
+        let outcome = super::exchange_outcome_rs(&dir.join("a.txt"), &dir.join("folder")).unwrap();
+        // The file keeps its extension, the folder has none to keep
+        assert_eq!(outcome.new_path1, dir.join("folder.txt"));
+        assert_eq!(outcome.new_path2, dir.join("a"));
+        assert_eq!(fs::read_to_string(dir.join("folder.txt")).unwrap(), "a");
+        assert_eq!(
+            fs::read_to_string(dir.join("a").join("inner.txt")).unwrap(),
+            "f"
+        );
+    }
+
+    #[test]
+    fn swaps_dir_with_item_inside_it() {
+        let dir = test_dir("nested_swap");
+        let outer = dir.join("outer");
+        fs::create_dir_all(outer.join("inner")).unwrap();
+        fs::write(outer.join("inner").join("file.txt"), "i").unwrap();
+
+        let outcome = super::exchange_outcome_rs(&outer, &outer.join("inner")).unwrap();
+        assert_eq!(outcome.new_path1, dir.join("inner"));
+        // The inner item moved along with the renamed outer folder
+        assert_eq!(
+            fs::read_to_string(dir.join("inner").join("outer").join("file.txt")).unwrap(),
+            "i"
+        );
+        assert!(!outer.exists());
+    }
+
+    #[test]
+    fn reports_missing_items_and_collisions() {
+        let dir = test_dir("codes");
+        fs::write(dir.join("a.txt"), "a").unwrap();
+        fs::create_dir_all(dir.join("x")).unwrap();
+        fs::write(dir.join("x").join("b.txt"), "b").unwrap();
+        // a.txt would become b.txt in its own directory, which is taken
+        fs::write(dir.join("b.txt"), "bystander").unwrap();
+
+        let missing = super::exchange_rs(&dir.join("a.txt"), &dir.join("none.txt"));
+        assert_eq!(missing.unwrap_err().to_code(), 1);
+
+        let taken = super::exchange_rs(&dir.join("a.txt"), &dir.join("x").join("b.txt"));
+        assert_eq!(taken.unwrap_err().to_code(), 3);
+        assert_eq!(fs::read_to_string(dir.join("a.txt")).unwrap(), "a");
+        assert_eq!(fs::read_to_string(dir.join("b.txt")).unwrap(), "bystander");
+    }
+
+    #[test]
+    fn undo_restores_names() {
+        let dir = test_dir("undo");
+        fs::write(dir.join("a.txt"), "a").unwrap();
+        fs::write(dir.join("b.log"), "b").unwrap();
+
+        super::exchange_rs(&dir.join("a.txt"), &dir.join("b.log")).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("b.txt")).unwrap(), "a");
+
+        super::undo_last_rs().unwrap();
+        assert_eq!(fs::read_to_string(dir.join("a.txt")).unwrap(), "a");
+        assert_eq!(fs::read_to_string(dir.join("b.log")).unwrap(), "b");
+        assert!(super::undo_last_rs().is_err());
+    }
+
+    #[test]
+    fn undo_restores_names_after_named_exchange() {
+        let dir = test_dir("undo_named");
+        fs::write(dir.join("a.txt"), "a").unwrap();
+        fs::write(dir.join("b.txt"), "b").unwrap();
+
+        super::exchange_named_rs(
+            &dir.join("a.txt"),
+            &dir.join("b.txt"),
+            "x.txt",
+            "y.txt",
+            &super::ExchangeOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(fs::read_to_string(dir.join("x.txt")).unwrap(), "a");
+
+        // A failed undo keeps the record for another attempt
+        fs::rename(dir.join("y.txt"), dir.join("moved.txt")).unwrap();
+        assert_eq!(super::undo_last_rs().unwrap_err().to_code(), 1);
+        fs::rename(dir.join("moved.txt"), dir.join("y.txt")).unwrap();
+
+        super::undo_last_rs().unwrap();
+        assert_eq!(fs::read_to_string(dir.join("a.txt")).unwrap(), "a");
+        assert_eq!(fs::read_to_string(dir.join("b.txt")).unwrap(), "b");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn same_dir_handles_roots() {
+        use crate::types::GetPathInfo;
+
+        let (root, sub) = if cfg!(windows) {
+            ("C:\\", "C:\\sub")
+        } else {
+            ("/", "/sub")
+        };
+        let info = |path1: &str, path2: &str| GetPathInfo {
+            path1: PathBuf::from(path1),
+            path2: PathBuf::from(path2),
+        };
+
+        assert!(info(root, root).if_same_dir());
+        assert!(!info(root, sub).if_same_dir());
+        assert!(!info(sub, root).if_same_dir());
+    }
+
+    #[test]
+    fn resolves_multi_segment_relative_paths() {
+        let base = test_dir("relative").canonicalize().unwrap();
+        let nested = base.join("sub").join("dir");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(nested.join("file.txt"), "").unwrap();
+
+        for input in ["sub/dir/file.txt", "./sub/dir/file.txt", "sub/../sub/dir/file.txt"] {
+            let (exists, resolved) = super::resolve_path_rs(&PathBuf::from(input), &base).unwrap();
+            assert!(exists, "{}", input);
+            assert_eq!(resolved, nested.join("file.txt").canonicalize().unwrap());
+        }
+    }
+
+    #[test]
+    fn collapses_repeated_separators() {
+        use crate::exchange::collapse_separators;
+
+        assert_eq!(collapse_separators("a///b//c", '/', false), "a/b/c");
+        assert_eq!(collapse_separators("C:\\\\a\\\\\\b", '\\', true), "C:\\a\\b");
+        assert_eq!(
+            collapse_separators("\\\\server\\\\share\\f", '\\', true),
+            "\\\\server\\share\\f"
+        );
+        assert_eq!(collapse_separators("//srv/share", '/', false), "/srv/share");
+    }
+
+    #[test]
+    fn strips_only_matched_quotes() {
+        use super::sanitize_input;
+
+        assert_eq!(sanitize_input("\"C:\\a\\b.txt\""), "C:\\a\\b.txt");
+        assert_eq!(sanitize_input("  \\\\srv\\share\\f "), "\\\\srv\\share\\f");
+        assert_eq!(sanitize_input("'weird'.txt"), "'weird'.txt");
+        assert_eq!(sanitize_input("\"a.txt'"), "\"a.txt'");
+    }
+
+    #[test]
+    fn keeps_compound_extensions() {
+        use crate::types::GetPathInfo;
+
+        let split = |name: &str| {
+            let info = GetPathInfo {
+                path1: PathBuf::from(name),
+                path2: PathBuf::from("b.zip"),
+            };
+            let (meta, _) = info.metadata_collect(true, true);
+            (meta.name, meta.ext, meta.full_ext)
+        };
+
+        assert_eq!(
+            split("a.tar.gz"),
+            ("a".into(), ".tar.gz".into(), ".tar.gz".into())
+        );
+        assert_eq!(
+            split("a.TAR.BZ2"),
+            ("a".into(), ".TAR.BZ2".into(), ".TAR.BZ2".into())
+        );
+        assert_eq!(
+            split("a.en.srt"),
+            ("a.en".into(), ".srt".into(), ".en.srt".into())
+        );
+        assert_eq!(split("a.txt"), ("a".into(), ".txt".into(), ".txt".into()));
+        assert_eq!(
+            split("字幕.en.srt"),
+            ("字幕.en".into(), ".srt".into(), ".en.srt".into())
+        );
+        assert_eq!(split(".config.bak").2, ".bak");
+    }
+
+    #[test]
+    fn splits_names_into_stem_and_ext() {
+        use super::split_name_rs;
+
+        let split = |name: &str, is_file: bool| split_name_rs(&PathBuf::from(name), is_file);
+        let pair = |stem: &str, ext: &str| (stem.to_string(), ext.to_string());
+
+        assert_eq!(split("a.txt", true), pair("a", ".txt"));
+        assert_eq!(split("dir/sub.d/a.txt", true), pair("a", ".txt"));
+        assert_eq!(split("README", true), pair("README", ""));
+        assert_eq!(split("archive.tar.gz", true), pair("archive", ".tar.gz"));
+        assert_eq!(split("movie.en.srt", true), pair("movie.en", ".srt"));
+        assert_eq!(split(".gitignore", true), pair(".gitignore", ""));
+        assert_eq!(split(".env.local", true), pair(".env", ".local"));
+        // A compound extension is never the whole name
+        assert_eq!(split(".tar.gz", true), pair(".tar", ".gz"));
+        assert_eq!(split("docs.v2", false), pair("docs.v2", ""));
+        assert_eq!(split(".vscode", false), pair(".vscode", ""));
+        assert_eq!(split("..", true), pair("", ""));
+    }
+
+    #[test]
+    fn treats_leading_dots_as_names() {
+        let dir = test_dir("dotfiles");
+        for name in [".gitignore", ".env", ".env.local", "notes.txt"] {
+            fs::write(dir.join(name), name).unwrap();
+        }
+        fs::create_dir(dir.join(".vscode")).unwrap();
+        fs::create_dir(dir.join("docs.v2")).unwrap();
+
+        let split = |name: &str| {
+            let meta = super::metadata_rs(&dir.join(name));
+            (meta.name, meta.ext, meta.full_ext)
+        };
+        assert_eq!(
+            split(".gitignore"),
+            (".gitignore".into(), "".into(), "".into())
+        );
+        assert_eq!(split(".env"), (".env".into(), "".into(), "".into()));
+        assert_eq!(
+            split(".env.local"),
+            (".env".into(), ".local".into(), ".local".into())
+        );
+        assert_eq!(split(".vscode"), (".vscode".into(), "".into(), "".into()));
+        assert_eq!(split("docs.v2"), ("docs.v2".into(), "".into(), "".into()));
+
+        // The dotfile keeps no extension of its own, the other file keeps its `.txt`
+        super::exchange_rs(&dir.join(".gitignore"), &dir.join("notes.txt")).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("notes")).unwrap(), ".gitignore");
+        assert_eq!(
+            fs::read_to_string(dir.join(".gitignore.txt")).unwrap(),
+            "notes.txt"
+        );
+
+        super::exchange_rs(&dir.join(".vscode"), &dir.join("docs.v2")).unwrap();
+        assert!(dir.join("docs.v2").is_dir() && dir.join(".vscode").is_dir());
+        super::exchange_rs(&dir.join(".env"), &dir.join(".vscode")).unwrap();
+        assert_eq!(fs::read_to_string(dir.join(".vscode")).unwrap(), ".env");
+        assert!(dir.join(".env").is_dir());
+    }
+
+    #[test]
+    fn rejects_reserved_device_names() {
+        use crate::types::GetPathInfo;
+
+        let check = |name: &str| GetPathInfo::validate_name(&PathBuf::from(name)).is_ok();
+
+        for name in [
+            "CON",
+            "con.txt",
+            "Nul",
+            "aux.tar.gz",
+            "COM1",
+            "lpt9.log",
+            "PRN ",
+        ] {
+            assert!(!check(name), "{}", name);
+        }
+        for name in ["CONSOLE", "COM0", "COM10", "a.con", "nully.txt"] {
+            assert!(check(name), "{}", name);
+        }
+    }
+
+    #[test]
+    fn rejects_targets_windows_cannot_create() {
+        use crate::types::GetPathInfo;
+
+        let check = |name: &str| GetPathInfo::validate_target(&PathBuf::from("dir").join(name));
+
+        // Separators never reach a computed name, they split the path instead
+        for c in ['<', '>', ':', '"', '|', '?', '*', '\t'] {
+            let name = format!("a{}b.txt", c);
+            let err = check(&name).unwrap_err();
+            assert_eq!(err.to_code(), 14, "{:?}", name);
+        }
+
+        let long = "a".repeat(252);
+        assert!(check(&format!("{}.txt", long)).is_err());
+        assert!(check(&format!("{}.tx", long)).is_ok());
+        assert!(check("plain name.txt").is_ok());
+
+        // The message names the kind of error once
+        assert_eq!(
+            GetPathInfo::validate_target_name("a:b")
+                .unwrap_err()
+                .to_string(),
+            "Illegal target name: \"a:b\""
+        );
+        assert_eq!(check("con.txt").unwrap_err().to_code(), 9);
+    }
+
+    #[test]
+    fn swaps_inside_deep_tree() {
+        let mut deep = test_dir("deep");
+        while deep.to_string_lossy().len() < 300 {
+            deep.push("a_rather_long_directory_name");
+        }
+        fs::create_dir_all(&deep).unwrap();
+        fs::write(deep.join("first.txt"), "1").unwrap();
+        fs::write(deep.join("second.log"), "2").unwrap();
+
+        let (new1, new2) =
+            super::preview_rs(&deep.join("first.txt"), &deep.join("second.log")).unwrap();
+        assert!(!new1.to_string_lossy().starts_with(r"\\?\"));
+
+        super::exchange_rs(&deep.join("first.txt"), &deep.join("second.log")).unwrap();
+        assert_eq!(fs::read_to_string(&new1).unwrap_or_default(), "1");
+        assert_eq!(fs::read_to_string(&new2).unwrap_or_default(), "2");
+    }
+
+    #[test]
+    fn case_only_rename_lands() {
+        let dir = test_dir("case_only");
+        fs::create_dir_all(dir.join("one")).unwrap();
+        fs::create_dir_all(dir.join("two")).unwrap();
+        fs::write(dir.join("one").join("Report.txt"), "").unwrap();
+        fs::write(dir.join("two").join("report.log"), "").unwrap();
+
+        super::exchange_rs(
+            &dir.join("one").join("Report.txt"),
+            &dir.join("two").join("report.log"),
+        )
+        .unwrap();
+
+        let names = |sub: &str| -> Vec<String> {
+            fs::read_dir(dir.join(sub))
+                .unwrap()
+                .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+                .collect()
+        };
+        assert_eq!(names("one"), ["report.txt"]);
+        assert_eq!(names("two"), ["Report.log"]);
+    }
+
+    #[test]
+    fn transforms_name_case() {
+        use super::NameCase;
+
+        assert_eq!(NameCase::Keep.apply("My File", ".TXT"), "My File.TXT");
+        assert_eq!(NameCase::Lower.apply("My File", ".TXT"), "my file.txt");
+        assert_eq!(NameCase::Upper.apply("My File", ".txt"), "MY FILE.TXT");
+        assert_eq!(
+            NameCase::Title.apply("don't_STOP-me now2day", ".PDF"),
+            "Don't_Stop-Me Now2day.pdf"
+        );
+
+        let dir = test_dir("name_case");
+        fs::create_dir_all(dir.join("one")).unwrap();
+        fs::create_dir_all(dir.join("two")).unwrap();
+        fs::write(dir.join("one").join("Report.TXT"), "1").unwrap();
+        fs::write(dir.join("two").join("NOTES.log"), "2").unwrap();
+
+        let options = super::ExchangeOptions::from_flags(super::FLAG_LOWERCASE);
+        assert_eq!(options.name_case, NameCase::Lower);
+        let outcome = super::exchange_with_rs(
+            &dir.join("one").join("Report.TXT"),
+            &dir.join("two").join("NOTES.log"),
+            &options,
+        )
+        .unwrap();
+        assert_eq!(outcome.new_path1, dir.join("one").join("notes.txt"));
+        assert_eq!(outcome.new_path2, dir.join("two").join("report.log"));
+        assert_eq!(fs::read_to_string(&outcome.new_path1).unwrap(), "1");
+
+        // Undo brings back the original letter case instead of casing again
+        super::undo_last_rs().unwrap();
+        let names = |sub: &str| -> Vec<String> {
+            fs::read_dir(dir.join(sub))
+                .unwrap()
+                .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+                .collect()
+        };
+        assert_eq!(names("one"), ["Report.TXT"]);
+        assert_eq!(names("two"), ["NOTES.log"]);
+
+        // Stems differing only in case, each item then only changes its letter case
+        fs::write(dir.join("Readme.md"), "md").unwrap();
+        fs::write(dir.join("README.txt"), "txt").unwrap();
+        let outcome =
+            super::exchange_with_rs(&dir.join("Readme.md"), &dir.join("README.txt"), &options)
+                .unwrap();
+        assert_eq!(outcome.new_path1, dir.join("readme.md"));
+        assert_eq!(outcome.new_path2, dir.join("readme.txt"));
+        assert_eq!(fs::read_to_string(dir.join("readme.md")).unwrap(), "md");
+        assert_eq!(fs::read_to_string(dir.join("readme.txt")).unwrap(), "txt");
+        let mut names: Vec<String> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .filter(|name| name.starts_with(['r', 'R']))
+            .collect();
+        names.sort();
+        assert_eq!(names, ["readme.md", "readme.txt"]);
+    }
+
+    #[test]
+    fn compares_case_per_directory() {
+        use crate::exchange::paths_match;
+        use std::path::Path;
+
+        let path = |text: &str| PathBuf::from(text);
+        let insensitive = |_: &Path| false;
+        // Only `/data/wsl` is flagged, its parent and children are not
+        let wsl_only = |dir: &Path| dir == Path::new("/data/wsl");
+
+        assert!(paths_match(
+            &path("/data/Docs/A.txt"),
+            &path("/data/docs/a.txt"),
+            insensitive
+        ));
+        assert!(!paths_match(
+            &path("/data/wsl/A.txt"),
+            &path("/data/wsl/a.txt"),
+            wsl_only
+        ));
+        assert!(paths_match(
+            &path("/data/WSL/a.txt"),
+            &path("/data/wsl/a.txt"),
+            wsl_only
+        ));
+        assert!(!paths_match(
+            &path("/data/wsl/Sub/a.txt"),
+            &path("/data/wsl/sub/a.txt"),
+            wsl_only
+        ));
+        assert!(paths_match(
+            &path("/data/wsl/sub/A.txt"),
+            &path("/data/wsl/sub/a.txt"),
+            wsl_only
+        ));
+        assert!(!paths_match(
+            &path("/data/a.txt"),
+            &path("/data/a.txt/b"),
+            insensitive
+        ));
+    }
+
+    #[test]
+    fn expands_existing_env_vars_only() {
+        use crate::exchange::expand_env_vars;
+
+        std::env::set_var("NAME_EXCHANGER_TEST_DIR", "C:\\Users\\me");
+        assert_eq!(
+            expand_env_vars("%NAME_EXCHANGER_TEST_DIR%\\Desktop\\a.txt"),
+            "C:\\Users\\me\\Desktop\\a.txt"
+        );
+        assert_eq!(
+            expand_env_vars("100% %NAME_EXCHANGER_TEST_DIR%"),
+            "100% C:\\Users\\me"
+        );
+        assert_eq!(
+            expand_env_vars("%NAME_EXCHANGER_NOT_SET%.txt"),
+            "%NAME_EXCHANGER_NOT_SET%.txt"
+        );
+        assert_eq!(expand_env_vars("50% off.txt"), "50% off.txt");
+    }
+
+    #[test]
+    fn counts_entries_of_swapped_dirs() {
+        let dir = test_dir("count");
+        fs::create_dir_all(dir.join("left").join("inner")).unwrap();
+        fs::write(dir.join("left").join("inner").join("a"), "").unwrap();
+        fs::create_dir_all(dir.join("right")).unwrap();
+        fs::write(dir.join("right").join("b"), "").unwrap();
+
+        let outcome = super::exchange_outcome_rs(&dir.join("left"), &dir.join("right")).unwrap();
+        assert_eq!(outcome.entries, 3);
+        assert!(outcome.new_path1.join("inner").join("a").exists());
+
+        // Nothing is walked unless asked for
+        let options = super::ExchangeOptions::default();
+        let outcome =
+            super::exchange_with_rs(&dir.join("left"), &dir.join("right"), &options).unwrap();
+        assert_eq!(outcome.entries, 0);
+        assert!(dir.join("left").join("inner").join("a").exists());
+    }
+
+    #[test]
+    fn preserves_mtime_when_requested() {
+        use std::time::{Duration, SystemTime};
+
+        let dir = test_dir("mtime");
+        // A different time per file, so times restored onto the wrong item show up
+        let old_a = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        let old_b = SystemTime::UNIX_EPOCH + Duration::from_secs(1_200_000_000);
+        for (name, old) in [("a.txt", old_a), ("b.txt", old_b)] {
+            fs::write(dir.join(name), name).unwrap();
+            let file = fs::File::options()
+                .write(true)
+                .open(dir.join(name))
+                .unwrap();
+            file.set_modified(old).unwrap();
+        }
+        let modified = |path: &std::path::Path| fs::metadata(path).unwrap().modified().unwrap();
+
+        // Each time stays with its content, whether or not it is reapplied
+        for preserve_timestamps in [true, false] {
+            let options = super::ExchangeOptions {
+                preserve_timestamps,
+                ..Default::default()
+            };
+            let outcome =
+                super::exchange_with_rs(&dir.join("a.txt"), &dir.join("b.txt"), &options).unwrap();
+            for path in [outcome.new_path1, outcome.new_path2] {
+                let old = match fs::read_to_string(&path).unwrap().as_str() {
+                    "a.txt" => old_a,
+                    _ => old_b,
+                };
+                assert_eq!(modified(&path), old, "{}", preserve_timestamps);
+            }
+        }
+    }
+
+    #[test]
+    fn uses_and_validates_custom_temp_stem() {
+        let dir = test_dir("temp_stem");
+        fs::write(dir.join("a.txt"), "a").unwrap();
+        fs::write(dir.join("b.txt"), "b").unwrap();
+        // Occupy the preferred temp name to exercise the counter suffix
+        fs::write(dir.join("ne_tmp.txt"), "x").unwrap();
+
+        let mut options = super::ExchangeOptions {
+            temp_stem: Some("ne_tmp".to_string()),
+            ..Default::default()
+        };
+        super::exchange_with_rs(&dir.join("a.txt"), &dir.join("b.txt"), &options).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("a.txt")).unwrap(), "b");
+        assert_eq!(fs::read_to_string(dir.join("ne_tmp.txt")).unwrap(), "x");
+
+        for stem in ["", "bad:name", "trailing.", "nul"] {
+            options.temp_stem = Some(stem.to_string());
+            let err = super::exchange_with_rs(&dir.join("a.txt"), &dir.join("b.txt"), &options)
+                .unwrap_err();
+            assert!(matches!(err.to_code(), 5 | 9), "{:?}", stem);
+        }
+    }
+
+    #[test]
+    fn rotates_names_among_three_files() {
+        let dir = test_dir("rotate");
+        for name in ["a", "b", "c"] {
+            fs::write(dir.join(format!("{}.txt", name)), name).unwrap();
+        }
+        fs::write(dir.join("d.log"), "d").unwrap();
+
+        let paths: Vec<_> = ["a.txt", "b.txt", "c.txt", "d.log"]
+            .iter()
+            .map(|name| dir.join(name))
+            .collect();
+        let new_paths = super::rotate_rs(&paths).unwrap();
+        assert_eq!(new_paths[3], dir.join("a.log"));
+
+        // a.txt -> b.txt, b.txt -> c.txt, c.txt -> d.txt, d.log -> a.log
+        assert_eq!(fs::read_to_string(dir.join("b.txt")).unwrap(), "a");
+        assert_eq!(fs::read_to_string(dir.join("c.txt")).unwrap(), "b");
+        assert_eq!(fs::read_to_string(dir.join("d.txt")).unwrap(), "c");
+        assert_eq!(fs::read_to_string(dir.join("a.log")).unwrap(), "d");
+        assert!(!dir.join("a.txt").exists());
+    }
+
+    #[test]
+    fn detects_nesting_through_dot_segments_and_separators() {
+        use crate::types::GetPathInfo;
+
+        let dir = crate::long_path::strip_extended(&test_dir("nesting").canonicalize().unwrap());
+        fs::create_dir_all(dir.join("bar").join("sub")).unwrap();
+        let text = dir.to_string_lossy().to_string();
+
+        // "foo" does not exist, so this is resolved lexically to dir/bar
+        let dotted = PathBuf::from(format!("{}/foo/../bar", text));
+        let child = dir.join("bar").join("sub");
+        assert!(GetPathInfo::path_is_parent(&dotted, &child));
+
+        // Raw strip_prefix would report dir/bar as parent of dir/bar/../baz
+        let escaping = PathBuf::from(format!("{}/bar/../baz", text));
+        assert!(!GetPathInfo::path_is_parent(&dir.join("bar"), &escaping));
+
+        let mixed = PathBuf::from(format!("{}\\bar/sub\\missing", text));
+        assert!(GetPathInfo::path_is_parent(&dir.join("bar"), &mixed));
+        assert!(!GetPathInfo::path_is_parent(&mixed, &dir.join("bar")));
+
+        // Letter case counts where the directory tells it apart
+        assert_eq!(
+            GetPathInfo::path_is_parent(&dir.join("BAR"), &child),
+            cfg!(windows)
+        );
+    }
+
+    #[test]
+    fn siblings_sharing_a_name_prefix_are_not_nested() {
+        use crate::types::GetPathInfo;
+
+        let dir = crate::long_path::strip_extended(&test_dir("prefix").canonicalize().unwrap());
+        fs::create_dir_all(dir.join("data")).unwrap();
+        fs::create_dir_all(dir.join("data-old")).unwrap();
+        let text = dir.to_string_lossy().to_string();
+        let sep = std::path::MAIN_SEPARATOR;
+
+        let cases = [
+            (dir.join("data"), dir.join("data-old")),
+            (
+                PathBuf::from(format!("{text}{sep}data{sep}")),
+                dir.join("data-old"),
+            ),
+            (dir.join("data"), dir.join("datax").join("missing")),
+            (dir.join("missing"), dir.join("missing-old")),
+        ];
+        for (path1, path2) in cases {
+            let paths = GetPathInfo { path1, path2 };
+            assert_eq!(paths.if_root(), 0, "{:?}", paths);
+        }
+
+        let paths = GetPathInfo {
+            path1: PathBuf::from(format!("{text}{sep}data{sep}")),
+            path2: dir.join("data").join("inner"),
+        };
+        assert_eq!(paths.if_root(), 1);
+    }
+
+    #[test]
+    fn plans_every_rename_step() {
+        use super::StepKind;
+
+        let dir = test_dir("plan");
+        fs::write(dir.join("a.txt"), "a").unwrap();
+        fs::write(dir.join("b.log"), "b").unwrap();
+
+        let options = super::ExchangeOptions::default();
+        let plan =
+            super::exchange_plan_rs(&dir.join("a.txt"), &dir.join("b.log"), &options).unwrap();
+        assert_eq!((plan.mode, plan.nested, plan.file1_first), (0, false, true));
+        let kinds: Vec<StepKind> = plan.steps.iter().map(|step| step.kind).collect();
+        assert_eq!(kinds, [StepKind::Rename; 3]);
+        // The second item hops through its temporary name
+        let temp = plan.steps[0].to.clone().unwrap();
+        assert_eq!(plan.steps[0].from, dir.join("b.log"));
+        assert_eq!(
+            plan.steps[1].to.as_deref(),
+            Some(dir.join("b.txt").as_path())
+        );
+        assert_eq!(plan.steps[2].from, temp);
+        assert_eq!(
+            plan.steps[2].to.as_deref(),
+            Some(dir.join("a.log").as_path())
+        );
+        assert!(plan.to_json().contains("\"action\":\"rename\""));
+        assert_eq!(
+            super::json_string_rs("C:\\a \"b\"\n"),
+            "\"C:\\\\a \\\"b\\\"\\n\""
+        );
+        // Nothing was renamed
+        assert_eq!(fs::read_to_string(dir.join("a.txt")).unwrap(), "a");
+        assert!(!temp.exists());
+
+        let outer = dir.join("outer");
+        fs::create_dir_all(outer.join("inner")).unwrap();
+        let plan = super::exchange_plan_rs(&outer, &outer.join("inner"), &options).unwrap();
+        assert_eq!((plan.mode, plan.nested), (1, true));
+        assert_eq!(plan.steps.len(), 2);
+        assert_eq!(plan.steps[0].from, outer.join("inner"));
+    }
+
+    #[test]
+    fn refuses_nested_items_on_request() {
+        let dir = test_dir("refuse_nested");
+        let outer = dir.join("outer");
+        let inner = outer.join("inner");
+        fs::create_dir_all(&inner).unwrap();
+        fs::create_dir_all(dir.join("other")).unwrap();
+
+        assert!(super::is_nested_rs(&inner, &outer).unwrap());
+        assert!(!super::is_nested_rs(&outer, &dir.join("other")).unwrap());
+
+        let options = super::ExchangeOptions::from_flags(super::FLAG_REFUSE_NESTED);
+        let result = super::exchange_with_rs(&outer, &inner, &options);
+        assert!(
+            matches!(result, Err(super::RenameError::NestedItems(_))),
+            "{:?}",
+            result
+        );
+        assert_eq!(result.unwrap_err().to_code(), 21);
+        assert!(inner.is_dir());
+    }
+
+    #[test]
+    fn resolves_relative_paths_against_base_dir() {
+        let dir = test_dir("base_dir");
+        fs::write(dir.join("a.txt"), "a").unwrap();
+        fs::write(dir.join("b.txt"), "b").unwrap();
+
+        let options = super::ExchangeOptions {
+            base_dir: Some(dir.clone()),
+            ..Default::default()
+        };
+        let outcome =
+            super::exchange_with_rs(&PathBuf::from("a.txt"), &PathBuf::from("b.txt"), &options)
+                .unwrap();
+        assert_eq!(outcome.new_path1, dir.join("b.txt"));
+        assert_eq!(fs::read_to_string(dir.join("a.txt")).unwrap(), "b");
+    }
+
+    #[test]
+    fn resolves_relative_paths_against_the_working_directory() {
+        // Without `base_dir` the current directory comes first
+        let cwd = std::env::current_dir().unwrap();
+        let options = super::ExchangeOptions::default();
+        assert_eq!(
+            crate::exchange::resolve_base_dir_with(&options).unwrap(),
+            cwd
+        );
+
+        let err = super::exchange_with_rs(
+            &PathBuf::from("missing_1.txt"),
+            &PathBuf::from("missing_2.txt"),
+            &options,
+        )
+        .unwrap_err();
+        assert_eq!(err.to_code(), 1);
+        assert_eq!(
+            super::last_error_detail_rs().unwrap(),
+            cwd.join("missing_1.txt").display().to_string()
+        );
+    }
+
+    #[test]
+    fn pairs_items_by_pattern() {
+        let dir = test_dir("pattern");
+        for name in [
+            "a.en.srt", "b.en.srt", "c.en.srt", "a.zh.srt", "b.zh.srt", "d.zh.srt",
+        ] {
+            fs::write(dir.join(name), name).unwrap();
+        }
+        fs::write(dir.join("notes.txt"), "").unwrap();
+
+        let found =
+            super::pair_by_pattern_rs(&dir.join("*.en.srt"), &dir.join("*.zh.srt")).unwrap();
+        let names = |paths: Vec<&PathBuf>| -> Vec<String> {
+            paths
+                .iter()
+                .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+                .collect()
+        };
+        assert_eq!(
+            names(found.pairs.iter().flat_map(|(p1, p2)| [p1, p2]).collect()),
+            ["a.en.srt", "a.zh.srt", "b.en.srt", "b.zh.srt"]
+        );
+        assert_eq!(names(found.unmatched1.iter().collect()), ["c.en.srt"]);
+        assert_eq!(names(found.unmatched2.iter().collect()), ["d.zh.srt"]);
+
+        let failed = super::exchange_many_rs(&found.pairs, &Default::default(), |_, _| {});
+        assert_eq!(failed, 0);
+        assert_eq!(
+            fs::read_to_string(dir.join("a.zh.srt")).unwrap(),
+            "a.en.srt"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("b.en.srt")).unwrap(),
+            "b.zh.srt"
+        );
+
+        // `?` matches one character, and wildcards are not allowed in directory names
+        let found =
+            super::pair_by_pattern_rs(&dir.join("?.en.srt"), &dir.join("?.zh.srt")).unwrap();
+        assert_eq!(found.pairs.len(), 2);
+
+        // An item matched by both patterns is never paired, not even with itself
+        let found = super::pair_by_pattern_rs(&dir.join("*.srt"), &dir.join("*.srt")).unwrap();
+        assert!(found.pairs.is_empty());
+        assert_eq!(found.unmatched1.len(), 6);
+        assert_eq!(found.unmatched1, found.unmatched2);
+        let found = super::pair_by_pattern_rs(&dir.join("*.srt"), &dir.join("*.en.srt")).unwrap();
+        assert!(found.pairs.is_empty());
+        assert_eq!(
+            names(found.unmatched2.iter().collect()),
+            ["a.en.srt", "b.en.srt", "c.en.srt"]
+        );
+        let result = super::pair_by_pattern_rs(&dir.join("*").join("a.srt"), &dir.join("*.srt"));
+        assert!(
+            matches!(result, Err(super::RenameError::InvalidPath(_))),
+            "{:?}",
+            result
+        );
+    }
+
+    #[test]
+    fn rechecks_items_before_renaming() {
+        let dir = test_dir("refresh");
+        let file1 = dir.join("a.txt");
+        let file2 = dir.join("b.txt");
+        fs::write(&file1, "a").unwrap();
+        fs::write(&file2, "b").unwrap();
+
+        // Replaced by a directory between planning and renaming
+        let exchange_info = super::prepare_exchange(file1.clone(), file2.clone()).unwrap();
+        remove_file(&file2).unwrap();
+        fs::create_dir(&file2).unwrap();
+        let result = super::commit_exchange(exchange_info);
+        assert!(
+            matches!(result, Err(super::RenameError::ItemChanged(_))),
+            "{:?}",
+            result
+        );
+        assert_eq!(result.unwrap_err().to_code(), 22);
+        assert_eq!(fs::read_to_string(&file1).unwrap(), "a");
+
+        let exchange_info = super::prepare_exchange(file1.clone(), file2.clone()).unwrap();
+        remove_file(&file1).unwrap();
+        let result = super::commit_exchange(exchange_info);
+        assert!(
+            matches!(result, Err(super::RenameError::NotExists)),
+            "{:?}",
+            result
+        );
+        assert!(file2.is_dir());
+    }
+
+    /// Create a directory link, a junction on Windows since it needs no privilege
+    fn link_dir(target: &std::path::Path, link: &std::path::Path) {
+        #[cfg(windows)]
+        {
+            let status = std::process::Command::new("cmd")
+                .arg("/C")
+                .arg("mklink")
+                .arg("/J")
+                .arg(link)
+                .arg(target)
+                .output()
+                .unwrap()
+                .status;
+            assert!(status.success());
+        }
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(target, link).unwrap();
+    }
+
+    #[test]
+    fn swaps_the_link_and_not_its_target() {
+        let dir = test_dir("link");
+        fs::create_dir(dir.join("target")).unwrap();
+        fs::write(dir.join("target").join("inner.txt"), "t").unwrap();
+        fs::create_dir(dir.join("real")).unwrap();
+        link_dir(&dir.join("target"), &dir.join("link"));
+
+        let info = super::metadata_rs(&dir.join("link"));
+        assert!(info.is_symlink || info.is_junction);
+        assert!(!super::metadata_rs(&dir.join("real")).is_symlink);
+
+        let outcome = super::exchange_outcome_rs(&dir.join("link"), &dir.join("real")).unwrap();
+        assert_eq!(outcome.entries, 0);
+
+        // The link now carries the name "real", the target kept its name
+        let moved = fs::symlink_metadata(dir.join("real")).unwrap();
+        assert!(moved.file_type().is_symlink());
+        assert!(!fs::symlink_metadata(dir.join("link"))
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert!(dir.join("target").join("inner.txt").exists());
+        assert!(dir.join("real").join("inner.txt").exists());
+
+        let options = super::ExchangeOptions {
+            refuse_linked_pair: true,
+            ..Default::default()
+        };
+        let err =
+            super::exchange_with_rs(&dir.join("real"), &dir.join("target"), &options).unwrap_err();
+        assert_eq!(err.to_code(), 4);
+        assert!(dir.join("target").join("inner.txt").exists());
+    }
+
+    #[test]
+    fn swaps_a_dangling_link() {
+        let dir = test_dir("dangling_link");
+        fs::create_dir(dir.join("target")).unwrap();
+        fs::create_dir(dir.join("real")).unwrap();
+        link_dir(&dir.join("target"), &dir.join("link"));
+        fs::remove_dir(dir.join("target")).unwrap();
+
+        // The link points nowhere but is still an item that can be renamed
+        super::exchange_outcome_rs(&dir.join("link"), &dir.join("real")).unwrap();
+        let moved = fs::symlink_metadata(dir.join("real")).unwrap();
+        assert!(moved.file_type().is_symlink());
+        assert!(dir.join("link").is_dir());
+        assert!(!dir.join("real").exists());
+
+        // A dangling link at a target name is a conflict like any other item
+        fs::create_dir(dir.join("x")).unwrap();
+        fs::write(dir.join("a.txt"), "a").unwrap();
+        fs::write(dir.join("x").join("b.txt"), "b").unwrap();
+        fs::write(dir.join("x").join("c.txt"), "c").unwrap();
+        link_dir(&dir.join("target"), &dir.join("b.txt"));
+        let result = super::exchange_rs(&dir.join("a.txt"), &dir.join("x").join("b.txt"));
+        assert!(matches!(result, Err(super::RenameError::AlreadyExists)));
+        let result = super::rotate_rs(&[
+            dir.join("a.txt"),
+            dir.join("x").join("b.txt"),
+            dir.join("x").join("c.txt"),
+        ]);
+        assert!(matches!(result, Err(super::RenameError::AlreadyExists)));
+        assert!(fs::symlink_metadata(dir.join("b.txt")).is_ok());
+        assert_eq!(fs::read_to_string(dir.join("a.txt")).unwrap(), "a");
+    }
+
+    #[test]
+    fn copy_fallback_moves_links_as_links() {
+        let dir = test_dir("link_copy");
+        fs::create_dir(dir.join("target")).unwrap();
+        fs::write(dir.join("target").join("inner.txt"), "t").unwrap();
+        link_dir(&dir.join("target"), &dir.join("link"));
+
+        crate::copy_move::move_by_copy(&dir.join("link"), &dir.join("moved"), None).unwrap();
+
+        assert!(fs::symlink_metadata(dir.join("link")).is_err());
+        let moved = fs::symlink_metadata(dir.join("moved")).unwrap();
+        assert!(moved.file_type().is_symlink());
+        #[cfg(windows)]
+        assert!(super::metadata_rs(&dir.join("moved")).is_junction);
+        // The target was neither copied nor deleted
+        assert!(dir.join("moved").join("inner.txt").exists());
+        assert!(dir.join("target").join("inner.txt").exists());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn summarizes_the_last_operation() {
+        let dir = test_dir("op_log");
+        fs::write(dir.join("a.txt"), "a").unwrap();
+        fs::write(dir.join("b.log"), "b").unwrap();
+
+        super::exchange_outcome_rs(&dir.join("a.txt"), &dir.join("b.log")).unwrap();
+        let log = super::last_operation_log_rs();
+        let lines: Vec<&str> = log.lines().collect();
+        assert_eq!(lines.len(), 6, "{}", log);
+        assert!(lines[0].ends_with(&format!("a.txt → {}", dir.join("b.txt").display())));
+        assert!(lines[1].ends_with(&format!("b.log → {}", dir.join("a.log").display())));
+        assert_eq!(lines[2], "Swapped through a temporary name:");
+
+        let mut buffer = [0 as std::ffi::c_char; 8];
+        let code = unsafe { super::last_operation_log(buffer.as_mut_ptr(), buffer.len()) };
+        assert_eq!(code, 7);
+
+        // Rejected before anything was renamed, the summary stays
+        super::exchange_outcome_rs(&dir.join("a.log"), &dir.join("missing")).unwrap_err();
+        assert_eq!(super::last_operation_log_rs(), log);
+    }
+
+    #[test]
+    fn describes_the_last_error() {
+        use std::ffi::{CStr, CString};
+
+        let dir = test_dir("last_error");
+        fs::write(dir.join("a.txt"), "a").unwrap();
+        fs::write(dir.join("b.txt"), "b").unwrap();
+        let message = || {
+            let ptr = super::last_error_message();
+            (!ptr.is_null()).then(|| unsafe { CStr::from_ptr(ptr) }.to_string_lossy().to_string())
+        };
+        let c_path = |name: &str| {
+            CString::new(dir.join(name).to_string_lossy().as_ref()).expect("test names have no NUL")
+        };
+
+        super::clear_last_error();
+        assert_eq!(message(), None);
+
+        let (a, missing) = (c_path("a.txt"), c_path("missing.txt"));
+        assert_eq!(unsafe { super::exchange(a.as_ptr(), missing.as_ptr()) }, 1);
+        let text = message().unwrap();
+        assert!(text.starts_with("File does not exist"), "{}", text);
+        assert!(text.contains("missing.txt"), "{}", text);
+
+        // A success keeps the message, a failure without context replaces it
+        let b = c_path("b.txt");
+        let options = super::ExchangeOptions::default();
+        super::exchange_with_rs(&dir.join("a.txt"), &dir.join("b.txt"), &options).unwrap();
+        assert_eq!(message().unwrap(), text);
+        assert_eq!(unsafe { super::exchange(a.as_ptr(), std::ptr::null()) }, 6);
+        assert_eq!(message().unwrap(), "Null pointer passed as path");
+        assert_eq!(unsafe { super::exchange(a.as_ptr(), b.as_ptr()) }, 0);
+
+        super::clear_last_error();
+        assert_eq!(message(), None);
+    }
+
+    #[test]
+    fn reports_nothing_to_do_for_equal_names() {
+        let dir = test_dir("noop");
+        fs::write(dir.join("a.txt"), "t").unwrap();
+        fs::write(dir.join("a.log"), "l").unwrap();
+
+        let err = super::exchange_rs(&dir.join("a.txt"), &dir.join("a.log")).unwrap_err();
+        assert_eq!(err.to_code(), 10);
+        assert_eq!(fs::read_to_string(dir.join("a.txt")).unwrap(), "t");
+        assert_eq!(fs::read_to_string(dir.join("a.log")).unwrap(), "l");
+    }
+
+    #[test]
+    fn detects_collision_with_bystander_in_same_dir() {
+        let dir = test_dir("bystander");
+        fs::write(dir.join("a.txt"), "a").unwrap();
+        fs::write(dir.join("b.log"), "b").unwrap();
+        // a.txt would become b.txt, which belongs to neither side of the swap
+        fs::write(dir.join("b.txt"), "bystander").unwrap();
+
+        let err = super::exchange_rs(&dir.join("a.txt"), &dir.join("b.log")).unwrap_err();
+        assert_eq!(err.to_code(), 3);
+        assert_eq!(fs::read_to_string(dir.join("a.txt")).unwrap(), "a");
+        assert_eq!(fs::read_to_string(dir.join("b.log")).unwrap(), "b");
+        assert_eq!(fs::read_to_string(dir.join("b.txt")).unwrap(), "bystander");
+    }
+
+    #[test]
+    fn force_overwrites_bystanders() {
+        let dir = test_dir("force");
+        fs::write(dir.join("a.txt"), "a").unwrap();
+        fs::write(dir.join("b.log"), "b").unwrap();
+        fs::write(dir.join("b.txt"), "bystander").unwrap();
+
+        let force = super::ExchangeOptions::from_flags(super::FLAG_FORCE);
+        super::exchange_with_rs(&dir.join("a.txt"), &dir.join("b.log"), &force).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("b.txt")).unwrap(), "a");
+        assert_eq!(fs::read_to_string(dir.join("a.log")).unwrap(), "b");
+
+        // A directory in the way needs the extra flag
+        fs::create_dir(dir.join("d")).unwrap();
+        fs::create_dir_all(dir.join("x").join("c")).unwrap();
+        fs::create_dir(dir.join("x").join("d")).unwrap();
+        fs::write(dir.join("x").join("d").join("old.txt"), "old").unwrap();
+        let (c, d) = (dir.join("x").join("c"), dir.join("d"));
+
+        let err = super::exchange_with_rs(&c, &d, &force).unwrap_err();
+        assert_eq!(err.to_code(), 3);
+        assert!(dir.join("x").join("d").join("old.txt").exists());
+
+        let force_dirs = super::ExchangeOptions::from_flags(
+            super::FLAG_FORCE | super::FLAG_FORCE_DIRS | super::FLAG_DELETE_PERMANENTLY,
+        );
+        super::exchange_with_rs(&c, &d, &force_dirs).unwrap();
+        assert!(dir.join("c").is_dir() && dir.join("x").join("d").is_dir());
+        assert!(!dir.join("x").join("d").join("old.txt").exists());
+    }
+
+    #[test]
+    fn keeps_bystanders_when_the_swap_fails() {
+        let dir = test_dir("force_rollback");
+        fs::write(dir.join("a.txt"), "a").unwrap();
+        fs::write(dir.join("b.log"), "b").unwrap();
+        fs::write(dir.join("b.txt"), "bystander").unwrap();
+
+        let force =
+            super::ExchangeOptions::from_flags(super::FLAG_FORCE | super::FLAG_DELETE_PERMANENTLY);
+        let exchange_info =
+            super::prepare_exchange_in(dir.join("a.txt"), dir.join("b.log"), &dir, &force).unwrap();
+        assert_eq!(exchange_info.overwritten.len(), 1);
+        // A non-empty directory at the other target makes its rename fail
+        fs::create_dir_all(dir.join("a.log").join("blocker")).unwrap();
+
+        assert!(super::commit_exchange(exchange_info).is_err());
+        assert_eq!(fs::read_to_string(dir.join("b.txt")).unwrap(), "bystander");
+        assert_eq!(fs::read_to_string(dir.join("a.txt")).unwrap(), "a");
+        assert_eq!(fs::read_to_string(dir.join("b.log")).unwrap(), "b");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 4);
+    }
+
+    #[test]
+    fn backs_up_before_swapping() {
+        let dir = test_dir("backup");
+        fs::write(dir.join("a.txt"), "a").unwrap();
+        fs::create_dir(dir.join("b")).unwrap();
+        fs::write(dir.join("b").join("inner.txt"), "inner").unwrap();
+        let backup_dir = dir.join("backups");
+
+        let options = super::ExchangeOptions {
+            backup_dir: Some(backup_dir.clone()),
+            ..Default::default()
+        };
+        super::exchange_with_rs(&dir.join("a.txt"), &dir.join("b"), &options).unwrap();
+        super::undo_last_rs().unwrap();
+        super::exchange_with_rs(&dir.join("a.txt"), &dir.join("b"), &options).unwrap();
+
+        // Each swap gets its own folder holding both originals
+        let folders: Vec<_> = fs::read_dir(&backup_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        assert_eq!(folders.len(), 2);
+        for folder in folders {
+            assert_eq!(fs::read_to_string(folder.join("1_a.txt")).unwrap(), "a");
+            let inner = folder.join("2_b").join("inner.txt");
+            assert_eq!(fs::read_to_string(inner).unwrap(), "inner");
+        }
+
+        // A backup that cannot succeed stops the swap
+        let inside = super::ExchangeOptions {
+            backup_dir: Some(dir.join("a").join("backups")),
+            ..Default::default()
+        };
+        let err = super::exchange_with_rs(&dir.join("b.txt"), &dir.join("a"), &inside).unwrap_err();
+        assert_eq!(err.to_code(), 11);
+        assert!(dir.join("b.txt").exists() && dir.join("a").is_dir());
+    }
+
+    #[test]
+    fn swaps_extensions_only_on_request() {
+        let dir = test_dir("swap_extensions");
+        fs::write(dir.join("a.txt"), "a").unwrap();
+        fs::write(dir.join("b.jpg"), "b").unwrap();
+
+        // Default, each file keeps its own extension
+        let options = super::ExchangeOptions::default();
+        super::exchange_with_rs(&dir.join("a.txt"), &dir.join("b.jpg"), &options).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("b.txt")).unwrap(), "a");
+        assert_eq!(fs::read_to_string(dir.join("a.jpg")).unwrap(), "b");
+
+        // The whole name moves, which puts the contents under the other name
+        let options = super::ExchangeOptions::from_flags(super::FLAG_SWAP_EXTENSIONS);
+        super::exchange_with_rs(&dir.join("b.txt"), &dir.join("a.jpg"), &options).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("a.jpg")).unwrap(), "a");
+        assert_eq!(fs::read_to_string(dir.join("b.txt")).unwrap(), "b");
+
+        // Same stem, only the extensions differ
+        fs::write(dir.join("c.tar.gz"), "c").unwrap();
+        fs::write(dir.join("c.zip"), "z").unwrap();
+        let default = super::ExchangeOptions::default();
+        assert!(matches!(
+            super::exchange_with_rs(&dir.join("c.tar.gz"), &dir.join("c.zip"), &default),
+            Err(super::RenameError::NothingToDo)
+        ));
+        super::exchange_with_rs(&dir.join("c.tar.gz"), &dir.join("c.zip"), &options).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("c.zip")).unwrap(), "c");
+        assert_eq!(fs::read_to_string(dir.join("c.tar.gz")).unwrap(), "z");
+    }
+
+    #[test]
+    fn rejects_volume_roots() {
+        let (root1, root2) = if cfg!(windows) {
+            (c"C:\\", c"D:\\")
+        } else {
+            (c"/", c"//")
+        };
+        assert_eq!(
+            unsafe { super::exchange(root1.as_ptr(), root2.as_ptr()) },
+            16
+        );
+
+        // One root is enough, and the other item is left alone
+        let dir = test_dir("volume_root");
+        fs::write(dir.join("a.txt"), "a").unwrap();
+        let result = super::exchange_with_rs(
+            &dir.join("a.txt"),
+            &PathBuf::from(root1.to_string_lossy().to_string()),
+            &super::ExchangeOptions::default(),
+        );
+        assert!(matches!(result, Err(super::RenameError::VolumeRoot(_))));
+        assert!(dir.join("a.txt").exists());
+
+        // A link pointing at a root is not a root itself
+        use crate::types::GetPathInfo;
+        let root = dir.ancestors().last().unwrap().to_path_buf();
+        let link = dir.join("root_link");
+        link_dir(&root, &link);
+        assert!(GetPathInfo::validate_not_root(&link).is_ok());
+        assert!(GetPathInfo::validate_not_root(&root).is_err());
+        super::exchange_with_rs(
+            &dir.join("a.txt"),
+            &link,
+            &super::ExchangeOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(fs::read_to_string(dir.join("root_link.txt")).unwrap(), "a");
+        assert!(fs::symlink_metadata(dir.join("a"))
+            .unwrap()
+            .file_type()
+            .is_symlink());
+    }
+
+    #[test]
+    fn rejects_paths_with_nul() {
+        let dir = test_dir("embedded_nul");
+        fs::write(dir.join("a.txt"), "a").unwrap();
+        fs::write(dir.join("b.txt"), "b").unwrap();
+
+        let err = super::exchange_rs(&dir.join("a.txt\0.log"), &dir.join("b.txt")).unwrap_err();
+        assert!(matches!(err, super::RenameError::EmbeddedNul(_)));
+        assert_eq!(err.to_code(), 17);
+        assert!(err.to_string().contains("a.txt\\0.log"));
+        assert!(dir.join("a.txt").exists() && dir.join("b.txt").exists());
+    }
+
+    #[test]
+    fn measures_copies_across_volumes() {
+        let dir = test_dir("copy_size");
+        fs::write(dir.join("a.txt"), "a").unwrap();
+        fs::create_dir(dir.join("b")).unwrap();
+        fs::write(dir.join("b").join("data.bin"), vec![0; 4096]).unwrap();
+
+        // Both items stay in their directory, nothing is copied
+        let options = super::ExchangeOptions::default();
+        let bytes = super::copy_size_rs(&dir.join("a.txt"), &dir.join("b"), &options);
+        assert_eq!(bytes.unwrap(), 0);
+    }
+
+    #[test]
+    fn refuses_copies_that_do_not_fit() {
+        let dir = test_dir("copy_space");
+        fs::create_dir(dir.join("src")).unwrap();
+        fs::write(dir.join("src").join("a.bin"), vec![0; 4096]).unwrap();
+        fs::create_dir(dir.join("dst")).unwrap();
+        let (from, to) = (dir.join("src").join("a.bin"), dir.join("dst").join("b.bin"));
+        let moves = [(from.as_path(), to.as_path())];
+        // "dst" acts as another volume
+        let dst = dir.join("dst");
+        let volume_id = |path: &std::path::Path| Some(u64::from(path.starts_with(&dst)));
+        let check = |allow_copy, free| {
+            crate::copy_move::check_copy_space_with(&moves, allow_copy, volume_id, |_| free)
+        };
+
+        assert_eq!(check(true, Some(1 << 20)).unwrap(), 4096);
+        // Unknown free space is left to the copy
+        assert_eq!(check(true, None).unwrap(), 4096);
+        assert_eq!(check(true, Some(1024)).unwrap_err().to_code(), 18);
+        assert_eq!(check(false, Some(1 << 20)).unwrap_err().to_code(), 20);
+        assert!(from.exists());
+    }
+
+    #[test]
+    fn checks_exchange_ex_flags() {
+        let dir = test_dir("exchange_ex_flags");
+        fs::write(dir.join("a.txt"), "a").unwrap();
+        fs::write(dir.join("b.txt"), "b").unwrap();
+        let (a, b) = (
+            std::ffi::CString::new(dir.join("a.txt").to_string_lossy().as_ref()).unwrap(),
+            std::ffi::CString::new(dir.join("b.txt").to_string_lossy().as_ref()).unwrap(),
+        );
+
+        // Unknown bits are refused before anything is looked at
+        let code = unsafe { super::exchange_ex(a.as_ptr(), b.as_ptr(), 1 << 31) };
+        assert_eq!(code, 19);
+        let both = super::FLAG_LOWERCASE | super::FLAG_TITLECASE;
+        let code = unsafe { super::exchange_ex(a.as_ptr(), b.as_ptr(), both) };
+        assert_eq!(code, 19);
+        let code = unsafe { super::exchange_ex(a.as_ptr(), b.as_ptr(), super::FLAG_DRY_RUN) };
+        assert_eq!(code, 0);
+        assert_eq!(fs::read_to_string(dir.join("a.txt")).unwrap(), "a");
+
+        // Copying across volumes stays on unless refused, as for `exchange`
+        assert!(super::ExchangeOptions::default().cross_volume_copy);
+        assert!(super::ExchangeOptions::from_flags(0).cross_volume_copy);
+        let refuse = super::ExchangeOptions::from_flags(super::FLAG_REFUSE_CROSS_VOLUME);
+        assert!(!refuse.cross_volume_copy);
+
+        // Following the link renames the directory it points at
+        fs::create_dir(dir.join("real")).unwrap();
+        link_dir(&dir.join("real"), &dir.join("link"));
+        let options = super::ExchangeOptions::from_flags(super::FLAG_FOLLOW_SYMLINKS);
+        let outcome = super::exchange_with_rs(&dir.join("link"), &dir.join("a.txt"), &options);
+        assert_eq!(outcome.unwrap().new_path1, dir.join("a"));
+        assert!(dir.join("a").is_dir());
+        assert_eq!(fs::read_to_string(dir.join("real.txt")).unwrap(), "a");
+        assert!(fs::symlink_metadata(dir.join("link")).is_ok());
+    }
+
+    #[test]
+    fn renames_to_chosen_names() {
+        let dir = test_dir("named");
+        fs::write(dir.join("a.txt"), "a").unwrap();
+        fs::write(dir.join("b.txt"), "b").unwrap();
+        let options = super::ExchangeOptions::default();
+
+        // Taking the name of the other item goes through the temporary name
+        let outcome = super::exchange_named_rs(
+            &dir.join("a.txt"),
+            &dir.join("b.txt"),
+            "b.txt",
+            "c.md",
+            &options,
+        )
+        .unwrap();
+        assert_eq!(outcome.new_path2, dir.join("c.md"));
+        assert_eq!(fs::read_to_string(dir.join("b.txt")).unwrap(), "a");
+        assert_eq!(fs::read_to_string(dir.join("c.md")).unwrap(), "b");
+
+        for (name1, name2) in [("x", "x"), ("sub/x", "y"), ("", "y")] {
+            let result = super::exchange_named_rs(
+                &dir.join("b.txt"),
+                &dir.join("c.md"),
+                name1,
+                name2,
+                &options,
+            );
+            assert!(result.is_err(), "{:?}", (name1, name2));
+        }
+        assert!(dir.join("b.txt").exists() && dir.join("c.md").exists());
+    }
+
+    #[test]
+    fn names_the_failing_rename() {
+        let dir = test_dir("failing_rename");
+        fs::create_dir(dir.join("locked")).unwrap();
+        fs::write(dir.join("locked").join("a.txt"), "a").unwrap();
+        fs::write(dir.join("b.txt"), "b").unwrap();
+
+        // A read-only directory on Unix, a handle that does not share deletion on Windows
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(dir.join("locked"), fs::Permissions::from_mode(0o555)).unwrap();
+        }
+        #[cfg(windows)]
+        let handle = {
+            use std::os::windows::fs::OpenOptionsExt;
+            fs::OpenOptions::new()
+                .read(true)
+                .share_mode(1)
+                .open(dir.join("locked").join("a.txt"))
+                .unwrap()
+        };
+
+        let options = super::ExchangeOptions {
+            rename_attempts: 1,
+            ..Default::default()
+        };
+        let result = super::exchange_with_rs(
+            &dir.join("locked").join("a.txt"),
+            &dir.join("b.txt"),
+            &options,
+        );
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(dir.join("locked"), fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        #[cfg(windows)]
+        drop(handle);
+        // Root ignores the permissions, nothing to check then
+        if result.is_ok() {
+            return;
+        }
+        let detail = super::last_error_detail_rs().unwrap();
+        assert!(detail.contains("a.txt"), "{}", detail);
+        assert!(super::last_operation_log_rs().contains(&detail));
+        assert_eq!(fs::read_to_string(dir.join("b.txt")).unwrap(), "b");
+    }
+
+    #[test]
+    fn verifies_swap_on_disk() {
+        let dir = test_dir("verify");
+        fs::create_dir(dir.join("outer")).unwrap();
+        fs::create_dir(dir.join("outer").join("inner")).unwrap();
+        fs::write(dir.join("a.txt"), "a").unwrap();
+        fs::write(dir.join("b.txt"), "b").unwrap();
+
+        let options = super::ExchangeOptions::from_flags(super::FLAG_VERIFY);
+        super::exchange_with_rs(&dir.join("a.txt"), &dir.join("b.txt"), &options).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("a.txt")).unwrap(), "b");
+
+        // Nested items, the inner one ends up below the renamed outer one
+        let outer = dir.join("outer");
+        super::exchange_with_rs(&outer, &outer.join("inner"), &options).unwrap();
+        assert!(dir.join("inner").join("outer").is_dir());
+    }
+
+    #[test]
+    fn batch_reports_every_pair() {
+        let dir = test_dir("batch");
+        for name in ["a.txt", "b.log", "c.txt", "d.log"] {
+            fs::write(dir.join(name), name).unwrap();
+        }
+
+        let pairs = vec![
+            (dir.join("a.txt"), dir.join("b.log")),
+            (dir.join("missing.txt"), dir.join("c.txt")),
+            (dir.join("c.txt"), dir.join("d.log")),
+        ];
+        let mut codes = Vec::new();
+        let options = super::ExchangeOptions::default();
+        let failed = super::exchange_many_rs(&pairs, &options, |index, result| {
+            codes.push((index, result.as_ref().map_or_else(|e| e.to_code(), |_| 0)));
+        });
+
+        assert_eq!(failed, 1);
+        assert_eq!(codes, [(0, 0), (1, 1), (2, 0)]);
+        assert_eq!(fs::read_to_string(dir.join("b.txt")).unwrap(), "a.txt");
+        assert_eq!(fs::read_to_string(dir.join("c.log")).unwrap(), "d.log");
+
+        // Cancelling stops the pairs not started yet
+        let cancel = std::sync::Arc::new(super::CancelToken::default());
+        let options = super::ExchangeOptions {
+            cancel: Some(cancel.clone()),
+            ..Default::default()
+        };
+        let pairs = vec![
+            (dir.join("b.txt"), dir.join("a.log")),
+            (dir.join("c.log"), dir.join("d.txt")),
+        ];
+        let mut codes = Vec::new();
+        let failed = super::exchange_many_rs(&pairs, &options, |index, result| {
+            codes.push((index, result.as_ref().map_or_else(|e| e.to_code(), |_| 0)));
+            cancel.cancel();
+        });
+        assert_eq!(failed, 1);
+        assert_eq!(codes, [(0, 0), (1, 13)]);
+        assert_eq!(fs::read_to_string(dir.join("a.txt")).unwrap(), "a.txt");
+        assert_eq!(fs::read_to_string(dir.join("c.log")).unwrap(), "d.log");
+    }
+
+    #[test]
+    fn retries_only_sharing_and_lock_violations() {
+        use crate::types::NameExchange;
+        use std::io;
+
+        let denied = io::Error::from(io::ErrorKind::PermissionDenied);
+        assert!(!NameExchange::is_transient(&denied));
+        #[cfg(windows)]
+        {
+            assert!(NameExchange::is_transient(&io::Error::from_raw_os_error(
+                32
+            )));
+            assert!(NameExchange::is_transient(&io::Error::from_raw_os_error(
+                33
+            )));
+            // ERROR_ACCESS_DENIED fails again on every attempt
+            assert!(!NameExchange::is_transient(&io::Error::from_raw_os_error(
+                5
+            )));
+        }
+    }
+
+    #[test]
+    fn creates_missing_target_parent_only_on_request() {
+        use crate::types::NameExchange;
+
+        let dir = test_dir("create_parents");
+        fs::write(dir.join("a.txt"), "a").unwrap();
+        let target = dir.join("new").join("a.txt");
+
+        let options = super::ExchangeOptions::default();
+        assert!(NameExchange::handle_rename(&options, &dir.join("a.txt"), &target).is_err());
+        assert!(!dir.join("new").exists());
+
+        let options = super::ExchangeOptions::from_flags(super::FLAG_CREATE_PARENTS);
+        NameExchange::handle_rename(&options, &dir.join("a.txt"), &target).unwrap();
+        assert_eq!(fs::read_to_string(&target).unwrap(), "a");
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn reports_cloud_provider_errors() {
+        // ERROR_CLOUD_FILE_PROVIDER_NOT_RUNNING
+        let err = super::RenameError::from(std::io::Error::from_raw_os_error(362));
+        assert_eq!(err.to_code(), 15);
+        let err = super::RenameError::from(std::io::Error::from_raw_os_error(5));
+        assert_eq!(err.to_code(), 2);
+    }
+
+    #[test]
+    fn swap_types_cross_threads() {
+        // The GUI runs swaps on a worker thread and posts the results back
+        fn assert_send<T: Send + 'static>() {}
+        assert_send::<super::ExchangeOptions>();
+        assert_send::<super::ExchangeOutcome>();
+        assert_send::<super::RenameError>();
+    }
+
+    #[test]
+    fn reports_version_and_build() {
+        let version = unsafe { std::ffi::CStr::from_ptr(super::lib_version()) };
+        assert_eq!(version.to_str().unwrap(), env!("CARGO_PKG_VERSION"));
+
+        let info = unsafe { std::ffi::CStr::from_ptr(super::build_info()) };
+        let (target, profile) = info.to_str().unwrap().split_once(' ').unwrap();
+        assert!(!target.is_empty() && !profile.is_empty());
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn matches_names_across_normalization_forms() {
+        let dir = test_dir("nfc");
+        // "café" composed on disk, typed decomposed
+        let composed = "caf\u{e9}";
+        let decomposed = "cafe\u{301}";
+        fs::write(dir.join(format!("{composed}.txt")), "cafe").unwrap();
+        fs::write(dir.join(format!("{decomposed}-old.log")), "old").unwrap();
+
+        let outcome = super::exchange_outcome_rs(
+            &dir.join(format!("{decomposed}.txt")),
+            &dir.join(format!("{decomposed}-old.log")),
+        )
+        .unwrap();
+        // The new names are composed whichever form the items had
+        let name = |path: &std::path::Path| path.file_name().unwrap().to_owned();
+        assert_eq!(name(&outcome.new_path1), *format!("{composed}-old.txt"));
+        assert_eq!(name(&outcome.new_path2), *format!("{composed}.log"));
+        assert_eq!(fs::read_to_string(&outcome.new_path1).unwrap(), "cafe");
+        assert_eq!(fs::read_to_string(&outcome.new_path2).unwrap(), "old");
+    }
+
+    #[test]
+    fn cancelled_copy_leaves_the_source_alone() {
+        let dir = test_dir("cancel");
+        let from = dir.join("tree");
+        fs::create_dir_all(from.join("sub")).unwrap();
+        for name in ["a.txt", "b.txt", "sub/c.txt"] {
+            fs::write(from.join(name), name).unwrap();
+        }
+
+        let token = super::CancelToken::default();
+        token.cancel();
+        let to = dir.join("copy");
+        let err = crate::copy_move::move_by_copy(&from, &to, Some(&token)).unwrap_err();
+        assert_eq!(err.to_code(), 13);
+        assert!(!to.exists());
+        assert_eq!(
+            fs::read_to_string(from.join("sub/c.txt")).unwrap(),
+            "sub/c.txt"
+        );
+
+        crate::copy_move::move_by_copy(&from, &to, None).unwrap();
+        assert!(!from.exists() && to.join("sub/c.txt").exists());
+    }
+
+    #[test]
+    fn keeps_trailing_spaces_and_dots() {
+        let dir = test_dir("trailing");
+        // Created through the prefix, Windows would trim the names otherwise
+        let path = |name: &str| crate::long_path::to_extended(&dir.join(name));
+        fs::write(path("note "), "note").unwrap();
+        fs::write(path("other"), "other").unwrap();
+        fs::create_dir(path("dir.")).unwrap();
+        fs::create_dir(path("folder")).unwrap();
+
+        super::exchange_rs(&dir.join("note "), &dir.join("other")).unwrap();
+        assert_eq!(fs::read_to_string(path("note ")).unwrap(), "other");
+        assert_eq!(fs::read_to_string(path("other")).unwrap(), "note");
+
+        super::exchange_rs(&dir.join("dir."), &dir.join("folder")).unwrap();
+        assert!(path("dir.").is_dir() && path("folder").is_dir());
+        let names: Vec<_> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(names.len(), 4);
+    }
+
+    #[test]
+    fn refuses_hard_links_to_one_file() {
+        let dir = test_dir("hardlink");
+        fs::write(dir.join("a.txt"), "a").unwrap();
+        fs::hard_link(dir.join("a.txt"), dir.join("b.log")).unwrap();
+
+        let err = super::exchange_rs(&dir.join("a.txt"), &dir.join("b.log")).unwrap_err();
+        assert_eq!(err.to_code(), 4);
+        assert!(dir.join("a.txt").exists() && dir.join("b.log").exists());
+    }
+
+    #[test]
+    fn rejects_null_and_invalid_utf8() {
+        let valid = c"1.ext1";
+        let invalid = [0xFFu8, 0xFE, 0x00];
+
+        let code = unsafe { super::exchange(std::ptr::null(), valid.as_ptr()) };
+        assert_eq!(code, 6);
+
+        let code = unsafe { super::exchange(invalid.as_ptr() as *const _, valid.as_ptr()) };
+        assert_eq!(code, 5);
     }
 }
@@ -0,0 +1,81 @@
+use std::path::Path;
+
+/// Names of the processes holding a file open
+///
+/// Asks the Windows Restart Manager which applications use `path`, turning a
+/// bare "permission denied" into "locked by Explorer / Word". Only available with
+/// the `lock-diagnostics` feature on Windows, otherwise always empty.
+///
+/// ### Parameters
+/// * `path` - File or directory that could not be renamed
+///
+/// ### Return Value
+/// Application names, empty when nothing was found or the query failed
+#[cfg(all(windows, feature = "lock-diagnostics"))]
+pub fn locking_processes(path: &Path) -> Vec<String> {
+    use std::{iter::once, os::windows::ffi::OsStrExt, ptr::null_mut};
+    use winapi::shared::winerror::{ERROR_MORE_DATA, ERROR_SUCCESS};
+    use winapi::um::restartmanager::{
+        RmEndSession, RmGetList, RmRegisterResources, RmStartSession, CCH_RM_SESSION_KEY,
+        RM_PROCESS_INFO,
+    };
+
+    let mut session = 0;
+    let mut key = [0u16; CCH_RM_SESSION_KEY + 1];
+    if unsafe { RmStartSession(&mut session, 0, key.as_mut_ptr()) } != ERROR_SUCCESS {
+        return Vec::new();
+    }
+
+    let wide: Vec<u16> = path.as_os_str().encode_wide().chain(once(0)).collect();
+    let mut files = [wide.as_ptr()];
+    let mut names = Vec::new();
+
+    let registered = unsafe {
+        RmRegisterResources(session, 1, files.as_mut_ptr(), 0, null_mut(), 0, null_mut())
+    };
+    if registered == ERROR_SUCCESS {
+        let mut infos: Vec<RM_PROCESS_INFO> = Vec::new();
+        // The list can grow between calls, so only retry a few times
+        for _ in 0..3 {
+            let mut needed = 0;
+            let mut count = infos.len() as u32;
+            let mut reasons = 0;
+            let buffer = if infos.is_empty() {
+                null_mut()
+            } else {
+                infos.as_mut_ptr()
+            };
+            let status =
+                unsafe { RmGetList(session, &mut needed, &mut count, buffer, &mut reasons) };
+
+            if status == ERROR_MORE_DATA {
+                infos = vec![unsafe { std::mem::zeroed() }; needed as usize];
+                continue;
+            }
+            if status == ERROR_SUCCESS {
+                names = infos
+                    .iter()
+                    .take(count as usize)
+                    .map(|info| {
+                        let name = &info.strAppName;
+                        let len = name.iter().position(|&c| c == 0).unwrap_or(name.len());
+                        String::from_utf16_lossy(&name[..len])
+                    })
+                    .collect();
+            }
+            break;
+        }
+    }
+
+    unsafe { RmEndSession(session) };
+    names
+}
+
+/// Names of the processes holding a file open
+///
+/// Process lookup needs the `lock-diagnostics` feature on Windows, so this
+/// always returns an empty list
+#[cfg(not(all(windows, feature = "lock-diagnostics")))]
+pub fn locking_processes(_path: &Path) -> Vec<String> {
+    Vec::new()
+}
@@ -0,0 +1,73 @@
+use std::{
+    ffi::{c_char, CString},
+    fmt,
+    sync::RwLock,
+};
+
+/// Severity of a log message passed to the registered callback
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogLevel {
+    /// Internal details such as resolved paths
+    Debug = 0,
+    /// Normal progress such as a completed swap
+    Info = 1,
+    /// Failures reported to the caller
+    Error = 2,
+}
+
+/// C callback receiving the log level and a NUL-terminated UTF-8 message
+///
+/// The message pointer is only valid for the duration of the call
+pub type LogCallback = extern "C" fn(level: LogLevel, msg: *const c_char);
+
+/// Registered log callback; logging is silent while this is `None`
+static LOG_CALLBACK: RwLock<Option<LogCallback>> = RwLock::new(None);
+
+/// Register or clear the callback that receives all library log messages
+///
+/// ### Parameters
+/// * `callback` - Callback to register, `None` to disable logging
+pub fn set_log_callback(callback: Option<LogCallback>) {
+    if let Ok(mut slot) = LOG_CALLBACK.write() {
+        *slot = callback;
+    }
+}
+
+/// Forward a message to the registered callback
+///
+/// The message is only formatted when a callback is registered, so logging
+/// costs nothing by default
+pub fn emit(level: LogLevel, args: fmt::Arguments) {
+    let callback = match LOG_CALLBACK.read() {
+        Ok(slot) => *slot,
+        Err(_) => None,
+    };
+
+    if let Some(callback) = callback {
+        let msg = args.to_string().replace('\0', " ");
+        if let Ok(msg) = CString::new(msg) {
+            callback(level, msg.as_ptr());
+        }
+    }
+}
+
+macro_rules! log_debug {
+    ($($arg:tt)*) => {
+        $crate::log::emit($crate::log::LogLevel::Debug, format_args!($($arg)*))
+    };
+}
+
+macro_rules! log_info {
+    ($($arg:tt)*) => {
+        $crate::log::emit($crate::log::LogLevel::Info, format_args!($($arg)*))
+    };
+}
+
+macro_rules! log_error {
+    ($($arg:tt)*) => {
+        $crate::log::emit($crate::log::LogLevel::Error, format_args!($($arg)*))
+    };
+}
+
+pub(crate) use {log_debug, log_error, log_info};
@@ -0,0 +1,69 @@
+use std::path::{Path, PathBuf};
+
+/// Length from which Windows APIs without the `\\?\` prefix start failing
+///
+/// `MAX_PATH` is 260 including the trailing NUL, and directories are further
+/// limited to 248 characters so that an 8.3 file name still fits
+pub const LONG_PATH_THRESHOLD: usize = 248;
+
+const VERBATIM_PREFIX: &str = r"\\?\";
+const VERBATIM_UNC_PREFIX: &str = r"\\?\UNC\";
+
+/// Add the extended-length `\\?\` prefix to a long absolute path
+///
+/// Also applied to a short path with a name ending in a dot or space, which
+/// Windows would otherwise silently trim. Other short paths, relative paths and
+/// already prefixed paths are returned unchanged. Does nothing on non-Windows platforms.
+///
+/// ### Parameters
+/// * `path` - Absolute, fully resolved path
+///
+/// ### Return Value
+/// Path usable by Windows file APIs regardless of its length
+pub fn to_extended(path: &Path) -> PathBuf {
+    if !cfg!(windows) {
+        return path.to_path_buf();
+    }
+
+    let text = path.to_string_lossy();
+    if text.starts_with(VERBATIM_PREFIX)
+        || (text.len() < LONG_PATH_THRESHOLD && !has_trimmed_name(&text))
+    {
+        return path.to_path_buf();
+    }
+
+    if let Some(unc) = text.strip_prefix(r"\\") {
+        PathBuf::from(format!("{}{}", VERBATIM_UNC_PREFIX, unc))
+    } else if path.is_absolute() {
+        PathBuf::from(format!("{}{}", VERBATIM_PREFIX, text))
+    } else {
+        path.to_path_buf()
+    }
+}
+
+/// Check whether a path has a name ending in a dot or space
+///
+/// `.` and `..` segments are not names and do not count
+pub fn has_trimmed_name(text: &str) -> bool {
+    text.split(['\\', '/'])
+        .filter(|name| !matches!(*name, "" | "." | ".."))
+        .any(|name| name.ends_with(['.', ' ']))
+}
+
+/// Remove the extended-length `\\?\` prefix before showing a path to the user
+///
+/// ### Parameters
+/// * `path` - Path possibly carrying `\\?\` or `\\?\UNC\`
+///
+/// ### Return Value
+/// Path in its familiar `C:\...` or `\\server\share\...` form
+pub fn strip_extended(path: &Path) -> PathBuf {
+    let text = path.to_string_lossy();
+    if let Some(unc) = text.strip_prefix(VERBATIM_UNC_PREFIX) {
+        PathBuf::from(format!(r"\\{}", unc))
+    } else if let Some(rest) = text.strip_prefix(VERBATIM_PREFIX) {
+        PathBuf::from(rest)
+    } else {
+        path.to_path_buf()
+    }
+}
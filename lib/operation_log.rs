@@ -0,0 +1,114 @@
+use std::{
+    cell::RefCell,
+    path::{Path, PathBuf},
+};
+
+use crate::last_error;
+use crate::log::log_debug;
+use crate::long_path::strip_extended;
+use crate::types::RenameError;
+
+thread_local! {
+    /// Steps of the exchange running on this thread, `None` outside of one
+    static STEPS: RefCell<Option<Vec<String>>> = const { RefCell::new(None) };
+    /// Summary of the last finished exchange on this thread
+    static LAST: RefCell<String> = const { RefCell::new(String::new()) };
+}
+
+/// Run the steps of an exchange while recording them for `last_operation_log`
+///
+/// ### Parameters
+/// * `renames` - Planned `(original, new)` path of every item, listed first on success
+/// * `steps` - Work to run
+///
+/// ### Return Value
+/// Result of `steps`
+pub(crate) fn run<T>(
+    renames: Vec<(PathBuf, PathBuf)>,
+    steps: impl FnOnce() -> Result<T, RenameError>,
+) -> Result<T, RenameError> {
+    begin();
+    let result = steps();
+    let summary = match &result {
+        Ok(_) => renames
+            .iter()
+            .map(|(from, to)| {
+                format!(
+                    "{} → {}",
+                    strip_extended(from).display(),
+                    strip_extended(to).display()
+                )
+            })
+            .collect(),
+        // Name the rename that failed, the error alone does not tell which one
+        Err(e) => match last_error::detail() {
+            Some(detail) => vec![format!("Failed: {} ({})", e, detail)],
+            None => vec![format!("Failed: {}", e)],
+        },
+    };
+    finish(summary);
+    result
+}
+
+/// Start recording the steps of an exchange, dropping any unfinished record
+fn begin() {
+    STEPS.with(|steps| *steps.borrow_mut() = Some(Vec::new()));
+}
+
+/// Add a line to the running record, ignored outside of an exchange
+pub(crate) fn record(line: impl Into<String>) {
+    STEPS.with(|steps| {
+        if let Some(steps) = steps.borrow_mut().as_mut() {
+            steps.push(line.into());
+        }
+    });
+}
+
+/// Record a completed rename
+///
+/// ### Parameters
+/// * `from` - Path before the rename
+/// * `to` - Path after the rename
+/// * `copied` - The rename fell back to copying across volumes
+pub(crate) fn record_rename(from: &Path, to: &Path, copied: bool) {
+    let from = strip_extended(from);
+    let to = strip_extended(to);
+    if copied {
+        record(format!(
+            "  {} → {} (copied across volumes, original deleted)",
+            from.display(),
+            to.display()
+        ));
+    } else {
+        record(format!("  {} → {}", from.display(), to.display()));
+    }
+}
+
+/// Finish the running record and keep it for `last_operation_log`
+///
+/// ### Parameters
+/// * `summary` - Lines placed before the recorded steps
+fn finish(summary: Vec<String>) {
+    let steps = STEPS
+        .with(|steps| steps.borrow_mut().take())
+        .unwrap_or_default();
+    let text = summary
+        .into_iter()
+        .chain(steps)
+        .collect::<Vec<_>>()
+        .join("\n");
+    log_debug!("Operation summary:\n{}", text);
+    LAST.with(|last| *last.borrow_mut() = text);
+}
+
+/// Summary of the last exchange on the calling thread
+///
+/// The first lines name every item as `old → new` (or the error on failure),
+/// followed by the branch taken, each rename actually performed including
+/// the temporary names, rollbacks and copies across volumes
+///
+/// ### Return Value
+/// Multi-line summary, empty before the first exchange
+pub fn last_operation_log() -> String {
+    LAST.with(|last| last.borrow().clone())
+}
@@ -1,9 +1,21 @@
 use std::{
-    ffi::OsStr,
-    path::{Path, PathBuf},
+    fs, io,
+    path::{Component, Path, PathBuf},
 };
 
+use crate::exchange::is_same_path;
+use crate::last_error;
+use crate::log::log_error;
+use crate::long_path::{strip_extended, to_extended};
 use crate::types::*;
+use crate::unicode::to_nfc;
+
+/// Characters Windows forbids in file names
+const ILLEGAL_CHARS: [char; 9] = ['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
+/// Longest file name NTFS and FAT accept, in UTF-16 units
+const MAX_COMPONENT_LEN: usize = 255;
+/// Longest extended-length (`\\?\`) path, in UTF-16 units
+const MAX_EXTENDED_PATH_LEN: usize = 32767;
 
 /// All path-related operations
 impl GetPathInfo {
@@ -17,6 +29,229 @@ impl GetPathInfo {
         (self.path1.is_file(), self.path2.is_file())
     }
 
+    /// Check both paths again before renaming, since either may have been
+    /// deleted or replaced on disk after the swap was planned
+    ///
+    /// ### Parameters
+    /// * `was_file` - Result of `if_file` when the swap was planned
+    ///
+    /// ### Return Value
+    /// * `Ok(())` - Both paths still exist with the same type
+    /// * `Err(RenameError::NotExists)` - A path has vanished
+    /// * `Err(RenameError::ItemChanged)` - A file became a directory or the other way round
+    pub fn refresh(&self, was_file: (bool, bool)) -> Result<(), RenameError> {
+        let is_file = self.if_file();
+        for (path, was_file, is_file) in [
+            (&self.path1, was_file.0, is_file.0),
+            (&self.path2, was_file.1, is_file.1),
+        ] {
+            let shown = strip_extended(path).display().to_string();
+            // Not following links, as `resolve_path` does, so a dangling link still counts
+            if fs::symlink_metadata(to_extended(path)).is_err() {
+                log_error!("Path vanished before renaming: {}", shown);
+                last_error::set_detail(shown);
+                return Err(RenameError::NotExists);
+            }
+            if was_file != is_file {
+                let detail = if was_file {
+                    format!("{} is now a directory", shown)
+                } else {
+                    format!("{} is now a file", shown)
+                };
+                log_error!("Path changed type before renaming: {}", detail);
+                return Err(RenameError::ItemChanged(detail));
+            }
+        }
+        Ok(())
+    }
+
+    /// Check if both paths are located in the same directory
+    ///
+    /// Parents are compared as `Option<&Path>`, so two drive roots (no parent)
+    /// compare equal and a root never matches a non-root path
+    ///
+    /// ### Return Value
+    /// * `true` - Both paths share the same parent, or both are roots
+    /// * `false` - Paths live in different directories
+    pub fn if_same_dir(&self) -> bool {
+        self.path1.parent() == self.path2.parent()
+    }
+
+    /// Check whether both paths name the same file system object
+    ///
+    /// Compares file identity instead of path text, so hard links, 8.3 short
+    /// names and other spellings of one item are recognized. Links themselves are
+    /// not followed, a link and its target count as different items.
+    ///
+    /// ### Return Value
+    /// * `true` - Both paths refer to one file or directory
+    /// * `false` - Different items, or the identity of either cannot be read
+    pub fn are_same_file(&self) -> bool {
+        match (Self::file_id(&self.path1), Self::file_id(&self.path2)) {
+            (Some(id1), Some(id2)) => id1 == id2,
+            _ => false,
+        }
+    }
+
+    /// Volume serial number and file index of a path, not following a final link
+    #[cfg(windows)]
+    fn file_id(path: &Path) -> Option<(u32, u32, u32)> {
+        use std::os::windows::ffi::OsStrExt;
+        use winapi::um::fileapi::{
+            CreateFileW, GetFileInformationByHandle, BY_HANDLE_FILE_INFORMATION, OPEN_EXISTING,
+        };
+        use winapi::um::handleapi::{CloseHandle, INVALID_HANDLE_VALUE};
+        use winapi::um::winnt::{FILE_SHARE_DELETE, FILE_SHARE_READ, FILE_SHARE_WRITE};
+
+        // Needed to open a directory, and to open a link rather than its target
+        const FILE_FLAG_BACKUP_SEMANTICS: u32 = 0x0200_0000;
+        const FILE_FLAG_OPEN_REPARSE_POINT: u32 = 0x0020_0000;
+
+        let wide: Vec<u16> = crate::long_path::to_extended(path)
+            .as_os_str()
+            .encode_wide()
+            .chain(std::iter::once(0))
+            .collect();
+        unsafe {
+            // No access rights are needed to query the identity
+            let handle = CreateFileW(
+                wide.as_ptr(),
+                0,
+                FILE_SHARE_READ | FILE_SHARE_WRITE | FILE_SHARE_DELETE,
+                std::ptr::null_mut(),
+                OPEN_EXISTING,
+                FILE_FLAG_BACKUP_SEMANTICS | FILE_FLAG_OPEN_REPARSE_POINT,
+                std::ptr::null_mut(),
+            );
+            if handle == INVALID_HANDLE_VALUE {
+                return None;
+            }
+            let mut info: BY_HANDLE_FILE_INFORMATION = std::mem::zeroed();
+            let ok = GetFileInformationByHandle(handle, &mut info) != 0;
+            CloseHandle(handle);
+            ok.then_some((
+                info.dwVolumeSerialNumber,
+                info.nFileIndexHigh,
+                info.nFileIndexLow,
+            ))
+        }
+    }
+
+    /// Device and inode number of a path, not following a final link
+    #[cfg(unix)]
+    fn file_id(path: &Path) -> Option<(u64, u64)> {
+        use std::os::unix::fs::MetadataExt;
+
+        fs::symlink_metadata(path)
+            .ok()
+            .map(|metadata| (metadata.dev(), metadata.ino()))
+    }
+
+    #[cfg(not(any(windows, unix)))]
+    fn file_id(_path: &Path) -> Option<()> {
+        None
+    }
+
+    /// Identifier of the volume holding a path, not following a final link
+    #[cfg(windows)]
+    pub(crate) fn volume_id(path: &Path) -> Option<u64> {
+        Self::file_id(path).map(|(serial, _, _)| u64::from(serial))
+    }
+
+    /// Identifier of the volume holding a path, not following a final link
+    #[cfg(unix)]
+    pub(crate) fn volume_id(path: &Path) -> Option<u64> {
+        Self::file_id(path).map(|(device, _)| device)
+    }
+
+    #[cfg(not(any(windows, unix)))]
+    pub(crate) fn volume_id(_path: &Path) -> Option<u64> {
+        None
+    }
+
+    /// Whether a directory is flagged case-sensitive, as done for WSL interop
+    /// on Windows 10 1803 and later
+    ///
+    /// ### Return Value
+    /// `false` for ordinary directories and when the flag cannot be queried
+    #[cfg(windows)]
+    pub(crate) fn is_case_sensitive_dir(dir: &Path) -> bool {
+        use std::os::windows::ffi::OsStrExt;
+        use winapi::um::fileapi::{CreateFileW, OPEN_EXISTING};
+        use winapi::um::handleapi::{CloseHandle, INVALID_HANDLE_VALUE};
+        use winapi::um::minwinbase::FILE_INFO_BY_HANDLE_CLASS;
+        use winapi::um::winbase::GetFileInformationByHandleEx;
+        use winapi::um::winnt::{
+            FILE_READ_ATTRIBUTES, FILE_SHARE_DELETE, FILE_SHARE_READ, FILE_SHARE_WRITE,
+        };
+
+        // Not exported by winapi: FileCaseSensitiveInfo, FILE_CS_FLAG_CASE_SENSITIVE_DIR
+        const FILE_CASE_SENSITIVE_INFO: FILE_INFO_BY_HANDLE_CLASS = 23;
+        const CASE_SENSITIVE_DIR: u32 = 0x0000_0001;
+        const FILE_FLAG_BACKUP_SEMANTICS: u32 = 0x0200_0000;
+
+        let wide: Vec<u16> = crate::long_path::to_extended(dir)
+            .as_os_str()
+            .encode_wide()
+            .chain(std::iter::once(0))
+            .collect();
+        unsafe {
+            let handle = CreateFileW(
+                wide.as_ptr(),
+                FILE_READ_ATTRIBUTES,
+                FILE_SHARE_READ | FILE_SHARE_WRITE | FILE_SHARE_DELETE,
+                std::ptr::null_mut(),
+                OPEN_EXISTING,
+                FILE_FLAG_BACKUP_SEMANTICS,
+                std::ptr::null_mut(),
+            );
+            if handle == INVALID_HANDLE_VALUE {
+                return false;
+            }
+            let mut flags: u32 = 0;
+            let ok = GetFileInformationByHandleEx(
+                handle,
+                FILE_CASE_SENSITIVE_INFO,
+                &mut flags as *mut u32 as *mut _,
+                std::mem::size_of::<u32>() as u32,
+            ) != 0;
+            CloseHandle(handle);
+            ok && flags & CASE_SENSITIVE_DIR != 0
+        }
+    }
+
+    /// Bytes available to the current user on the volume holding `dir`
+    ///
+    /// ### Return Value
+    /// Free bytes, `None` when they cannot be queried (always outside Windows)
+    #[cfg(windows)]
+    pub(crate) fn free_space(dir: &Path) -> Option<u64> {
+        use std::os::windows::ffi::OsStrExt;
+        use winapi::um::fileapi::GetDiskFreeSpaceExW;
+        use winapi::um::winnt::ULARGE_INTEGER;
+
+        let wide: Vec<u16> = strip_extended(dir)
+            .as_os_str()
+            .encode_wide()
+            .chain(std::iter::once(0))
+            .collect();
+        unsafe {
+            let mut available: ULARGE_INTEGER = std::mem::zeroed();
+            let ok = GetDiskFreeSpaceExW(
+                wide.as_ptr(),
+                &mut available,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+            ) != 0;
+            ok.then(|| *available.QuadPart())
+        }
+    }
+
+    #[cfg(not(windows))]
+    pub(crate) fn free_space(_dir: &Path) -> Option<u64> {
+        None
+    }
+
     /// Detect if there is an inclusion relationship between two paths (parent-child directory issue)
     ///
     /// This method is used to determine if there is an inclusion relationship between two paths,
@@ -39,7 +274,10 @@ impl GetPathInfo {
 
     /// Helper function: Check if there is a parent-child directory relationship
     ///
-    /// Determine if potential_parent is the parent or ancestor directory of potential_child
+    /// Determine if potential_parent is the parent or ancestor directory of potential_child.
+    /// Both paths are normalized first, so `..` segments or mixed separators
+    /// cannot hide or fake a containment. Letter case is compared with the
+    /// rules of each directory, as for `is_same_path`
     ///
     /// ### Parameters
     /// * `potential_parent` - Potential parent directory path
@@ -48,15 +286,161 @@ impl GetPathInfo {
     /// ### Return Value
     /// * `true` - There is indeed a parent-child relationship
     /// * `false` - No parent-child relationship
-    fn path_is_parent(potential_parent: &Path, potential_child: &Path) -> bool {
-        // Try to determine the path of child relative to parent
-        if let Ok(relative) = potential_child.strip_prefix(potential_parent) {
-            *relative != *""
+    pub(crate) fn path_is_parent(potential_parent: &Path, potential_child: &Path) -> bool {
+        let parent = Self::normalize(potential_parent);
+        let child = Self::normalize(potential_child);
+
+        // Compare whole components, so `data` never contains `data-old`
+        // and a trailing separator makes no difference
+        let depth = parent.components().count();
+        if child.components().count() <= depth {
+            return false;
+        }
+        let ancestor: PathBuf = child.components().take(depth).collect();
+        is_same_path(&parent, &ancestor)
+    }
+
+    /// Bring a path into a single spelling for comparison
+    ///
+    /// Existing paths are canonicalized. Otherwise separators are unified and
+    /// `.` / `..` segments are resolved lexically.
+    ///
+    /// ### Parameters
+    /// * `path` - Path to normalize
+    ///
+    /// ### Return Value
+    /// Normalized path without the extended-length prefix
+    fn normalize(path: &Path) -> PathBuf {
+        if let Ok(canonical) = Self::canonicalize_entry(path) {
+            return strip_extended(&canonical);
+        }
+        Self::normalize_lexically(path)
+    }
+
+    /// Unify separators and resolve `.` / `..` segments without touching the file system
+    ///
+    /// ### Parameters
+    /// * `path` - Path to normalize
+    ///
+    /// ### Return Value
+    /// Normalized path without the extended-length prefix
+    fn normalize_lexically(path: &Path) -> PathBuf {
+        let unified = if cfg!(windows) {
+            path.to_string_lossy().replace('/', "\\")
         } else {
-            false
+            path.to_string_lossy().replace('\\', "/")
+        };
+
+        let mut normalized = PathBuf::new();
+        for component in Path::new(&unified).components() {
+            match component {
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    // Never climb above a root or prefix, and keep leading ".." of relative paths
+                    if matches!(
+                        normalized.components().next_back(),
+                        Some(Component::Normal(_))
+                    ) {
+                        normalized.pop();
+                    } else if !normalized.has_root() {
+                        normalized.push(component);
+                    }
+                }
+                _ => normalized.push(component),
+            }
+        }
+        strip_extended(&normalized)
+    }
+
+    /// Canonicalize a path without following it when the path itself is a link
+    ///
+    /// Only the parent directory is resolved for a symbolic link or junction,
+    /// so the swap renames the link rather than the item it points at
+    ///
+    /// ### Parameters
+    /// * `path` - Path to canonicalize
+    ///
+    /// ### Return Value
+    /// * `Ok(PathBuf)` - Canonical path of the entry itself
+    /// * `Err(io::Error)` - Path or its parent does not exist
+    pub(crate) fn canonicalize_entry(path: &Path) -> io::Result<PathBuf> {
+        // Junctions count as symbolic links here on Windows
+        let is_link = fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_symlink());
+        match (is_link, path.file_name()) {
+            (true, Some(name)) => {
+                let parent = match path.parent() {
+                    Some(parent) if *parent != *"" => parent,
+                    _ => Path::new("."),
+                };
+                Ok(parent.canonicalize()?.join(name))
+            }
+            _ => path.canonicalize(),
+        }
+    }
+
+    /// Check whether a path is an NTFS junction (mount point reparse point)
+    ///
+    /// ### Parameters
+    /// * `path` - Path to check, not followed
+    ///
+    /// ### Return Value
+    /// * `true` - Path is a junction
+    /// * `false` - Path is anything else, or cannot be read
+    #[cfg(windows)]
+    pub(crate) fn is_junction(path: &Path) -> bool {
+        use std::os::windows::ffi::OsStrExt;
+        use winapi::um::fileapi::{FindClose, FindFirstFileW};
+        use winapi::um::handleapi::INVALID_HANDLE_VALUE;
+        use winapi::um::minwinbase::WIN32_FIND_DATAW;
+
+        const FILE_ATTRIBUTE_REPARSE_POINT: u32 = 0x0400;
+        const IO_REPARSE_TAG_MOUNT_POINT: u32 = 0xA000_0003;
+
+        let wide: Vec<u16> = path
+            .as_os_str()
+            .encode_wide()
+            .chain(std::iter::once(0))
+            .collect();
+        unsafe {
+            let mut data: WIN32_FIND_DATAW = std::mem::zeroed();
+            let handle = FindFirstFileW(wide.as_ptr(), &mut data);
+            if handle == INVALID_HANDLE_VALUE {
+                return false;
+            }
+            FindClose(handle);
+            // dwReserved0 holds the reparse tag for reparse points
+            data.dwFileAttributes & FILE_ATTRIBUTE_REPARSE_POINT != 0
+                && data.dwReserved0 == IO_REPARSE_TAG_MOUNT_POINT
         }
     }
 
+    #[cfg(not(windows))]
+    pub(crate) fn is_junction(_path: &Path) -> bool {
+        false
+    }
+
+    /// Check if the content of a path is only stored by a cloud provider
+    ///
+    /// Attributes come from the directory entry, so checking does not download anything
+    #[cfg(windows)]
+    fn is_cloud_only(path: &Path) -> bool {
+        use std::os::windows::fs::MetadataExt;
+        use winapi::um::winnt::{
+            FILE_ATTRIBUTE_OFFLINE, FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS,
+            FILE_ATTRIBUTE_RECALL_ON_OPEN,
+        };
+
+        const CLOUD_ONLY: u32 = FILE_ATTRIBUTE_OFFLINE
+            | FILE_ATTRIBUTE_RECALL_ON_OPEN
+            | FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS;
+        fs::symlink_metadata(path).is_ok_and(|m| m.file_attributes() & CLOUD_ONLY != 0)
+    }
+
+    #[cfg(not(windows))]
+    fn is_cloud_only(_path: &Path) -> bool {
+        false
+    }
+
     /// Get metadata information of file or directory
     ///
     /// Extract the file name (without suffix), extension, and parent directory path
@@ -67,57 +451,236 @@ impl GetPathInfo {
     ///
     /// ### Return Value
     /// Returns `MetadataCollection` structure containing metadata
-    fn get_info(file_path: &Path, is_file: bool) -> MetadataCollection {
-        // Closure function to extract strings, processing file names and extensions
-        // If processing extension, add leading dot "."
-        let get_string_closure = |original_result: &Option<&OsStr>, is_ext: bool| {
-            match original_result {
-                Some(i) => {
-                    if is_ext {
-                        // Whether calculating suffix, if so, add leading dot "."
-                        ".".to_owned() + &i.to_string_lossy()
-                    } else {
-                        i.to_string_lossy().to_string()
-                    }
-                }
-                /*
-                If not available, ignore
-                Since verification has been completed earlier, if Err occurs here,
-                it is due to special file naming and does not affect subsequent operations.
-                e.g. "C:\\.cargo\\.config", this file cannot get suffix, this folder also cannot get suffix
-                */
-                Option::None => String::new(),
-            }
+    pub fn get_info(file_path: &Path, is_file: bool) -> MetadataCollection {
+        let (name, ext) = Self::split_name(file_path, is_file);
+        // Skip a leading dot so dotfiles like ".config" are not all extension,
+        // directories have no extension at all
+        let file_name = format!("{}{}", name, ext);
+        let full_ext = match file_name.char_indices().skip(1).find(|&(_, c)| c == '.') {
+            Some((index, _)) if is_file => file_name[index..].to_string(),
+            _ => String::new(),
         };
 
+        let metadata = fs::metadata(file_path).ok();
+        let is_junction = Self::is_junction(file_path);
+
+        MetadataCollection {
+            name,
+            ext,
+            full_ext,
+            parent_dir: {
+                match &file_path.parent() {
+                    Some(i) => i.to_path_buf(),
+                    Option::None => PathBuf::new(),
+                }
+            },
+            created: metadata.as_ref().and_then(|m| m.created().ok()),
+            modified: metadata.as_ref().and_then(|m| m.modified().ok()),
+            accessed: metadata.as_ref().and_then(|m| m.accessed().ok()),
+            is_symlink: !is_junction
+                && fs::symlink_metadata(file_path).is_ok_and(|m| m.file_type().is_symlink()),
+            is_junction,
+            is_cloud_only: Self::is_cloud_only(file_path),
+        }
+    }
+
+    /// Split the last component of a path into the stem and the extension a swap exchanges
+    ///
+    /// * Directories keep their whole name, dots included (`.vscode`, `docs.v2`)
+    /// * A known compound extension stays whole (`a.tar.gz` is `a` and `.tar.gz`)
+    /// * Otherwise the extension starts at the last dot (`a.en.srt` is `a.en` and `.srt`)
+    /// * A leading dot belongs to the stem, so `.gitignore` has no extension and
+    ///   `.env.local` ends in `.local`
+    ///
+    /// Both parts are in composed Unicode form, so new names do not depend on how
+    /// the old ones were typed
+    ///
+    /// ### Parameters
+    /// * `path` - File or directory path, only its last component is read
+    /// * `is_file` - Path points to a file, directories are never split
+    ///
+    /// ### Return Value
+    /// `(stem, extension)`, the extension with its leading dot or empty. Both are
+    /// empty when the path has no file name (e.g. a root or one ending in `..`)
+    pub fn split_name(path: &Path, is_file: bool) -> (String, String) {
+        let Some(file_name) = path.file_name() else {
+            return (String::new(), String::new());
+        };
+        let file_name = to_nfc(&file_name.to_string_lossy());
         if !is_file {
-            // Process directory path
-            MetadataCollection {
-                name: {
-                    // For directories, name includes stem and extension (if any)
-                    get_string_closure(&file_path.file_stem(), false)
-                        + get_string_closure(&file_path.extension(), true).as_ref()
-                },
-                ext: String::new(), // Directories have no extension
-                parent_dir: {
-                    match &file_path.parent() {
-                        Some(i) => i.to_path_buf(),
-                        Option::None => PathBuf::new(),
-                    }
-                },
-            }
+            return (file_name, String::new());
+        }
+        if let Some(ext_len) = Self::compound_ext(&file_name) {
+            let split = file_name.len() - ext_len;
+            return (
+                file_name[..split].to_string(),
+                file_name[split..].to_string(),
+            );
+        }
+
+        // `file_stem` keeps a leading dot in the stem, as described above
+        let name = Path::new(&file_name);
+        let stem = name
+            .file_stem()
+            .map_or_else(String::new, |stem| stem.to_string_lossy().into_owned());
+        let ext = name
+            .extension()
+            .map_or_else(String::new, |ext| format!(".{}", ext.to_string_lossy()));
+        (stem, ext)
+    }
+
+    /// Find a known compound extension (such as `.tar.gz`) at the end of a file name
+    ///
+    /// ### Parameters
+    /// * `file_name` - Complete file name including extension
+    ///
+    /// ### Return Value
+    /// * `Some(usize)` - Byte length of the compound extension
+    /// * `None` - No known compound extension, or nothing would be left as the name
+    fn compound_ext(file_name: &str) -> Option<usize> {
+        let lower = file_name.to_lowercase();
+        COMPOUND_EXTS
+            .iter()
+            .find(|ext| lower.len() > ext.len() && lower.ends_with(*ext))
+            .map(|ext| ext.len())
+    }
+
+    /// Check that a caller supplied temporary stem forms a legal Windows file name
+    ///
+    /// ### Parameters
+    /// * `stem` - Temporary stem to check
+    ///
+    /// ### Return Value
+    /// * `Ok(())` - Stem can be used
+    /// * `Err(RenameError)` - Stem is empty, contains illegal characters or is reserved
+    pub fn validate_stem(stem: &str) -> Result<(), RenameError> {
+        if stem.is_empty()
+            || stem.ends_with(' ')
+            || stem.ends_with('.')
+            || stem.chars().any(is_illegal_char)
+        {
+            return Err(RenameError::InvalidPath(format!(
+                "Illegal temporary name: {:?}",
+                stem
+            )));
+        }
+        Self::validate_name(Path::new(stem))
+    }
+
+    /// Check that a caller supplied target name is a single legal file name
+    ///
+    /// ### Parameters
+    /// * `name` - New file name, without directory
+    ///
+    /// ### Return Value
+    /// * `Ok(())` - Name can be used
+    /// * `Err(RenameError::IllegalName)` - Name is empty, `.`, `..` or has illegal characters
+    pub fn validate_target_name(name: &str) -> Result<(), RenameError> {
+        if name.is_empty() || name == "." || name == ".." || name.chars().any(is_illegal_char) {
+            return Err(RenameError::IllegalName(format!("{:?}", name)));
+        }
+        Ok(())
+    }
+
+    /// Check that a path to be renamed is not a volume root
+    ///
+    /// A drive root (`C:\`), share root (`\\server\share\`) or `/` has no
+    /// parent directory and no name, so there is nothing that could be renamed.
+    /// The path is checked as written, a link pointing at a root is a link
+    /// like any other and is renamed itself
+    ///
+    /// ### Parameters
+    /// * `path` - Resolved path of an item to be renamed
+    ///
+    /// ### Return Value
+    /// * `Ok(())` - Path has a parent directory
+    /// * `Err(RenameError::VolumeRoot)` - Path is the root of a volume
+    pub fn validate_not_root(path: &Path) -> Result<(), RenameError> {
+        let normalized = Self::normalize_lexically(path);
+        if normalized.parent().is_none() || normalized.file_name().is_none() {
+            return Err(RenameError::VolumeRoot(
+                strip_extended(path).display().to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Check that a computed target path can be created on Windows
+    ///
+    /// A name legal where it is now may not be once transplanted, e.g. a stem
+    /// with a colon taken from a Linux share. Rejects illegal characters in the
+    /// name, names longer than one component allows, paths beyond the extended
+    /// length limit and reserved device names
+    ///
+    /// ### Parameters
+    /// * `path` - Target path whose final component is checked
+    ///
+    /// ### Return Value
+    /// * `Ok(())` - Target can be used
+    /// * `Err(RenameError::IllegalName)` - Illegal character or length
+    /// * `Err(RenameError::ReservedName)` - Name is a reserved device name
+    pub fn validate_target(path: &Path) -> Result<(), RenameError> {
+        let name = match path.file_name() {
+            Some(name) => name.to_string_lossy().to_string(),
+            None => return Ok(()),
+        };
+
+        if let Some(c) = name.chars().find(|&c| is_illegal_char(c)) {
+            return Err(RenameError::IllegalName(format!(
+                "{:?} contains {:?}",
+                name, c
+            )));
+        }
+        if name.encode_utf16().count() > MAX_COMPONENT_LEN {
+            return Err(RenameError::IllegalName(format!(
+                "{:?} is longer than {} characters",
+                name, MAX_COMPONENT_LEN
+            )));
+        }
+        let path_len = strip_extended(path)
+            .to_string_lossy()
+            .encode_utf16()
+            .count();
+        if path_len > MAX_EXTENDED_PATH_LEN {
+            return Err(RenameError::IllegalName(format!(
+                "Path is longer than {} characters: {}",
+                MAX_EXTENDED_PATH_LEN,
+                path.display()
+            )));
+        }
+
+        Self::validate_name(path)
+    }
+
+    /// Check that the file name of a target path is not a Windows reserved device name
+    ///
+    /// Names such as `CON`, `NUL` or `COM1` are reserved regardless of case and
+    /// extension (`con.txt` is reserved too), and trailing spaces are ignored by Windows
+    ///
+    /// ### Parameters
+    /// * `path` - Target path whose final component is checked
+    ///
+    /// ### Return Value
+    /// * `Ok(())` - Name can be used
+    /// * `Err(RenameError::ReservedName)` - Name is a reserved device name
+    pub fn validate_name(path: &Path) -> Result<(), RenameError> {
+        let name = match path.file_name() {
+            Some(name) => name.to_string_lossy().to_string(),
+            None => return Ok(()),
+        };
+        let device = name.split('.').next().unwrap_or_default().trim_end();
+        let upper = device.to_uppercase();
+
+        let is_reserved = matches!(upper.as_str(), "CON" | "PRN" | "AUX" | "NUL")
+            || ((upper.starts_with("COM") || upper.starts_with("LPT"))
+                && upper.len() == 4
+                && upper.as_bytes()[3].is_ascii_digit()
+                && upper.as_bytes()[3] != b'0');
+
+        if is_reserved {
+            Err(RenameError::ReservedName(name))
         } else {
-            // Process file path
-            MetadataCollection {
-                name: get_string_closure(&file_path.file_stem(), false),
-                ext: get_string_closure(&file_path.extension(), true),
-                parent_dir: {
-                    match &file_path.parent() {
-                        Some(i) => i.to_path_buf(),
-                        Option::None => PathBuf::new(),
-                    }
-                },
-            }
+            Ok(())
         }
     }
 
@@ -139,3 +702,7 @@ impl GetPathInfo {
         (metadata1, metadata2)
     }
 }
+
+fn is_illegal_char(c: char) -> bool {
+    c.is_control() || ILLEGAL_CHARS.contains(&c)
+}
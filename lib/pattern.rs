@@ -0,0 +1,206 @@
+// Wildcard pairing module
+//
+// Pairs the items of two wildcard patterns by the text their wildcards match.
+// The matcher is written here instead of taking a glob crate: a glob only
+// answers whether a name matches, while pairing needs the text each wildcard
+// matched, and the library depends on nothing but the Windows API.
+
+use std::{
+    collections::{btree_map::Entry, BTreeMap},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::exchange::{is_same_path, resolve_path};
+use crate::last_error;
+use crate::log::log_info;
+use crate::long_path::strip_extended;
+use crate::types::{PatternPairs, RenameError};
+use crate::unicode::to_nfc;
+
+/// Items matching a pattern, keyed by the text of its wildcards
+type Matches = BTreeMap<Vec<String>, PathBuf>;
+
+/// Pair the items matched by two wildcard patterns by the text their wildcards match
+///
+/// `*` matches any run of characters and `?` a single one, in the last path
+/// component only. `subs\*.en.srt` and `subs\*.zh.srt` pair `a.en.srt` with
+/// `a.zh.srt`, since both wildcards match `a`. With several wildcards each one
+/// takes the shortest text that still lets the rest match, on both sides alike.
+/// An item matched by both patterns is not paired at all
+///
+/// ### Parameters
+/// * `pattern1` - Pattern of the first items
+/// * `pattern2` - Pattern of the second items
+/// * `base_dir` - Base directory for relative patterns
+///
+/// ### Return Value
+/// * `Ok(PatternPairs)` - Pairs ordered by the matched text, and the items without a partner
+/// * `Err(RenameError::InvalidPath)` - A wildcard outside of the last component
+/// * `Err(RenameError::NotExists)` - The directory of a pattern does not exist
+pub(crate) fn pair_by_pattern(
+    pattern1: &Path,
+    pattern2: &Path,
+    base_dir: &Path,
+) -> Result<PatternPairs, RenameError> {
+    let (mut side1, mut unmatched1) = matches_of(pattern1, base_dir)?;
+    let (mut side2, mut unmatched2) = matches_of(pattern2, base_dir)?;
+
+    // An item matching both patterns would be swapped with itself or take part in
+    // two swaps, so it is left out of the pairs and listed as unmatched on both sides
+    let shared: Vec<PathBuf> = side1
+        .values()
+        .chain(&unmatched1)
+        .filter(|path1| {
+            side2
+                .values()
+                .chain(&unmatched2)
+                .any(|path2| is_same_path(path1, path2))
+        })
+        .cloned()
+        .collect();
+    if !shared.is_empty() {
+        let is_shared = |path: &PathBuf| shared.iter().any(|item| is_same_path(item, path));
+        side1.retain(|_, path| !is_shared(path));
+        side2.retain(|_, path| !is_shared(path));
+        unmatched1.retain(|path| !is_shared(path));
+        unmatched2.retain(|path| !is_shared(path));
+        unmatched1.extend(shared.iter().cloned());
+        unmatched2.extend(shared);
+    }
+
+    let mut pairs = Vec::new();
+    for (key, path1) in side1 {
+        match side2.remove(&key) {
+            Some(path2) => pairs.push((path1, path2)),
+            None => unmatched1.push(path1),
+        }
+    }
+    unmatched2.extend(side2.into_values());
+    unmatched1.sort();
+    unmatched2.sort();
+
+    log_info!(
+        "Paired {} items by pattern, {} and {} left unmatched",
+        pairs.len(),
+        unmatched1.len(),
+        unmatched2.len()
+    );
+    Ok(PatternPairs {
+        pairs,
+        unmatched1,
+        unmatched2,
+    })
+}
+
+/// Items of one directory matching a pattern, keyed by the text of the wildcards
+///
+/// ### Return Value
+/// `(matches, duplicates)`, where a duplicate has the key of an earlier match,
+/// possible when letter case is ignored and cannot be paired reliably
+fn matches_of(pattern: &Path, base_dir: &Path) -> Result<(Matches, Vec<PathBuf>), RenameError> {
+    let Some(name_pattern) = pattern.file_name() else {
+        return Err(RenameError::InvalidPath(format!(
+            "Pattern has no file name: {}",
+            pattern.display()
+        )));
+    };
+    let parent = pattern.parent().unwrap_or(Path::new(""));
+    if has_wildcard(parent) {
+        return Err(RenameError::InvalidPath(format!(
+            "Wildcards are only supported in the file name: {}",
+            pattern.display()
+        )));
+    }
+
+    let parent = if parent.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        parent
+    };
+    let (exists, dir) = resolve_path(parent, base_dir)?;
+    if !exists || !dir.is_dir() {
+        last_error::set_detail(strip_extended(&dir).display().to_string());
+        return Err(RenameError::NotExists);
+    }
+
+    #[cfg(windows)]
+    let ignore_case = !crate::types::GetPathInfo::is_case_sensitive_dir(&dir);
+    #[cfg(not(windows))]
+    let ignore_case = false;
+
+    let pattern = collapse_stars(&to_nfc(&name_pattern.to_string_lossy()));
+    let mut matches = BTreeMap::new();
+    let mut duplicates = Vec::new();
+    for entry in fs::read_dir(&dir)? {
+        let entry = entry?;
+        let name: Vec<char> = to_nfc(&entry.file_name().to_string_lossy())
+            .chars()
+            .collect();
+        let mut captures = Vec::new();
+        if !capture(&pattern, &name, ignore_case, &mut captures) {
+            continue;
+        }
+        if ignore_case {
+            captures = captures.iter().map(|text| text.to_lowercase()).collect();
+        }
+        let path = strip_extended(&dir.join(entry.file_name()));
+        match matches.entry(captures) {
+            Entry::Vacant(entry) => {
+                entry.insert(path);
+            }
+            Entry::Occupied(_) => duplicates.push(path),
+        }
+    }
+    Ok((matches, duplicates))
+}
+
+fn has_wildcard(path: &Path) -> bool {
+    path.to_string_lossy().contains(['*', '?'])
+}
+
+/// Pattern characters with runs of `*` merged, they match the same and only slow down `capture`
+fn collapse_stars(pattern: &str) -> Vec<char> {
+    let mut chars: Vec<char> = Vec::with_capacity(pattern.len());
+    for c in pattern.chars() {
+        if !(c == '*' && chars.last() == Some(&'*')) {
+            chars.push(c);
+        }
+    }
+    chars
+}
+
+/// Match a name against a pattern, collecting the text of every wildcard
+///
+/// ### Return Value
+/// The whole name matches, `captures` then holds one entry per wildcard
+fn capture(pattern: &[char], name: &[char], ignore_case: bool, captures: &mut Vec<String>) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => {
+            for split in 0..=name.len() {
+                captures.push(name[..split].iter().collect());
+                if capture(rest, &name[split..], ignore_case, captures) {
+                    return true;
+                }
+                captures.pop();
+            }
+            false
+        }
+        Some((&p, rest)) => {
+            let Some((&c, tail)) = name.split_first() else {
+                return false;
+            };
+            if p == '?' {
+                captures.push(c.to_string());
+                if capture(rest, tail, ignore_case, captures) {
+                    return true;
+                }
+                captures.pop();
+                return false;
+            }
+            let same = p == c || (ignore_case && p.to_lowercase().eq(c.to_lowercase()));
+            same && capture(rest, tail, ignore_case, captures)
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use std::path::{Path, PathBuf};
+
+use crate::exchange::{aside_path, rename_order};
+use crate::long_path::strip_extended;
+use crate::types::{ExchangePlan, GetPathInfo, NameExchange, PlannedStep, StepKind};
+
+/// List every operation `commit_exchange` would perform for a prepared plan
+///
+/// ### Parameters
+/// * `exchange_info` - Plan produced by `prepare_exchange`
+/// * `base_dir` - Base directory the backup directory is resolved against
+///
+/// ### Return Value
+/// Operations in execution order, with the branch of the rename engine
+pub(crate) fn plan_steps(exchange_info: &NameExchange, base_dir: &Path) -> ExchangePlan {
+    let options = &exchange_info.options;
+    let mut steps = Vec::new();
+    let step = |kind, from: &Path, to: Option<&Path>| PlannedStep {
+        kind,
+        from: strip_extended(from),
+        to: to.map(strip_extended),
+    };
+
+    if let Some(backup_dir) = &options.backup_dir {
+        let backup_dir = base_dir.join(backup_dir);
+        for info in [&exchange_info.f1, &exchange_info.f2] {
+            steps.push(step(
+                StepKind::Backup,
+                &info.exchange.original_path,
+                Some(&backup_dir),
+            ));
+        }
+    }
+    // Moved aside first and only deleted once the swap succeeded
+    let set_aside: Vec<PathBuf> = exchange_info
+        .overwritten
+        .iter()
+        .map(|p| aside_path(p))
+        .collect();
+    for (path, aside) in exchange_info.overwritten.iter().zip(&set_aside) {
+        steps.push(step(StepKind::Rename, path, Some(aside)));
+    }
+
+    let (mode, nested, file1_first) = rename_order(exchange_info);
+    let mut created: Vec<PathBuf> = Vec::new();
+    for (from, to) in exchange_info.rename_steps(nested, file1_first) {
+        let (Some(from_dir), Some(to_dir)) = (from.parent(), to.parent()) else {
+            steps.push(step(StepKind::Rename, &from, Some(&to)));
+            continue;
+        };
+        if options.create_parents && !to_dir.exists() && !created.iter().any(|d| d == to_dir) {
+            steps.push(step(StepKind::CreateDir, to_dir, None));
+            created.push(to_dir.to_path_buf());
+        }
+
+        // A temporary name does not exist yet, its directory tells the volume
+        let crosses = matches!(
+            (GetPathInfo::volume_id(from_dir), GetPathInfo::volume_id(to_dir)),
+            (Some(source), Some(target)) if source != target
+        );
+        let kind = if crosses {
+            StepKind::CopyAcrossVolumes
+        } else if NameExchange::is_case_only(&from, &to) {
+            StepKind::CaseRename
+        } else {
+            StepKind::Rename
+        };
+        steps.push(step(kind, &from, Some(&to)));
+    }
+    for aside in &set_aside {
+        steps.push(step(StepKind::Delete, aside, None));
+    }
+
+    ExchangePlan {
+        mode,
+        nested,
+        file1_first,
+        new_path1: strip_extended(&exchange_info.f1.exchange.new_path),
+        new_path2: strip_extended(&exchange_info.f2.exchange.new_path),
+        steps,
+    }
+}
+
+impl StepKind {
+    /// Name of the kind in the JSON form of a plan
+    pub fn as_str(self) -> &'static str {
+        match self {
+            StepKind::Backup => "backup",
+            StepKind::Delete => "delete",
+            StepKind::CreateDir => "create_dir",
+            StepKind::Rename => "rename",
+            StepKind::CaseRename => "case_rename",
+            StepKind::CopyAcrossVolumes => "copy_across_volumes",
+        }
+    }
+}
+
+impl ExchangePlan {
+    /// Format the plan as one JSON object
+    ///
+    /// `{"mode":0,"nested":false,"file1_first":true,"new_path1":"…","new_path2":"…",
+    /// "steps":[{"action":"rename","from":"…","to":"…"},…]}`, where `to` is
+    /// left out of steps that have no destination
+    ///
+    /// ### Return Value
+    /// JSON text without a trailing newline
+    pub fn to_json(&self) -> String {
+        let steps: Vec<String> = self
+            .steps
+            .iter()
+            .map(|step| {
+                let mut fields = vec![
+                    format!("\"action\":\"{}\"", step.kind.as_str()),
+                    format!("\"from\":{}", json_string(&step.from.to_string_lossy())),
+                ];
+                if let Some(to) = &step.to {
+                    fields.push(format!("\"to\":{}", json_string(&to.to_string_lossy())));
+                }
+                format!("{{{}}}", fields.join(","))
+            })
+            .collect();
+        format!(
+            "{{\"mode\":{},\"nested\":{},\"file1_first\":{},\"new_path1\":{},\"new_path2\":{},\"steps\":[{}]}}",
+            self.mode,
+            self.nested,
+            self.file1_first,
+            json_string(&self.new_path1.to_string_lossy()),
+            json_string(&self.new_path2.to_string_lossy()),
+            steps.join(",")
+        )
+    }
+}
+
+/// Quote a string for JSON, escaping quotes, backslashes and control characters
+pub(crate) fn json_string(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
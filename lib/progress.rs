@@ -0,0 +1,28 @@
+use std::sync::RwLock;
+
+/// C callback receiving the bytes copied so far and the total bytes of a
+/// cross-volume move
+///
+/// Called on the thread performing the swap, after every copied file
+pub type ProgressCallback = extern "C" fn(copied: u64, total: u64);
+
+/// Registered progress callback; nothing is measured while this is `None`
+static PROGRESS_CALLBACK: RwLock<Option<ProgressCallback>> = RwLock::new(None);
+
+/// Register or clear the callback that receives copy progress
+///
+/// ### Parameters
+/// * `callback` - Callback to register, `None` to disable progress reports
+pub fn set_progress_callback(callback: Option<ProgressCallback>) {
+    if let Ok(mut slot) = PROGRESS_CALLBACK.write() {
+        *slot = callback;
+    }
+}
+
+/// Currently registered progress callback
+pub fn progress_callback() -> Option<ProgressCallback> {
+    match PROGRESS_CALLBACK.read() {
+        Ok(slot) => *slot,
+        Err(_) => None,
+    }
+}
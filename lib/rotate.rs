@@ -0,0 +1,245 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::copy_move::{backup_items, check_copy_space};
+use crate::exchange::{check_cloud_only, is_same_path, resolve_path};
+use crate::last_error;
+use crate::log::{log_debug, log_error, log_info};
+use crate::long_path::{strip_extended, to_extended};
+use crate::operation_log;
+use crate::types::{
+    ExchangeOptions, FileInfos, GetPathInfo, NameExchange, NameRotation, PrepareName, RenameError,
+};
+
+/// Cyclically shift names among three or more files or directories
+///
+/// Item `i` takes the name of item `i + 1` (A→B→C→A), each keeping its own
+/// extension and parent directory like a regular swap
+///
+/// ### Parameters
+/// * `paths` - Items in rotation order
+/// * `base_dir` - Base directory for relative paths
+/// * `options` - Optional behavior of the rotation
+///
+/// ### Return Value
+/// * `Ok(Vec<PathBuf>)` - New path of every item, in input order
+/// * `Err(RenameError)` - Error information
+pub fn rotate_paths_in(
+    paths: Vec<PathBuf>,
+    base_dir: &Path,
+    options: &ExchangeOptions,
+) -> Result<Vec<PathBuf>, RenameError> {
+    let rotation = prepare_rotation_in(paths, base_dir, options)?;
+    if options.dry_run {
+        log_info!("Dry run, nothing renamed");
+        return Ok(rotation
+            .items
+            .iter()
+            .map(|item| strip_extended(&item.exchange.new_path))
+            .collect());
+    }
+    if let Some(backup_dir) = &options.backup_dir {
+        let originals: Vec<&Path> = rotation
+            .items
+            .iter()
+            .map(|item| item.exchange.original_path.as_path())
+            .collect();
+        let folder = backup_items(&originals, &base_dir.join(backup_dir))?;
+        log_info!("Backed up to {}", folder.display());
+    }
+    let renames = rotation
+        .items
+        .iter()
+        .map(|item| {
+            (
+                item.exchange.original_path.clone(),
+                item.exchange.new_path.clone(),
+            )
+        })
+        .collect();
+    operation_log::run(renames, || rotation.rename_cycle())?;
+    Ok(rotation
+        .items
+        .iter()
+        .map(|item| strip_extended(&item.exchange.new_path))
+        .collect())
+}
+
+/// Run every validation step and compute target names without touching the disk
+///
+/// ### Parameters
+/// * `paths` - Items in rotation order, at least three
+/// * `base_dir` - Base directory for relative paths
+/// * `options` - Optional behavior of the rotation
+///
+/// ### Return Value
+/// * `Ok(NameRotation)` - Fully populated rotation plan
+/// * `Err(RenameError)` - Validation failure (missing file, nesting, conflict, etc.)
+pub fn prepare_rotation_in(
+    paths: Vec<PathBuf>,
+    base_dir: &Path,
+    options: &ExchangeOptions,
+) -> Result<NameRotation, RenameError> {
+    last_error::begin();
+    if paths.len() < 3 {
+        return Err(RenameError::InvalidPath(
+            "Rotation needs at least three items".to_string(),
+        ));
+    }
+    if let Some(stem) = &options.temp_stem {
+        GetPathInfo::validate_stem(stem)?;
+    }
+
+    let mut resolved = Vec::with_capacity(paths.len());
+    for path in &paths {
+        let (exists, path) = resolve_path(path, base_dir)?;
+        log_debug!("Resolved path: {} (exists: {})", path.display(), exists);
+        GetPathInfo::validate_not_root(&path)?;
+        if !exists {
+            log_error!("Path does not exist: {}", path.display());
+            last_error::set_detail(strip_extended(&path).display().to_string());
+            return Err(RenameError::NotExists);
+        }
+        resolved.push(path);
+    }
+
+    for (i, first) in resolved.iter().enumerate() {
+        for second in &resolved[i + 1..] {
+            if is_same_path(first, second) {
+                last_error::set_detail(format!(
+                    "{}, {}",
+                    strip_extended(first).display(),
+                    strip_extended(second).display()
+                ));
+                return Err(RenameError::SamePath);
+            }
+            // Renaming a directory would move the items inside it
+            if GetPathInfo::path_is_parent(first, second)
+                || GetPathInfo::path_is_parent(second, first)
+            {
+                return Err(RenameError::InvalidPath(format!(
+                    "Nested items cannot be rotated: {}, {}",
+                    first.display(),
+                    second.display()
+                )));
+            }
+        }
+    }
+
+    let mut items: Vec<FileInfos> = resolved
+        .into_iter()
+        .map(|path| {
+            let is_file = path.is_file();
+            FileInfos {
+                is_exist: true,
+                is_file,
+                packed_info: GetPathInfo::get_info(&path, is_file),
+                exchange: PrepareName {
+                    original_path: path,
+                    ..Default::default()
+                },
+            }
+        })
+        .collect();
+    for item in &items {
+        check_cloud_only(options, item)?;
+    }
+
+    let count = items.len();
+    for i in 0..count {
+        let next = &items[(i + 1) % count].packed_info;
+        let (next_name, next_ext) = (next.name.clone(), next.ext.clone());
+        let item = &mut items[i];
+        (item.exchange.pre_path, item.exchange.new_path) = NameExchange::make_name(
+            &item.packed_info.parent_dir,
+            next_name,
+            &item.packed_info.ext,
+            next_ext,
+            options.swap_extensions,
+            options.temp_stem.as_deref(),
+            options.name_case,
+        );
+        if cfg!(windows) {
+            GetPathInfo::validate_target(&item.exchange.new_path)?;
+        }
+    }
+
+    let is_member = |path: &Path| {
+        items
+            .iter()
+            .any(|item| is_same_path(path, &item.exchange.original_path))
+    };
+    let taken = |path: &Path| fs::symlink_metadata(to_extended(path)).is_ok();
+    if items
+        .iter()
+        .any(|item| taken(&item.exchange.new_path) && !is_member(&item.exchange.new_path))
+    {
+        return Err(RenameError::AlreadyExists);
+    }
+
+    let moves: Vec<_> = items
+        .iter()
+        .map(|item| {
+            (
+                item.exchange.original_path.as_path(),
+                item.exchange.new_path.as_path(),
+            )
+        })
+        .collect();
+    check_copy_space(&moves, options.cross_volume_copy)?;
+
+    Ok(NameRotation {
+        items,
+        options: options.clone(),
+    })
+}
+
+/// Rename execution of a rotation
+impl NameRotation {
+    /// Rename every item, breaking the cycle with a single temporary name
+    ///
+    /// The first item moves to its temporary name, then the items are renamed
+    /// from last to second, each into the spot freed by the previous step, and
+    /// finally the first item lands on its target. Completed steps are rolled
+    /// back on failure.
+    ///
+    /// ### Return Value
+    /// Returns `Ok(())` for success, `Err(RenameError)` for corresponding failure reason
+    pub fn rename_cycle(&self) -> Result<(), RenameError> {
+        let options = &self.options;
+        let first = &self.items[0].exchange;
+
+        let mut steps: Vec<(&PathBuf, &PathBuf)> = vec![(&first.original_path, &first.pre_path)];
+        steps.extend(
+            self.items[1..]
+                .iter()
+                .rev()
+                .map(|item| (&item.exchange.original_path, &item.exchange.new_path)),
+        );
+        steps.push((&first.pre_path, &first.new_path));
+
+        for (done, (from, to)) in steps.iter().enumerate() {
+            // Rename replaces an existing file on some platforms, never overwrite
+            let taken = fs::symlink_metadata(to_extended(to)).is_ok();
+            let result = if taken && !is_same_path(from, to) {
+                Err(RenameError::AlreadyExists)
+            } else {
+                NameExchange::handle_rename(options, from, to)
+            };
+
+            if let Err(e) = result {
+                let completed: Vec<(&Path, &Path)> = steps[..done]
+                    .iter()
+                    .map(|(from, to)| (from.as_path(), to.as_path()))
+                    .collect();
+                NameExchange::rollback(options, &completed);
+                NameExchange::report_stranded(&first.pre_path, &first.original_path);
+                return Err(e);
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -1,9 +1,17 @@
-use std::{io, path::PathBuf};
+use std::{
+    io,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::SystemTime,
+};
 
-/// Unique identifier for generating temporary filenames
-/// This GUID is used to create temporary filenames to ensure no conflict with existing files
-pub const GUID: &str = "5E702FA07C2FB332B76B";
-pub const DEBUG_MODE: bool = cfg!(debug_assertions);
+/// Multi-part extensions that are treated as a single extension when swapping
+pub const COMPOUND_EXTS: [&str; 6] = [
+    ".tar.gz", ".tar.bz2", ".tar.xz", ".tar.zst", ".tar.lz", ".tar.z",
+];
 
 /// Store metadata information of file or directory
 ///
@@ -13,9 +21,26 @@ pub struct MetadataCollection {
     /// File name or directory name (without extension)
     pub name: String,
     /// File extension (including leading dot "."), empty string for directories
+    ///
+    /// Known compound extensions such as `.tar.gz` are kept whole
     pub ext: String,
+    /// Everything after the first dot of a file name (e.g. `.en.srt`), empty for directories
+    pub full_ext: String,
     /// Parent directory path
     pub parent_dir: PathBuf,
+    /// Creation time before the swap, if available
+    pub created: Option<SystemTime>,
+    /// Last modification time before the swap, if available
+    pub modified: Option<SystemTime>,
+    /// Last access time before the swap, if available
+    pub accessed: Option<SystemTime>,
+    /// The path itself is a symbolic link
+    pub is_symlink: bool,
+    /// The path itself is an NTFS junction
+    pub is_junction: bool,
+    /// Content is only stored by a cloud provider (e.g. OneDrive Files On-Demand)
+    /// and reading it starts a download, renaming in place does not
+    pub is_cloud_only: bool,
 }
 
 /// Store path information required for file renaming
@@ -55,6 +80,263 @@ pub struct GetPathInfo {
     pub path2: PathBuf,
 }
 
+/// `exchange_ex` flag: restore the original timestamps after the swap
+pub const FLAG_PRESERVE_TIMESTAMPS: u32 = 1 << 0;
+
+/// `exchange_ex` flag: refuse to swap a link with the item it points at
+pub const FLAG_REFUSE_LINKED_PAIR: u32 = 1 << 1;
+
+/// `exchange_ex` flag: re-read the directories after the swap and report mismatches
+pub const FLAG_VERIFY: u32 = 1 << 2;
+
+/// `exchange_ex` flag: create the missing parent directories of a rename target
+pub const FLAG_CREATE_PARENTS: u32 = 1 << 3;
+
+/// `exchange_ex` flag: delete an existing file at a target name instead of failing
+pub const FLAG_FORCE: u32 = 1 << 4;
+
+/// `exchange_ex` flag: with `FLAG_FORCE`, also delete an existing directory at a target name
+pub const FLAG_FORCE_DIRS: u32 = 1 << 5;
+
+/// `exchange_ex` flag: delete overwritten targets permanently instead of
+/// moving them to the Recycle Bin
+pub const FLAG_DELETE_PERMANENTLY: u32 = 1 << 6;
+
+/// `exchange_ex` flag: refuse to touch items whose content is only in the cloud
+pub const FLAG_REFUSE_CLOUD_ONLY: u32 = 1 << 7;
+
+/// `exchange_ex` flag: swap whole file names, extensions included, instead of only the stems
+pub const FLAG_SWAP_EXTENSIONS: u32 = 1 << 8;
+
+/// `exchange_ex` flag: swap the items symbolic links and junctions point at instead of the links
+pub const FLAG_FOLLOW_SYMLINKS: u32 = 1 << 9;
+
+/// `exchange_ex` flag: run every check and report the new names without renaming anything
+pub const FLAG_DRY_RUN: u32 = 1 << 10;
+
+/// `exchange_ex` flag: refuse moving an item across volumes by copying it and deleting the original
+pub const FLAG_REFUSE_CROSS_VOLUME: u32 = 1 << 11;
+
+/// `exchange_ex` flag: lowercase the new names
+pub const FLAG_LOWERCASE: u32 = 1 << 12;
+
+/// `exchange_ex` flag: uppercase the new names
+pub const FLAG_UPPERCASE: u32 = 1 << 13;
+
+/// `exchange_ex` flag: titlecase the new names
+pub const FLAG_TITLECASE: u32 = 1 << 14;
+
+/// Flags changing the letter case, at most one of them can be given
+const CASE_FLAGS: u32 = FLAG_LOWERCASE | FLAG_UPPERCASE | FLAG_TITLECASE;
+
+/// `exchange_ex` flag: refuse to swap a folder with an item inside it
+pub const FLAG_REFUSE_NESTED: u32 = 1 << 15;
+
+/// Every flag `exchange_ex` understands, other bits are rejected
+pub(crate) const KNOWN_FLAGS: u32 = (1 << 16) - 1;
+
+/// Default total number of attempts for a rename blocked by another process
+pub const DEFAULT_RENAME_ATTEMPTS: u32 = 3;
+
+/// Most attempts made for one rename, larger `rename_attempts` are capped
+pub const MAX_RENAME_ATTEMPTS: u32 = 10;
+
+/// Request to stop a running exchange, shared between the caller and the exchange
+///
+/// Checked between files while a move across volumes copies a tree
+#[derive(Debug, Default)]
+pub struct CancelToken {
+    cancelled: AtomicBool,
+}
+
+impl CancelToken {
+    /// Ask the exchange using this token to stop
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Check whether `cancel` was called
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// Tokens are equal only to themselves, two fresh tokens still stop different exchanges
+impl PartialEq for CancelToken {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl Eq for CancelToken {}
+
+/// Letter case applied to the new names of an exchange
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NameCase {
+    /// Keep the names as they are
+    #[default]
+    Keep,
+    /// `Quarterly Report.PDF` becomes `quarterly report.pdf`
+    Lower,
+    /// `Quarterly Report.pdf` becomes `QUARTERLY REPORT.PDF`
+    Upper,
+    /// `quarterly_REPORT.PDF` becomes `Quarterly_Report.pdf`, the extension is lowercased
+    Title,
+}
+
+/// Optional behavior of an exchange
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExchangeOptions {
+    /// Reapply the original created/modified/accessed times after renaming
+    pub preserve_timestamps: bool,
+    /// Total attempts for a rename failing with a permission or sharing error,
+    /// `1` disables retrying, at most `MAX_RENAME_ATTEMPTS` are made
+    pub rename_attempts: u32,
+    /// Stem of the temporary name used during the swap, `None` to generate a random one
+    pub temp_stem: Option<String>,
+    /// Fail with `SamePath` when one path is a symbolic link or junction pointing
+    /// at the other, instead of renaming the link itself
+    pub refuse_linked_pair: bool,
+    /// Copy the original items into a new timestamped folder below this directory
+    /// before renaming, relative paths are resolved like the item paths
+    pub backup_dir: Option<PathBuf>,
+    /// After renaming, list the directories again and confirm the new names
+    /// are present and the old ones are gone
+    pub verify: bool,
+    /// Create the parent directories of a rename target when they are missing,
+    /// off by default so a mistyped target does not spawn new folders
+    pub create_parents: bool,
+    /// Delete an unrelated file occupying a target name instead of failing
+    /// with `AlreadyExists`
+    pub force: bool,
+    /// With `force`, also delete an unrelated directory occupying a target name,
+    /// a separate opt-in since it removes a whole tree
+    pub force_dirs: bool,
+    /// Delete items removed by `force` permanently instead of moving them to
+    /// the Recycle Bin, which does not exist outside Windows
+    pub delete_permanently: bool,
+    /// Fail with `CloudFile` when an item is a cloud-only placeholder, so a
+    /// copy across volumes cannot start a large download
+    pub refuse_cloud_only: bool,
+    /// Move the extension along with the stem (`a.txt`, `b.jpg` become `b.jpg`,
+    /// `a.txt`), off by default so each file keeps its own extension
+    pub swap_extensions: bool,
+    /// Stop with `Cancelled` when this token is cancelled during a copy across
+    /// volumes, after moving everything back
+    pub cancel: Option<Arc<CancelToken>>,
+    /// Rename what a symbolic link or junction points at instead of the link itself
+    pub follow_symlinks: bool,
+    /// Run every check and report the new names, but rename nothing
+    pub dry_run: bool,
+    /// Move an item whose target is on another volume by copying it and deleting
+    /// the original, on by default as `std::fs::rename` cannot move it otherwise
+    pub cross_volume_copy: bool,
+    /// New names of the first and second item, used instead of swapping their names.
+    /// The same temporary names and rename order keep the operation safe
+    pub target_names: Option<(String, String)>,
+    /// Letter case of the swapped names, names from `target_names` are used as given
+    pub name_case: NameCase,
+    /// Fail with `NestedItems` when one item is inside the other, since renaming
+    /// the outer folder restructures the tree around the inner item
+    pub refuse_nested: bool,
+    /// Directory relative paths are resolved against, `None` for the working
+    /// directory, or the executable's directory when that cannot be read
+    pub base_dir: Option<PathBuf>,
+    /// Count the entries inside two swapped directories for `ExchangeOutcome::entries`,
+    /// off by default since it walks both trees
+    pub count_entries: bool,
+}
+
+impl Default for ExchangeOptions {
+    fn default() -> Self {
+        Self {
+            preserve_timestamps: false,
+            rename_attempts: DEFAULT_RENAME_ATTEMPTS,
+            temp_stem: None,
+            refuse_linked_pair: false,
+            backup_dir: None,
+            verify: false,
+            create_parents: false,
+            force: false,
+            force_dirs: false,
+            delete_permanently: false,
+            refuse_cloud_only: false,
+            swap_extensions: false,
+            cancel: None,
+            follow_symlinks: false,
+            dry_run: false,
+            cross_volume_copy: true,
+            target_names: None,
+            name_case: NameCase::Keep,
+            refuse_nested: false,
+            base_dir: None,
+            count_entries: false,
+        }
+    }
+}
+
+impl ExchangeOptions {
+    /// Build options from `exchange_ex` bit flags, ignoring unknown bits
+    pub fn from_flags(flags: u32) -> Self {
+        Self {
+            preserve_timestamps: flags & FLAG_PRESERVE_TIMESTAMPS != 0,
+            refuse_linked_pair: flags & FLAG_REFUSE_LINKED_PAIR != 0,
+            verify: flags & FLAG_VERIFY != 0,
+            create_parents: flags & FLAG_CREATE_PARENTS != 0,
+            force: flags & FLAG_FORCE != 0,
+            force_dirs: flags & FLAG_FORCE_DIRS != 0,
+            delete_permanently: flags & FLAG_DELETE_PERMANENTLY != 0,
+            refuse_cloud_only: flags & FLAG_REFUSE_CLOUD_ONLY != 0,
+            swap_extensions: flags & FLAG_SWAP_EXTENSIONS != 0,
+            follow_symlinks: flags & FLAG_FOLLOW_SYMLINKS != 0,
+            dry_run: flags & FLAG_DRY_RUN != 0,
+            cross_volume_copy: flags & FLAG_REFUSE_CROSS_VOLUME == 0,
+            name_case: if flags & FLAG_LOWERCASE != 0 {
+                NameCase::Lower
+            } else if flags & FLAG_UPPERCASE != 0 {
+                NameCase::Upper
+            } else if flags & FLAG_TITLECASE != 0 {
+                NameCase::Title
+            } else {
+                NameCase::Keep
+            },
+            refuse_nested: flags & FLAG_REFUSE_NESTED != 0,
+            ..Default::default()
+        }
+    }
+
+    /// Build options from `exchange_ex` bit flags, rejecting unknown bits
+    ///
+    /// A flag added in a later version must not be silently ignored by an
+    /// older library, the caller would believe it took effect. For the same
+    /// reason more than one letter case flag is refused instead of one winning
+    ///
+    /// ### Return Value
+    /// * `Ok(ExchangeOptions)` - Options of the flags
+    /// * `Err(RenameError::UnknownFlags)` - `flags` has bits this version does not know,
+    ///   or several letter case flags
+    pub fn try_from_flags(flags: u32) -> Result<Self, RenameError> {
+        match flags & !KNOWN_FLAGS {
+            0 if (flags & CASE_FLAGS).count_ones() > 1 => {
+                Err(RenameError::UnknownFlags(flags & CASE_FLAGS))
+            }
+            0 => Ok(Self::from_flags(flags)),
+            unknown => Err(RenameError::UnknownFlags(unknown)),
+        }
+    }
+}
+
+/// Items paired by the text the wildcards of two patterns match
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct PatternPairs {
+    /// Partners of both patterns, ordered by the matched text
+    pub pairs: Vec<(PathBuf, PathBuf)>,
+    /// Items matching the first pattern without a partner
+    pub unmatched1: Vec<PathBuf>,
+    /// Items matching the second pattern without a partner
+    pub unmatched2: Vec<PathBuf>,
+}
+
 /// Main structure for file name exchange
 ///
 /// Contains complete information of two files for executing rename operations
@@ -64,9 +346,102 @@ pub struct NameExchange {
     pub f1: FileInfos,
     /// Complete information of second file
     pub f2: FileInfos,
+    /// Optional behavior requested by the caller
+    pub options: ExchangeOptions,
+    /// Unrelated items at the target names, deleted before renaming with `force`
+    pub overwritten: Vec<PathBuf>,
+    /// Bytes the copy fallback would copy because a target is on another volume,
+    /// `0` when both renames stay on their volumes
+    pub copy_bytes: u64,
+    /// One item is a folder containing the other
+    pub nested: bool,
+}
+
+/// Result of a successful exchange
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct ExchangeOutcome {
+    /// New path of the first file or directory
+    pub new_path1: PathBuf,
+    /// New path of the second file or directory
+    pub new_path2: PathBuf,
+    /// Number of entries inside the swapped directories, `0` unless both are
+    /// directories and `ExchangeOptions::count_entries` was set
+    pub entries: u64,
 }
 
-/// Error type used internally in rename process
+/// Kind of filesystem operation listed in an `ExchangePlan`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepKind {
+    /// Copy the item into a new timestamped folder below the backup directory
+    Backup,
+    /// Delete an unrelated item moved aside from a target name, to the Recycle
+    /// Bin unless `delete_permanently` is set
+    Delete,
+    /// Create the missing parent directory of a target
+    CreateDir,
+    /// Rename within a volume
+    Rename,
+    /// Rename changing only the letter case, done through an extra temporary name
+    CaseRename,
+    /// Copy to a target on another volume, verify the copy and delete the original
+    CopyAcrossVolumes,
+}
+
+/// One filesystem operation an exchange would perform
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedStep {
+    pub kind: StepKind,
+    /// Item operated on, or the directory created by `CreateDir`
+    pub from: PathBuf,
+    /// Destination, `None` for `Delete` and `CreateDir`
+    pub to: Option<PathBuf>,
+}
+
+/// Every step an exchange would take, produced without touching the disk
+///
+/// Temporary names are examples, a real swap picks fresh ones
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct ExchangePlan {
+    /// Containment of the items: `0` none, `1` the first contains the second,
+    /// `2` the second contains the first
+    pub mode: u8,
+    /// Branch of the rename engine: nested items are renamed in place instead
+    /// of through a temporary name
+    pub nested: bool,
+    /// The first item takes its final name before the second
+    pub file1_first: bool,
+    /// New path of the first file or directory
+    pub new_path1: PathBuf,
+    /// New path of the second file or directory
+    pub new_path2: PathBuf,
+    /// Operations in execution order
+    pub steps: Vec<PlannedStep>,
+}
+
+/// Plan for cyclically shifting names among three or more items
+///
+/// Item `i` takes the name of item `i + 1`, and the last item takes the name of the first
+#[derive(Default, Debug, Clone)]
+pub struct NameRotation {
+    /// Complete information of every item, in rotation order
+    pub items: Vec<FileInfos>,
+    /// Optional behavior requested by the caller
+    pub options: ExchangeOptions,
+}
+
+/// A pair of C string paths passed to the batch FFI interface
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct Pair {
+    /// First file or directory path (C string pointer)
+    pub path1: *const std::ffi::c_char,
+    /// Second file or directory path (C string pointer)
+    pub path2: *const std::ffi::c_char,
+}
+
+/// Error of a rename process, also returned by the Rust interface functions
+///
+/// The FFI functions report the same errors as `to_code` numbers
 #[derive(Debug, Clone)]
 pub enum RenameError {
     PermissionDenied,
@@ -74,6 +449,23 @@ pub enum RenameError {
     NotExists,
     SamePath,
     InvalidPath(String),
+    NullPointer,
+    BufferTooSmall,
+    CopyVerifyFailed,
+    ReservedName(String),
+    NothingToDo,
+    BackupFailed(String),
+    VerifyFailed(String),
+    Cancelled,
+    IllegalName(String),
+    CloudFile(String),
+    VolumeRoot(String),
+    EmbeddedNul(String),
+    InsufficientSpace(String),
+    UnknownFlags(u32),
+    CrossesVolumes(String),
+    NestedItems(String),
+    ItemChanged(String),
     Unknown(String),
 }
 
@@ -86,6 +478,23 @@ impl RenameError {
             Self::AlreadyExists => 3,
             Self::SamePath => 4,
             Self::InvalidPath(_) => 5,
+            Self::NullPointer => 6,
+            Self::BufferTooSmall => 7,
+            Self::CopyVerifyFailed => 8,
+            Self::ReservedName(_) => 9,
+            Self::NothingToDo => 10,
+            Self::BackupFailed(_) => 11,
+            Self::VerifyFailed(_) => 12,
+            Self::Cancelled => 13,
+            Self::IllegalName(_) => 14,
+            Self::CloudFile(_) => 15,
+            Self::VolumeRoot(_) => 16,
+            Self::EmbeddedNul(_) => 17,
+            Self::InsufficientSpace(_) => 18,
+            Self::UnknownFlags(_) => 19,
+            Self::CrossesVolumes(_) => 20,
+            Self::NestedItems(_) => 21,
+            Self::ItemChanged(_) => 22,
             Self::Unknown(_) => 255,
         }
     }
@@ -99,13 +508,61 @@ impl std::fmt::Display for RenameError {
             Self::NotExists => write!(f, "File does not exist"),
             Self::SamePath => write!(f, "Two paths refer to the same file"),
             Self::InvalidPath(msg) => write!(f, "Invalid path: {}", msg),
+            Self::NullPointer => write!(f, "Null pointer passed as path"),
+            Self::BufferTooSmall => write!(f, "Output buffer is too small"),
+            Self::CopyVerifyFailed => write!(f, "Copied data could not be verified"),
+            Self::ReservedName(name) => write!(f, "Reserved device name: {}", name),
+            Self::NothingToDo => write!(f, "Both items already have the target names"),
+            Self::BackupFailed(msg) => write!(f, "Backup failed: {}", msg),
+            Self::VerifyFailed(msg) => write!(f, "Swap not reflected on disk: {}", msg),
+            Self::Cancelled => write!(f, "Cancelled, the original names were restored"),
+            Self::IllegalName(msg) => write!(f, "Illegal target name: {}", msg),
+            Self::CloudFile(msg) => write!(f, "Cloud file: {}", msg),
+            Self::VolumeRoot(path) => write!(f, "Volume root cannot be renamed: {}", path),
+            Self::EmbeddedNul(path) => write!(f, "Path contains a NUL character: {}", path),
+            Self::InsufficientSpace(msg) => {
+                write!(f, "Not enough space to copy across volumes: {}", msg)
+            }
+            Self::UnknownFlags(flags) => {
+                write!(f, "Unknown or conflicting option flags: {:#x}", flags)
+            }
+            Self::CrossesVolumes(msg) => {
+                write!(f, "Target is on another volume and copying is off: {}", msg)
+            }
+            Self::NestedItems(msg) => write!(
+                f,
+                "One folder is inside the other, swapping would restructure the tree: {}",
+                msg
+            ),
+            Self::ItemChanged(msg) => {
+                write!(
+                    f,
+                    "Item changed on disk since the swap was planned: {}",
+                    msg
+                )
+            }
             Self::Unknown(msg) => write!(f, "Unknown error: {}", msg),
         }
     }
 }
 
+impl std::error::Error for RenameError {}
+
+/// `ERROR_CLOUD_FILE_*` codes of a cloud provider refusing or failing an operation:
+/// provider not running, read-only volume, authentication failed, network
+/// unavailable, unsuccessful, in use, pinned, access denied, request canceled
+/// and provider terminated
+const CLOUD_FILE_ERRORS: [i32; 10] = [362, 381, 386, 388, 389, 391, 392, 395, 398, 404];
+
 impl From<io::Error> for RenameError {
     fn from(value: io::Error) -> Self {
+        if cfg!(windows)
+            && value
+                .raw_os_error()
+                .is_some_and(|code| CLOUD_FILE_ERRORS.contains(&code))
+        {
+            return RenameError::CloudFile(value.to_string());
+        }
         match value.kind() {
             io::ErrorKind::NotFound => RenameError::NotExists,
             io::ErrorKind::PermissionDenied => RenameError::PermissionDenied,
@@ -0,0 +1,58 @@
+/// Bring a name into Unicode normalization form C (composed characters)
+///
+/// Windows file systems store names as given, so a name typed or pasted in
+/// decomposed form (common for files coming from macOS) does not match the
+/// visually identical composed name on disk. Text that cannot be normalized
+/// is returned unchanged. Does nothing on non-Windows platforms.
+///
+/// ### Parameters
+/// * `text` - Name or path to normalize
+///
+/// ### Return Value
+/// Composed form of `text`
+#[cfg(windows)]
+pub fn to_nfc(text: &str) -> String {
+    use winapi::um::winnls::{NormalizationC, NormalizeString};
+
+    // ASCII is the same in every normalization form
+    if text.is_ascii() {
+        return text.to_string();
+    }
+
+    let source: Vec<u16> = text.encode_utf16().collect();
+    let mut estimate = unsafe {
+        NormalizeString(
+            NormalizationC,
+            source.as_ptr(),
+            source.len() as i32,
+            std::ptr::null_mut(),
+            0,
+        )
+    };
+    // The estimate can be too small, the second call then reports a better one
+    for _ in 0..3 {
+        if estimate <= 0 {
+            break;
+        }
+        let mut target = vec![0u16; estimate as usize];
+        let written = unsafe {
+            NormalizeString(
+                NormalizationC,
+                source.as_ptr(),
+                source.len() as i32,
+                target.as_mut_ptr(),
+                target.len() as i32,
+            )
+        };
+        if written > 0 {
+            return String::from_utf16_lossy(&target[..written as usize]);
+        }
+        estimate = -written;
+    }
+    text.to_string()
+}
+
+#[cfg(not(windows))]
+pub fn to_nfc(text: &str) -> String {
+    text.to_string()
+}
@@ -0,0 +1,407 @@
+// Command-line mode
+//
+// Swaps two paths given on the command line, every tab-separated pair
+// read from standard input, or every pair of items matched by two wildcard
+// patterns, without opening the window, so scripts can use the exit code as
+// the result. With `--json` every result is printed as one JSON object per
+// line instead of a message.
+
+use std::{
+    ffi::OsString,
+    io::{self, BufRead},
+    path::{Path, PathBuf},
+};
+
+use name_exchanger_lib::{
+    exchange_many_rs, exchange_with_rs, is_nested_rs, json_string_rs, pair_by_pattern_rs,
+    ExchangeOptions, ExchangeOutcome, RenameError,
+};
+use winapi::um::wincon::{AttachConsole, ATTACH_PARENT_PROCESS};
+
+use crate::messages::{self, fill, text, Text};
+use crate::{config, msgbox, storage};
+
+/// Argument forcing the window, passed by the desktop and Start menu shortcuts
+/// so that items dropped on them are shown for review instead of swapped at once
+pub const GUI_FLAG: &str = "--gui";
+
+/// Argument reading newline-delimited, tab-separated path pairs from standard input
+pub const STDIN_FLAG: &str = "--stdin";
+
+/// Argument followed by a directory receiving copies of both items before each swap
+pub const BACKUP_FLAG: &str = "--backup";
+
+/// Argument deleting an unrelated file that already uses a target name
+pub const FORCE_FLAG: &str = "--force";
+
+/// Argument also deleting an unrelated directory that already uses a target name,
+/// kept apart from `--force` since it removes a whole tree
+pub const FORCE_DIRS_FLAG: &str = "--force-dirs";
+
+/// Argument refusing to delete anything at a target name, overriding `force = true`
+/// in the settings
+pub const NO_FORCE_FLAG: &str = "--no-force";
+
+/// Argument refusing to delete a directory at a target name while still deleting files
+pub const NO_FORCE_DIRS_FLAG: &str = "--no-force-dirs";
+
+/// Argument allowing a folder to be swapped with an item inside it, which
+/// restructures the tree and is refused without it
+pub const ALLOW_NESTED_FLAG: &str = "--allow-nested";
+
+/// Argument followed by two wildcard patterns, swapping every pair of items
+/// whose wildcards match the same text (`*.en.srt` and `*.zh.srt`)
+pub const MATCH_FLAG: &str = "--match";
+
+/// Argument printing results as JSON objects for scripts
+pub const JSON_FLAG: &str = "--json";
+
+/// Run the command-line mode when the arguments ask for it
+///
+/// ### Parameters
+/// * `args` - Command-line arguments without the program name
+///
+/// ### Return Value
+/// * `Some(i32)` - Arguments were handled, exit with this code
+/// * `None` - Launch the window
+pub fn run(args: &[OsString]) -> Option<i32> {
+    // Arguments override the configured defaults
+    let mut options = config::Settings::load().exchange_options();
+    // Nobody reviews a command-line swap, so nesting needs an explicit opt-in
+    // or, without a console, a confirmation
+    options.refuse_nested = true;
+    // Relative arguments are typed against the shell's directory, whatever the
+    // library falls back to when it cannot be read
+    options.base_dir = std::env::current_dir().ok();
+    // First option seen, named in the usage error when the paths are missing
+    let mut option_flag = None;
+    let mut json = false;
+    let mut rest = Vec::with_capacity(args.len());
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg == BACKUP_FLAG {
+            let Some(dir) = iter.next() else {
+                attach_console();
+                eprintln!("{}", fill(text(Text::CliMissingBackupDir), &[&BACKUP_FLAG]));
+                return Some(5);
+            };
+            options.backup_dir = Some(PathBuf::from(dir));
+            option_flag.get_or_insert(BACKUP_FLAG);
+        } else if arg == FORCE_FLAG {
+            options.force = true;
+            option_flag.get_or_insert(FORCE_FLAG);
+        } else if arg == FORCE_DIRS_FLAG {
+            options.force = true;
+            options.force_dirs = true;
+            option_flag.get_or_insert(FORCE_DIRS_FLAG);
+        } else if arg == NO_FORCE_FLAG {
+            options.force = false;
+            options.force_dirs = false;
+            option_flag.get_or_insert(NO_FORCE_FLAG);
+        } else if arg == NO_FORCE_DIRS_FLAG {
+            options.force_dirs = false;
+            option_flag.get_or_insert(NO_FORCE_DIRS_FLAG);
+        } else if arg == ALLOW_NESTED_FLAG {
+            options.refuse_nested = false;
+            option_flag.get_or_insert(ALLOW_NESTED_FLAG);
+        } else if arg == JSON_FLAG {
+            json = true;
+            option_flag.get_or_insert(JSON_FLAG);
+        } else {
+            rest.push(arg);
+        }
+    }
+
+    if let [flag] = rest.as_slice() {
+        if *flag == STDIN_FLAG {
+            attach_console();
+            return Some(run_stdin(&options, json));
+        }
+    }
+    if rest.first().is_some_and(|flag| **flag == MATCH_FLAG) {
+        attach_console();
+        let [_, pattern1, pattern2] = rest.as_slice() else {
+            eprintln!("{}", fill(text(Text::CliMatchUsage), &[&MATCH_FLAG]));
+            return Some(5);
+        };
+        return Some(run_match(
+            Path::new(pattern1),
+            Path::new(pattern2),
+            &options,
+            json,
+        ));
+    }
+
+    let [path1, path2] = rest.as_slice() else {
+        if let Some(flag) = option_flag {
+            attach_console();
+            eprintln!(
+                "{}",
+                fill(text(Text::CliOptionUsage), &[&flag, &STDIN_FLAG])
+            );
+            return Some(5);
+        }
+        return None;
+    };
+    if *path1 == GUI_FLAG || *path2 == GUI_FLAG {
+        return None;
+    }
+
+    let console = attach_console();
+    if !console {
+        // Explorer "Send To" starts in an unrelated directory, resolve as the window does
+        options.base_dir = storage::exe_dir();
+    }
+    // Started from Explorer "Send To" nobody can add `--allow-nested`, so ask as the window does
+    if !console
+        && options.refuse_nested
+        && is_nested_rs(Path::new(path1), Path::new(path2)).unwrap_or(false)
+    {
+        if !crate::confirm_nested(Path::new(path1), Path::new(path2)) {
+            return Some(RenameError::Cancelled.to_code());
+        }
+        options.refuse_nested = false;
+    }
+    let result = exchange_with_rs(Path::new(path1), Path::new(path2), &options);
+    let code = result_code(&result);
+    if json {
+        println!("{}", json_result(&result, Vec::new()));
+        return Some(code);
+    }
+
+    let message = result_message(&result);
+    match (console, code) {
+        (true, 0) => println!("{}", message),
+        (true, _) => eprintln!("{}", message),
+        // Started from Explorer "Send To", there is nowhere to print
+        (false, 0) => {
+            msgbox::info_msgbox(message, text(Text::TitleSuccess), 0);
+        }
+        (false, _) => {
+            msgbox::error_msgbox(message, text(Text::TitleError), 0);
+        }
+    }
+    Some(code)
+}
+
+/// Swap every pair read from standard input, printing one result per line
+///
+/// ### Parameters
+/// * `options` - Options of every swap
+/// * `json` - Print each result as a JSON object instead of a message, and no summary
+///
+/// ### Return Value
+/// * `0` - Every pair was swapped
+/// * `1` - At least one pair failed or could not be read
+fn run_stdin(options: &ExchangeOptions, json: bool) -> i32 {
+    let (mut succeeded, mut failed) = (0, 0);
+
+    for (index, line) in io::stdin().lock().lines().enumerate() {
+        let line_no = index + 1;
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => {
+                eprintln!("{}: {}", line_no, e);
+                failed += 1;
+                break;
+            }
+        };
+        let line = line.trim_end_matches('\r');
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let result = match line.split_once('\t') {
+            Some((path1, path2)) => {
+                exchange_with_rs(Path::new(path1.trim()), Path::new(path2.trim()), options)
+            }
+            None => Err(RenameError::InvalidPath(
+                text(Text::CliMissingTab).to_string(),
+            )),
+        };
+        let code = result_code(&result);
+        if json {
+            if code == 0 {
+                succeeded += 1;
+            } else {
+                failed += 1;
+            }
+            println!(
+                "{}",
+                json_result(&result, vec![format!("\"line\":{}", line_no)])
+            );
+            continue;
+        }
+
+        // Keep each result on one line so the output stays easy to parse
+        let message = result_message(&result).replace('\n', " ");
+        if code == 0 {
+            succeeded += 1;
+            println!("{}: [{}] {}", line_no, code, message);
+        } else {
+            failed += 1;
+            eprintln!("{}: [{}] {}", line_no, code, message);
+        }
+    }
+
+    if !json {
+        println!(
+            "{}",
+            fill(
+                text(Text::BatchSummary),
+                &[&(succeeded + failed), &succeeded, &failed]
+            )
+        );
+    }
+    if failed == 0 {
+        0
+    } else {
+        1
+    }
+}
+
+/// Swap every pair of items matched by two wildcard patterns, printing one result per pair
+///
+/// ### Parameters
+/// * `pattern1` - Pattern of the first items
+/// * `pattern2` - Pattern of the second items
+/// * `options` - Options of every swap
+/// * `json` - Print each result and unmatched item as a JSON object, and no summary
+///
+/// ### Return Value
+/// * `0` - Every pair was swapped, items without a partner are only reported
+/// * `1` - At least one pair failed, or nothing matched
+/// * Other - Code of the error reading the patterns
+fn run_match(pattern1: &Path, pattern2: &Path, options: &ExchangeOptions, json: bool) -> i32 {
+    let found = match pair_by_pattern_rs(pattern1, pattern2) {
+        Ok(found) => found,
+        Err(e) => {
+            let result = Err(e);
+            if json {
+                println!("{}", json_result(&result, Vec::new()));
+            } else {
+                eprintln!("{}", result_message(&result));
+            }
+            return result_code(&result);
+        }
+    };
+
+    for path in found.unmatched1.iter().chain(&found.unmatched2) {
+        if json {
+            println!(
+                "{{\"status\":\"unmatched\",\"path\":{}}}",
+                json_string_rs(&path.to_string_lossy())
+            );
+        } else {
+            eprintln!("{}", fill(text(Text::CliUnmatched), &[&path.display()]));
+        }
+    }
+    if found.pairs.is_empty() {
+        if !json {
+            eprintln!("{}", text(Text::CliNoMatches));
+        }
+        return 1;
+    }
+
+    let failed = exchange_many_rs(&found.pairs, options, |index, result| {
+        let (path1, path2) = &found.pairs[index];
+        if json {
+            let fields = vec![
+                format!("\"pair\":{}", index + 1),
+                format!("\"path1\":{}", json_string_rs(&path1.to_string_lossy())),
+                format!("\"path2\":{}", json_string_rs(&path2.to_string_lossy())),
+            ];
+            println!("{}", json_result(result, fields));
+            return;
+        }
+        let message = result_message(result).replace('\n', " ");
+        let line = format!(
+            "{} ↔ {}: [{}] {}",
+            path1.display(),
+            path2.display(),
+            result_code(result),
+            message
+        );
+        if result.is_ok() {
+            println!("{}", line);
+        } else {
+            eprintln!("{}", line);
+        }
+    });
+
+    if !json {
+        let total = found.pairs.len();
+        let unmatched = found.unmatched1.len() + found.unmatched2.len();
+        println!(
+            "{}",
+            fill(
+                text(Text::MatchSummary),
+                &[&total, &(total - failed), &failed, &unmatched]
+            )
+        );
+    }
+    if failed == 0 {
+        0
+    } else {
+        1
+    }
+}
+
+fn result_code(result: &Result<ExchangeOutcome, RenameError>) -> i32 {
+    result.as_ref().map_or_else(|e| e.to_code(), |_| 0)
+}
+
+fn result_message(result: &Result<ExchangeOutcome, RenameError>) -> String {
+    match result {
+        Ok(_) => messages::code_message(0).to_string(),
+        Err(e) => messages::error_message(e),
+    }
+}
+
+/// Format a swap result as a single-line JSON object
+///
+/// ### Parameters
+/// * `result` - Result of the swap
+/// * `fields` - Formatted fields placed first, e.g. the input line number in `--stdin` mode
+///
+/// ### Return Value
+/// `{"status":"ok","code":0,"new_path1":...,"new_path2":...}` on success,
+/// `{"status":"error","code":...,"message":...}` on failure
+fn json_result(result: &Result<ExchangeOutcome, RenameError>, mut fields: Vec<String>) -> String {
+    match result {
+        Ok(outcome) => {
+            fields.push("\"status\":\"ok\"".to_string());
+            fields.push("\"code\":0".to_string());
+            fields.push(format!(
+                "\"new_path1\":{}",
+                json_string_rs(&outcome.new_path1.to_string_lossy())
+            ));
+            fields.push(format!(
+                "\"new_path2\":{}",
+                json_string_rs(&outcome.new_path2.to_string_lossy())
+            ));
+        }
+        Err(e) => {
+            fields.push("\"status\":\"error\"".to_string());
+            fields.push(format!("\"code\":{}", e.to_code()));
+            fields.push(format!(
+                "\"message\":{}",
+                json_string_rs(&messages::error_message(e))
+            ));
+        }
+    }
+    format!("{{{}}}", fields.join(","))
+}
+
+/// Reuse the console of the calling shell, release builds have none of their own
+///
+/// ### Return Value
+/// * `true` - Output reaches a console
+/// * `false` - No console to print to
+fn attach_console() -> bool {
+    if cfg!(debug_assertions) {
+        // Debug builds use the console subsystem and already own one
+        return true;
+    }
+    unsafe { AttachConsole(ATTACH_PARENT_PROCESS) != 0 }
+}
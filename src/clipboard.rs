@@ -0,0 +1,155 @@
+// Paths copied to the clipboard while the program runs
+//
+// The main window listens for clipboard changes and keeps the last two items
+// copied in Explorer (a file list) or as text ("Copy as path"), so they can be
+// swapped from the tray without pasting either one. Clipboard text counts only
+// when every line is an absolute path, anything else is ignored. Whether the
+// items exist is checked by the tray action, not on every clipboard change
+
+use std::{
+    collections::VecDeque,
+    ffi::OsString,
+    os::windows::ffi::OsStringExt,
+    path::{Path, PathBuf},
+    ptr,
+};
+
+use winapi::shared::windef::HWND;
+use winapi::um::shellapi::{DragQueryFileW, HDROP};
+use winapi::um::winbase::{GlobalLock, GlobalUnlock};
+use winapi::um::winuser::{
+    AddClipboardFormatListener, CloseClipboard, GetClipboardData, IsClipboardFormatAvailable,
+    OpenClipboard, RemoveClipboardFormatListener, CF_HDROP, CF_UNICODETEXT,
+};
+
+/// Sent to a listening window whenever the clipboard contents change
+pub const WM_CLIPBOARDUPDATE: u32 = 0x031D;
+/// Items kept, the swap takes both
+const KEPT: usize = 2;
+/// Longer text is not read, a copied document holds no path list
+const MAX_TEXT_LEN: usize = 32 * 1024;
+
+/// Last items copied while the program runs, oldest first
+#[derive(Default)]
+pub struct RecentCopies {
+    paths: VecDeque<PathBuf>,
+}
+
+impl RecentCopies {
+    /// Remember the items on the clipboard, called on `WM_CLIPBOARDUPDATE`
+    ///
+    /// ### Parameters
+    /// * `hwnd` - Window opening the clipboard
+    pub fn record(&mut self, hwnd: HWND) {
+        for path in copied_paths(hwnd) {
+            // Copying an item again makes it the latest instead of taking both places
+            self.paths.retain(|kept| *kept != path);
+            self.paths.push_back(path);
+            if self.paths.len() > KEPT {
+                self.paths.pop_front();
+            }
+        }
+    }
+
+    /// The two last copied items, in the order they were copied
+    ///
+    /// ### Return Value
+    /// `None` unless two items were copied and both still exist
+    pub fn last_two(&self) -> Option<(PathBuf, PathBuf)> {
+        match (self.paths.front(), self.paths.back()) {
+            (Some(first), Some(second))
+                if self.paths.len() == KEPT && first.exists() && second.exists() =>
+            {
+                Some((first.clone(), second.clone()))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Start sending `WM_CLIPBOARDUPDATE` to a window
+///
+/// ### Return Value
+/// The window is listening
+pub fn listen(hwnd: HWND) -> bool {
+    unsafe { AddClipboardFormatListener(hwnd) != 0 }
+}
+
+/// Stop sending `WM_CLIPBOARDUPDATE` to a window
+pub fn stop_listening(hwnd: HWND) {
+    unsafe {
+        RemoveClipboardFormatListener(hwnd);
+    }
+}
+
+/// Items currently on the clipboard, empty when it holds anything else
+fn copied_paths(hwnd: HWND) -> Vec<PathBuf> {
+    unsafe {
+        // Another program may still hold the clipboard, the change is then missed
+        if OpenClipboard(hwnd) == 0 {
+            return Vec::new();
+        }
+        let paths = if IsClipboardFormatAvailable(CF_HDROP) != 0 {
+            file_list(GetClipboardData(CF_HDROP) as HDROP)
+        } else if IsClipboardFormatAvailable(CF_UNICODETEXT) != 0 {
+            text_paths(GetClipboardData(CF_UNICODETEXT))
+        } else {
+            Vec::new()
+        };
+        CloseClipboard();
+        paths
+    }
+}
+
+/// Files copied in Explorer, the same list as dropped files
+unsafe fn file_list(hdrop: HDROP) -> Vec<PathBuf> {
+    if hdrop.is_null() {
+        return Vec::new();
+    }
+    let count = DragQueryFileW(hdrop, 0xFFFFFFFF, ptr::null_mut(), 0);
+    (0..count)
+        .filter_map(|i| {
+            // Asked first, a long path does not fit `MAX_PATH`
+            let len = DragQueryFileW(hdrop, i, ptr::null_mut(), 0) as usize;
+            let mut buffer = vec![0u16; len + 1];
+            let copied = DragQueryFileW(hdrop, i, buffer.as_mut_ptr(), buffer.len() as u32);
+            (copied != 0).then(|| PathBuf::from(OsString::from_wide(&buffer[..copied as usize])))
+        })
+        .collect()
+}
+
+/// Paths in clipboard text, one per line and optionally quoted as by "Copy as path"
+unsafe fn text_paths(handle: winapi::um::winnt::HANDLE) -> Vec<PathBuf> {
+    if handle.is_null() {
+        return Vec::new();
+    }
+    let data = GlobalLock(handle) as *const u16;
+    if data.is_null() {
+        return Vec::new();
+    }
+    let mut len = 0;
+    while len <= MAX_TEXT_LEN && *data.add(len) != 0 {
+        len += 1;
+    }
+    let text = String::from_utf16_lossy(std::slice::from_raw_parts(data, len));
+    GlobalUnlock(handle);
+    if len > MAX_TEXT_LEN {
+        return Vec::new();
+    }
+
+    let mut paths = Vec::new();
+    for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        let line = line
+            .strip_prefix('"')
+            .and_then(|line| line.strip_suffix('"'))
+            .unwrap_or(line);
+        let path = Path::new(line);
+        // Relative text is more likely a word than a path. Looking the lines up
+        // on disk would stall the window on every copy, e.g. of a slow share
+        if !path.is_absolute() {
+            return Vec::new();
+        }
+        paths.push(path.to_path_buf());
+    }
+    paths
+}
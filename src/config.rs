@@ -0,0 +1,247 @@
+// Persistent GUI settings
+//
+// Stored as TOML-style `key = value` lines in a file in the data directory
+// chosen by `storage`, with strings in double quotes. A missing or malformed file, or any single
+// malformed line, falls back to the defaults. Besides the window state it
+// holds the default swap options of both the window and the command line,
+// which command-line arguments override.
+
+use std::{fs, io, path::PathBuf};
+
+use name_exchanger_lib::{ExchangeOptions, DEFAULT_RENAME_ATTEMPTS, MAX_RENAME_ATTEMPTS};
+
+use crate::messages::Language;
+use crate::storage;
+
+const CONFIG_FILE: &str = "NameExchanger.cfg";
+
+/// Settings remembered between launches
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Settings {
+    /// Ask for confirmation before every swap
+    pub confirm_swap: bool,
+    /// Hide to the tray when the window is closed instead of exiting
+    pub close_to_tray: bool,
+    /// The first-run offer to create shortcuts was already shown
+    pub shortcuts_offered: bool,
+    /// Content of the first path field
+    pub path1: String,
+    /// Content of the second path field
+    pub path2: String,
+    /// Last window position
+    pub window_pos: Option<(i32, i32)>,
+    /// Last outer window size in pixels, at the DPI of that session
+    pub window_size: Option<(i32, i32)>,
+    /// Interface language, `None` follows the system
+    pub language: Option<Language>,
+    /// Directory receiving copies of both items before every swap
+    pub backup: Option<PathBuf>,
+    /// Delete an unrelated file already using a target name
+    pub force: bool,
+    /// Delete what `force` removes permanently instead of moving it to the Recycle Bin
+    pub delete_permanently: bool,
+    /// Refuse to swap a link with the item it points at instead of renaming the link
+    pub refuse_linked_pair: bool,
+    /// Swap the names of the items links point at instead of the names of the links
+    pub follow_symlinks: bool,
+    /// Total attempts for a rename blocked by another process
+    pub retry_count: u32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            confirm_swap: true,
+            close_to_tray: true,
+            shortcuts_offered: false,
+            path1: String::new(),
+            path2: String::new(),
+            window_pos: None,
+            window_size: None,
+            language: None,
+            backup: None,
+            force: false,
+            delete_permanently: false,
+            refuse_linked_pair: false,
+            follow_symlinks: false,
+            retry_count: DEFAULT_RENAME_ATTEMPTS,
+        }
+    }
+}
+
+impl Settings {
+    /// Load settings, falling back to defaults for anything missing or unreadable
+    pub fn load() -> Settings {
+        Self::path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|text| Self::parse(&text))
+            .unwrap_or_default()
+    }
+
+    /// Write settings to the data directory chosen by `storage`
+    pub fn save(&self) -> io::Result<()> {
+        let path = Self::path().ok_or(io::ErrorKind::NotFound)?;
+        fs::write(path, self.serialize())
+    }
+
+    /// Settings with the swap options and preferences edited in the settings
+    /// dialog reset to their defaults, keeping the session and window state
+    pub fn with_default_options(&self) -> Settings {
+        Settings {
+            shortcuts_offered: self.shortcuts_offered,
+            path1: self.path1.clone(),
+            path2: self.path2.clone(),
+            window_pos: self.window_pos,
+            window_size: self.window_size,
+            ..Settings::default()
+        }
+    }
+
+    /// Swap options configured as defaults
+    pub fn exchange_options(&self) -> ExchangeOptions {
+        ExchangeOptions {
+            backup_dir: self.backup.clone(),
+            force: self.force,
+            delete_permanently: self.delete_permanently,
+            refuse_linked_pair: self.refuse_linked_pair,
+            follow_symlinks: self.follow_symlinks,
+            rename_attempts: self.retry_count,
+            // The window resolves typed relative paths against its own folder,
+            // the command line replaces this with the shell's directory
+            base_dir: storage::exe_dir(),
+            ..Default::default()
+        }
+    }
+
+    fn path() -> Option<PathBuf> {
+        storage::file(CONFIG_FILE)
+    }
+
+    fn parse(text: &str) -> Settings {
+        let mut settings = Settings::default();
+        for line in text.lines() {
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let value = value.trim();
+            match key.trim() {
+                "confirm_swap" => {
+                    settings.confirm_swap = value.parse().unwrap_or(settings.confirm_swap)
+                }
+                "close_to_tray" => {
+                    settings.close_to_tray = value.parse().unwrap_or(settings.close_to_tray)
+                }
+                "shortcuts_offered" => {
+                    settings.shortcuts_offered = value.parse().unwrap_or(settings.shortcuts_offered)
+                }
+                "language" => settings.language = Language::from_key(value),
+                "backup" => {
+                    settings.backup = unquote(value)
+                        .filter(|dir| !dir.is_empty())
+                        .map(PathBuf::from)
+                }
+                "force" => settings.force = value.parse().unwrap_or(settings.force),
+                "delete_permanently" => {
+                    settings.delete_permanently =
+                        value.parse().unwrap_or(settings.delete_permanently)
+                }
+                "refuse_linked_pair" => {
+                    settings.refuse_linked_pair =
+                        value.parse().unwrap_or(settings.refuse_linked_pair)
+                }
+                "follow_symlinks" => {
+                    settings.follow_symlinks = value.parse().unwrap_or(settings.follow_symlinks)
+                }
+                "retry_count" => {
+                    // At least one attempt, the rename itself, and no more than the library makes
+                    settings.retry_count = value
+                        .parse()
+                        .ok()
+                        .filter(|count| (1..=MAX_RENAME_ATTEMPTS).contains(count))
+                        .unwrap_or(settings.retry_count)
+                }
+                "path1" => settings.path1 = unquote(value).unwrap_or_default(),
+                "path2" => settings.path2 = unquote(value).unwrap_or_default(),
+                "window_x" | "window_y" => {
+                    if let Ok(coord) = value.parse() {
+                        let (x, y) = settings.window_pos.get_or_insert((0, 0));
+                        if key.trim() == "window_x" {
+                            *x = coord;
+                        } else {
+                            *y = coord;
+                        }
+                    }
+                }
+                "window_width" | "window_height" => {
+                    if let Ok(length) = value.parse() {
+                        let (width, height) = settings.window_size.get_or_insert((0, 0));
+                        if key.trim() == "window_width" {
+                            *width = length;
+                        } else {
+                            *height = length;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        settings
+    }
+
+    fn serialize(&self) -> String {
+        let mut text = format!(
+            "confirm_swap = {}\nclose_to_tray = {}\nshortcuts_offered = {}\npath1 = {}\npath2 = {}\n",
+            self.confirm_swap,
+            self.close_to_tray,
+            self.shortcuts_offered,
+            quote(&self.path1),
+            quote(&self.path2)
+        );
+        text.push_str(&format!(
+            "language = {}\nbackup = {}\nforce = {}\nretry_count = {}\n",
+            self.language.map_or("auto", Language::key),
+            quote(
+                &self
+                    .backup
+                    .as_ref()
+                    .map_or(String::new(), |dir| { dir.to_string_lossy().to_string() })
+            ),
+            self.force,
+            self.retry_count
+        ));
+        text.push_str(&format!(
+            "delete_permanently = {}\nrefuse_linked_pair = {}\nfollow_symlinks = {}\n",
+            self.delete_permanently, self.refuse_linked_pair, self.follow_symlinks
+        ));
+        if let Some((x, y)) = self.window_pos {
+            text.push_str(&format!("window_x = {}\nwindow_y = {}\n", x, y));
+        }
+        if let Some((width, height)) = self.window_size {
+            text.push_str(&format!(
+                "window_width = {}\nwindow_height = {}\n",
+                width, height
+            ));
+        }
+        text
+    }
+}
+
+/// Write a string as a double-quoted value, escaping `\` and `"`
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Read a double-quoted value written by `quote`
+fn unquote(value: &str) -> Option<String> {
+    let inner = value.strip_prefix('"')?.strip_suffix('"')?;
+    let mut result = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            result.push(chars.next()?);
+        } else {
+            result.push(c);
+        }
+    }
+    Some(result)
+}
@@ -0,0 +1,98 @@
+// Swap history shown in the main window
+//
+// Stored as tab-separated `code<TAB>path1<TAB>path2` lines in a file in the
+// data directory chosen by `storage`. Windows paths cannot contain tabs, so no escaping is needed.
+
+use std::{collections::HashSet, fs, io, path::PathBuf};
+
+use crate::storage;
+
+const HISTORY_FILE: &str = "NameExchanger.history";
+const MAX_ENTRIES: usize = 200;
+/// Paths offered by the recent path lists of the path fields
+const MAX_RECENT: usize = 15;
+
+/// One swap attempt
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryEntry {
+    /// First path as entered by the user
+    pub path1: PathBuf,
+    /// Second path as entered by the user
+    pub path2: PathBuf,
+    /// Result code of the swap, `0` for success
+    pub code: i32,
+}
+
+/// Swap attempts, oldest first
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    pub entries: Vec<HistoryEntry>,
+}
+
+impl History {
+    /// Load the history, starting empty if the file is missing or unreadable
+    pub fn load() -> History {
+        let entries = Self::path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|text| text.lines().filter_map(Self::parse_line).collect())
+            .unwrap_or_default();
+        History { entries }
+    }
+
+    /// Append an entry, dropping the oldest ones beyond the size limit
+    pub fn push(&mut self, entry: HistoryEntry) {
+        self.entries.push(entry);
+        if self.entries.len() > MAX_ENTRIES {
+            let excess = self.entries.len() - MAX_ENTRIES;
+            self.entries.drain(..excess);
+        }
+    }
+
+    /// Distinct paths of the latest swaps, newest first
+    ///
+    /// Paths differing only in letter case name the same item on Windows and
+    /// are listed once
+    pub fn recent_paths(&self) -> Vec<PathBuf> {
+        let mut seen = HashSet::new();
+        self.entries
+            .iter()
+            .rev()
+            .flat_map(|entry| [&entry.path1, &entry.path2])
+            .filter(|path| {
+                !path.as_os_str().is_empty() && seen.insert(path.to_string_lossy().to_lowercase())
+            })
+            .take(MAX_RECENT)
+            .cloned()
+            .collect()
+    }
+
+    /// Write the history to the data directory chosen by `storage`
+    pub fn save(&self) -> io::Result<()> {
+        let path = Self::path().ok_or(io::ErrorKind::NotFound)?;
+        let text: String = self
+            .entries
+            .iter()
+            .map(|entry| {
+                format!(
+                    "{}\t{}\t{}\n",
+                    entry.code,
+                    entry.path1.display(),
+                    entry.path2.display()
+                )
+            })
+            .collect();
+        fs::write(path, text)
+    }
+
+    fn path() -> Option<PathBuf> {
+        storage::file(HISTORY_FILE)
+    }
+
+    fn parse_line(line: &str) -> Option<HistoryEntry> {
+        let mut fields = line.split('\t');
+        let code = fields.next()?.parse().ok()?;
+        let path1 = PathBuf::from(fields.next()?);
+        let path2 = PathBuf::from(fields.next()?);
+        Some(HistoryEntry { path1, path2, code })
+    }
+}
@@ -0,0 +1,112 @@
+// Single-instance support
+//
+// A named mutex marks the running instance. A second launch forwards its
+// command-line paths to the existing window through WM_COPYDATA, brings
+// that window to the front and exits.
+
+use std::{iter::once, path::PathBuf, ptr};
+
+use winapi::shared::minwindef::FALSE;
+use winapi::um::{errhandlingapi::GetLastError, handleapi::CloseHandle, synchapi::CreateMutexW};
+use winapi::um::{winnt::HANDLE, winuser};
+
+const MUTEX_NAME: &str = "Local\\NameExchanger.SingleInstance";
+const ERROR_ALREADY_EXISTS: u32 = 183;
+const SW_RESTORE: i32 = 9;
+
+/// `dwData` tag of a WM_COPYDATA message carrying newline-separated paths
+pub const COPYDATA_PATHS: usize = 0x4E58_5041;
+
+/// Ownership of the single-instance mutex, released when dropped
+pub struct InstanceGuard(HANDLE);
+
+impl Drop for InstanceGuard {
+    fn drop(&mut self) {
+        unsafe {
+            CloseHandle(self.0);
+        }
+    }
+}
+
+/// Try to become the only running instance
+///
+/// ### Return Value
+/// * `Some(InstanceGuard)` - This is the first instance, keep the guard alive until exit
+/// * `None` - Another instance is already running
+pub fn acquire() -> Option<InstanceGuard> {
+    let name = wide(MUTEX_NAME);
+    unsafe {
+        let handle = CreateMutexW(ptr::null_mut(), FALSE, name.as_ptr());
+        if handle.is_null() {
+            // Without a mutex single-instance cannot be enforced, just run
+            return Some(InstanceGuard(handle));
+        }
+        if GetLastError() == ERROR_ALREADY_EXISTS {
+            CloseHandle(handle);
+            return None;
+        }
+        Some(InstanceGuard(handle))
+    }
+}
+
+/// Hand the paths over to the running instance and bring its window to the front
+///
+/// ### Parameters
+/// * `titles` - Possible titles of the main window of the running instance,
+///   which depends on the language it was started in
+/// * `paths` - Command-line paths of this launch, may be empty
+pub fn forward_to_existing(titles: &[&str], paths: &[PathBuf]) {
+    unsafe {
+        let Some(hwnd) = titles
+            .iter()
+            .map(|title| winuser::FindWindowW(ptr::null(), wide(title).as_ptr()))
+            .find(|hwnd| !hwnd.is_null())
+        else {
+            return;
+        };
+
+        if !paths.is_empty() {
+            let text: Vec<String> = paths
+                .iter()
+                .map(|path| path.to_string_lossy().to_string())
+                .collect();
+            let data: Vec<u16> = text.join("\n").encode_utf16().collect();
+            let mut copy = winuser::COPYDATASTRUCT {
+                dwData: COPYDATA_PATHS,
+                cbData: (data.len() * 2) as u32,
+                lpData: data.as_ptr() as *mut _,
+            };
+            winuser::SendMessageW(hwnd, winuser::WM_COPYDATA, 0, &mut copy as *mut _ as isize);
+        }
+
+        winuser::ShowWindow(hwnd, SW_RESTORE);
+        winuser::SetForegroundWindow(hwnd);
+    }
+}
+
+/// Decode the paths of a WM_COPYDATA message sent by `forward_to_existing`
+///
+/// ### Return Value
+/// The forwarded paths, `None` if the message is not ours
+///
+/// # Safety
+/// `copy` must point to the `COPYDATASTRUCT` of a WM_COPYDATA message
+pub unsafe fn read_paths(copy: *const winuser::COPYDATASTRUCT) -> Option<Vec<PathBuf>> {
+    let copy = copy.as_ref()?;
+    if copy.dwData != COPYDATA_PATHS || copy.lpData.is_null() {
+        return None;
+    }
+
+    let data = std::slice::from_raw_parts(copy.lpData as *const u16, copy.cbData as usize / 2);
+    Some(
+        String::from_utf16_lossy(data)
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(PathBuf::from)
+            .collect(),
+    )
+}
+
+fn wide(text: &str) -> Vec<u16> {
+    text.encode_utf16().chain(once(0)).collect()
+}
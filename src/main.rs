@@ -1,16 +1,35 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 use std::cell::RefCell;
+use std::ffi::OsString;
 use std::mem;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::ptr;
 use std::rc::Rc;
-
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+mod cli;
+mod clipboard;
+mod config;
+mod history;
+mod instance;
+mod messages;
+mod settings_dialog;
+mod shell;
+mod storage;
+mod worker;
 mod msgbox {
     include!("../lib/msgbox.rs");
 }
 
-use name_exchanger_lib::exchange_rs;
+use messages::{fill, text, text_in, Language, Text};
+use name_exchanger_lib::{
+    copy_size_rs, exchange_many_rs, exchange_with_rs, is_nested_rs, last_error_detail_rs,
+    last_operation_log_rs, preview_rs, resolve_path_rs, CancelToken, ExchangeOptions,
+    ExchangeOutcome, RenameError,
+};
 use native_windows_gui as nwg;
 use nwg::NativeUi;
 
@@ -24,13 +43,31 @@ const GWL_EXSTYLE: i32 = -20;
 const WS_EX_APPWINDOW: u32 = 0x00040000;
 const WS_EX_TOPMOST: u32 = 0x00000008;
 const WM_DROPFILES: u32 = 0x0233;
+const WM_COPYDATA: u32 = 0x004A;
 const WM_GETMINMAXINFO: u32 = 0x0024;
+const WM_DPICHANGED: u32 = 0x02E0;
+const PBM_SETPOS: u32 = 0x0402;
+const TTM_SETMAXTIPWIDTH: u32 = 0x0418;
+const CB_SETDROPPEDWIDTH: u32 = 0x0160;
 const WM_NCHITTEST: u32 = 0x0084;
+const WM_NCCALCSIZE: u32 = 0x0083;
 const HTCAPTION: isize = 2;
+const HTLEFT: isize = 10;
+const HTRIGHT: isize = 11;
+const HTTOP: isize = 12;
+const HTTOPLEFT: isize = 13;
+const HTTOPRIGHT: isize = 14;
+const HTBOTTOM: isize = 15;
+const HTBOTTOMLEFT: isize = 16;
+const HTBOTTOMRIGHT: isize = 17;
 const MAX_PATH: usize = 260;
+// Pixel sizes below are given at `DEFAULT_DPI` and scaled with `scaled`
+const DEFAULT_DPI: u32 = 96;
 const MIN_WIDTH: i32 = 450;
-const MIN_HEIGHT: i32 = 250;
+const MIN_HEIGHT: i32 = 630;
 const TITLE_BAR_HEIGHT: i32 = 44;
+/// Width of the invisible edge that resizes the borderless window
+const RESIZE_BORDER: i32 = 6;
 const HWND_TOPMOST: isize = -1;
 const HWND_NOTOPMOST: isize = -2;
 const SWP_NOSIZE: u32 = 0x0001;
@@ -41,14 +78,28 @@ const SWP_FRAMECHANGED: u32 = 0x0020;
 const SWP_NOOWNERZORDER: u32 = 0x0200;
 const SW_MINIMIZE: i32 = 6;
 const SW_RESTORE: i32 = 9;
+const PREVIEW_DELAY: Duration = Duration::from_millis(300);
 const THEME_COLOR: [u8; 3] = [0x00, 0x78, 0xD7];
+const FONT_SIZE: i32 = 20;
+const SMALL_FONT_SIZE: i32 = 14;
+/// Widths of the status and path columns of the queue and history lists
+const COLUMN_WIDTHS: [i32; 3] = [70, 170, 170];
+const TOOLTIP_WIDTH: i32 = 600;
+/// Width of the open recent path lists, the closed boxes only show their arrow
+const RECENT_LIST_WIDTH: i32 = 400;
+/// Copies across volumes from this size on are confirmed before swapping
+const LARGE_COPY_BYTES: u64 = 100 * 1024 * 1024;
 
 static mut OLD_WND_PROC: Option<isize> = None;
+static mut PROGRESS_HWND: Option<isize> = None;
+/// DPI of the monitor showing the window, updated on `WM_DPICHANGED`
+static DPI: AtomicU32 = AtomicU32::new(DEFAULT_DPI);
 
 pub struct App {
     window: nwg::Window,
-    font: nwg::Font,
-    small_font: nwg::Font,
+    /// Rebuilt at the new size when the DPI changes
+    font: RefCell<nwg::Font>,
+    small_font: RefCell<nwg::Font>,
 
     title_bar: nwg::Frame,
     title_label: nwg::Label,
@@ -60,26 +111,114 @@ pub struct App {
 
     content: nwg::Frame,
     label_path1: nwg::Label,
+    icon_path1: nwg::ImageFrame,
     text_path1: nwg::TextInput,
+    combo_recent1: nwg::ComboBox<String>,
+    btn_file1: nwg::Button,
+    btn_dir1: nwg::Button,
+    row1_layout: nwg::FlexboxLayout,
 
     label_path2: nwg::Label,
+    icon_path2: nwg::ImageFrame,
     text_path2: nwg::TextInput,
+    combo_recent2: nwg::ComboBox<String>,
+    btn_file2: nwg::Button,
+    btn_dir2: nwg::Button,
+    row2_layout: nwg::FlexboxLayout,
+
+    file_dialog: nwg::FileDialog,
+    dir_dialog: nwg::FileDialog,
+
+    preview_label: nwg::Label,
+    preview_timer: nwg::AnimationTimer,
 
+    check_send_to: nwg::CheckBox,
+    btn_settings: nwg::Button,
+    options_layout: nwg::FlexboxLayout,
     btn_exchange: nwg::Button,
+    copy_progress: nwg::ProgressBar,
+    btn_queue_add: nwg::Button,
+    btn_queue_run: nwg::Button,
+    queue_layout: nwg::FlexboxLayout,
+    queue_view: nwg::ListView,
+    queue_progress: nwg::ProgressBar,
+    history_view: nwg::ListView,
+    status_label: nwg::Label,
+    /// Shows the steps of the last swap over the status bar
+    status_tooltip: nwg::Tooltip,
+    btn_copy_result: nwg::Button,
+    btn_open_folder: nwg::Button,
+    status_layout: nwg::FlexboxLayout,
     content_layout: nwg::FlexboxLayout,
     main_layout: nwg::FlexboxLayout,
 
     tray_icon: nwg::Icon,
     tray: nwg::TrayNotification,
+    tray_menu: nwg::Menu,
+    tray_show: nwg::MenuItem,
+    tray_swap_clipboard: nwg::MenuItem,
+    tray_swap_recent: nwg::MenuItem,
+    tray_exit: nwg::MenuItem,
+
+    settings_dialog: settings_dialog::SettingsDialog,
 
     path1: RefCell<Option<PathBuf>>,
     path2: RefCell<Option<PathBuf>>,
+    /// Shell icon and type of each field's item, kept alive while its frame shows the icon
+    file_types: RefCell<[Option<shell::FileType>; 2]>,
+    last_dir: RefCell<Option<PathBuf>>,
+    /// Command-line paths, applied once the saved session is restored
+    launch_paths: RefCell<Vec<PathBuf>>,
+    settings: RefCell<config::Settings>,
+    history: RefCell<history::History>,
+    queue: RefCell<Vec<QueuedPair>>,
+    /// New paths of the last successful swap, offered by the copy button
+    last_result: RefCell<Option<(PathBuf, PathBuf)>>,
+    /// Items copied to the clipboard while running, offered by the tray menu
+    recent_copies: RefCell<clipboard::RecentCopies>,
     is_topmost: RefCell<bool>,
+    worker: worker::Worker<WorkerMessage>,
+    /// Token of the running job, cancelled by the swap button while it shows "Cancel"
+    job_cancel: RefCell<Option<Arc<CancelToken>>>,
+}
+
+/// Results posted by the background worker
+enum WorkerMessage {
+    Swapped {
+        path1: PathBuf,
+        path2: PathBuf,
+        result: Result<ExchangeOutcome, RenameError>,
+        /// Steps taken, as reported by the library on the worker thread
+        log: String,
+        /// Failing rename with its paths, as reported on the worker thread
+        detail: Option<String>,
+    },
+    Queued {
+        row: usize,
+        path1: PathBuf,
+        path2: PathBuf,
+        code: i32,
+    },
+    QueueDone {
+        total: usize,
+        failed: usize,
+    },
+    Undone(Result<(), RenameError>),
+}
+
+/// A pair waiting in the batch queue
+struct QueuedPair {
+    path1: PathBuf,
+    path2: PathBuf,
+    /// Result code of the last run, `None` while it has not run yet
+    code: Option<i32>,
 }
 
 pub struct AppUi {
     inner: Rc<App>,
     default_handler: RefCell<Option<nwg::EventHandler>>,
+    /// The settings dialog is a separate top-level window with its own handler
+    dialog_handler: RefCell<Option<nwg::EventHandler>>,
 }
 
 impl App {
@@ -88,34 +227,620 @@ impl App {
         let p2_str = self.text_path2.text();
 
         if p1_str.is_empty() || p2_str.is_empty() {
-            msgbox::warn_msgbox("请输入连个完整的文件或文件夹路径！", "提示", 0);
+            msgbox::warn_msgbox(text(Text::NeedTwoPaths), text(Text::TitleNotice), 0);
+            return;
+        }
+
+        // Resolved here, the checks below take no base directory
+        let p1 = resolve_typed(Path::new(&p1_str));
+        let p2 = resolve_typed(Path::new(&p2_str));
+
+        // Errors are left to the swap itself to report
+        let nested = is_nested_rs(&p1, &p2).unwrap_or(false);
+        if self.settings.borrow().confirm_swap {
+            if !confirm_swap(&p1, &p2, nested) {
+                return;
+            }
+        } else if nested && !confirm_nested(&p1, &p2) {
+            return;
+        }
+
+        let cancel = Arc::new(CancelToken::default());
+        let options = ExchangeOptions {
+            // Shown in the result message
+            count_entries: true,
+            cancel: Some(cancel.clone()),
+            ..self.settings.borrow().exchange_options()
+        };
+        let started = self.start_job(Some(cancel), move |post| {
+            // Measured here, walking a big directory would freeze the window. The
+            // message box has no owner window, so it touches no control
+            if let Ok(bytes) = copy_size_rs(&p1, &p2, &options) {
+                if bytes >= LARGE_COPY_BYTES {
+                    let megabytes = bytes / (1024 * 1024);
+                    let msg = fill(text(Text::ConfirmLargeCopy), &[&megabytes]);
+                    if msgbox::quest_msgbox_yesno(msg, text(Text::TitleConfirm), 0) != 6 {
+                        return;
+                    }
+                }
+            }
+            let result = exchange_with_rs(&p1, &p2, &options);
+            post(WorkerMessage::Swapped {
+                path1: p1,
+                path2: p2,
+                result,
+                log: last_operation_log_rs(),
+                detail: last_error_detail_rs(),
+            });
+        });
+        if !started {
+            msgbox::warn_msgbox(text(Text::StillWorking), text(Text::TitleNotice), 0);
+        }
+    }
+
+    /// Run a swap job on the worker, keeping the swap buttons disabled meanwhile
+    ///
+    /// ### Parameters
+    /// * `cancel` - Token passed to the job's swaps, the swap button then
+    ///   cancels the job instead of being disabled
+    /// * `job` - Work to run on the worker
+    ///
+    /// ### Return Value
+    /// * `true` - Job was started
+    /// * `false` - Another job is still running
+    fn start_job(
+        &self,
+        cancel: Option<Arc<CancelToken>>,
+        job: impl FnOnce(&dyn Fn(WorkerMessage)) + Send + 'static,
+    ) -> bool {
+        if !self.worker.start(job) {
+            return false;
+        }
+        self.btn_exchange.set_enabled(cancel.is_some());
+        self.btn_exchange.set_text(text(if cancel.is_some() {
+            Text::Cancel
+        } else {
+            Text::Swapping
+        }));
+        self.btn_queue_run.set_enabled(false);
+        *self.job_cancel.borrow_mut() = cancel;
+        true
+    }
+
+    /// Ask the running job to stop, its swaps put everything back before returning
+    fn cancel_job(&self) {
+        if let Some(cancel) = self.job_cancel.borrow().as_ref() {
+            cancel.cancel();
+            self.btn_exchange.set_enabled(false);
+            self.btn_exchange.set_text(text(Text::Cancelling));
+        }
+    }
+
+    /// Enable or disable the swap button, left alone while it cancels a running job
+    fn set_swap_enabled(&self, enabled: bool) {
+        if !self.worker.is_busy() {
+            self.btn_exchange.set_enabled(enabled);
+        }
+    }
+
+    fn on_worker_notice(&self) {
+        let (messages, finished) = self.worker.receive();
+        // Reset first, the results below may open a message box
+        if finished {
+            self.job_cancel.borrow_mut().take();
+            self.copy_progress.set_pos(0);
+            self.btn_exchange.set_text(text(Text::SwapNames));
+            self.btn_queue_run.set_enabled(true);
+            self.update_preview();
+        }
+        for message in messages {
+            match message {
+                WorkerMessage::Swapped {
+                    path1,
+                    path2,
+                    result,
+                    log,
+                    detail,
+                } => {
+                    self.status_tooltip
+                        .set_text(&self.status_label.handle, &log);
+                    self.finish_swap(&path1, &path2, result, detail);
+                }
+                WorkerMessage::Queued {
+                    row,
+                    path1,
+                    path2,
+                    code,
+                } => self.finish_queued(row, &path1, &path2, code),
+                WorkerMessage::QueueDone { total, failed } => self.finish_queue(total, failed),
+                WorkerMessage::Undone(result) => self.finish_undo(result),
+            }
+        }
+    }
+
+    fn finish_swap(
+        &self,
+        p1: &Path,
+        p2: &Path,
+        result: Result<ExchangeOutcome, RenameError>,
+        detail: Option<String>,
+    ) {
+        self.record_history(p1, p2, result.as_ref().map_or_else(|e| e.to_code(), |_| 0));
+        self.show_status(&result);
+
+        match result {
+            Ok(outcome) => {
+                let mut msg = format!(
+                    "{}\n{}",
+                    messages::code_message(0),
+                    describe_swap(p1, &outcome.new_path1, p2, &outcome.new_path2)
+                );
+                if outcome.entries > 0 {
+                    msg.push('\n');
+                    msg.push_str(&fill(text(Text::EntriesMoved), &[&outcome.entries]));
+                }
+                self.report(
+                    &msg,
+                    text(Text::TitleSuccess),
+                    nwg::TrayNotificationFlags::INFO_ICON,
+                );
+                // The fields may have been edited while the swap was running
+                if Path::new(&self.text_path1.text()) == p1
+                    && Path::new(&self.text_path2.text()) == p2
+                {
+                    self.text_path1.set_text("");
+                    self.text_path2.set_text("");
+                    *self.path1.borrow_mut() = None;
+                    *self.path2.borrow_mut() = None;
+                }
+            }
+            Err(e @ RenameError::SamePath) => {
+                self.report(
+                    &messages::error_message(&e),
+                    text(Text::TitleNotice),
+                    nwg::TrayNotificationFlags::WARNING_ICON,
+                );
+            }
+            Err(e) => {
+                let mut msg = fill(text(Text::SwapFailed), &[&messages::error_message(&e)]);
+                if let Some(detail) = detail {
+                    msg.push('\n');
+                    msg.push_str(&detail);
+                }
+                self.report(
+                    &msg,
+                    text(Text::TitleError),
+                    nwg::TrayNotificationFlags::ERROR_ICON,
+                );
+            }
+        }
+    }
+
+    /// Keep the outcome of the last swap in the status bar
+    fn show_status(&self, result: &Result<ExchangeOutcome, RenameError>) {
+        let status = match result {
+            Ok(outcome) => fill(
+                text(Text::StatusSwapped),
+                &[
+                    &display_name(&outcome.new_path1),
+                    &display_name(&outcome.new_path2),
+                ],
+            ),
+            Err(e) => fill(text(Text::SwapFailed), &[&messages::error_message(e)]),
+        };
+        self.status_label.set_text(&status.replace('\n', " "));
+
+        *self.last_result.borrow_mut() = result
+            .as_ref()
+            .ok()
+            .map(|outcome| (outcome.new_path1.clone(), outcome.new_path2.clone()));
+        self.btn_copy_result.set_enabled(result.is_ok());
+        self.btn_open_folder.set_enabled(result.is_ok());
+    }
+
+    /// Restore the names changed by the last swap
+    fn undo_swap(&self) {
+        // The undo record lives on the worker thread that ran the swap
+        let started = self.start_job(None, |post| {
+            post(WorkerMessage::Undone(name_exchanger_lib::undo_last_rs()));
+        });
+        if !started {
+            self.status_label.set_text(text(Text::StillWorking));
+        }
+    }
+
+    fn finish_undo(&self, result: Result<(), RenameError>) {
+        self.status_tooltip.set_text(&self.status_label.handle, "");
+        let status = match result {
+            Ok(()) => text(Text::StatusUndone).to_string(),
+            Err(RenameError::NotExists) => text(Text::NothingToUndo).to_string(),
+            Err(e) => fill(text(Text::UndoFailed), &[&messages::error_message(&e)]),
+        };
+        self.status_label.set_text(&status.replace('\n', " "));
+
+        *self.last_result.borrow_mut() = None;
+        self.btn_copy_result.set_enabled(false);
+        self.btn_open_folder.set_enabled(false);
+    }
+
+    fn copy_result(&self) {
+        if let Some((path1, path2)) = &*self.last_result.borrow() {
+            let paths = format!("{}\r\n{}", path1.display(), path2.display());
+            nwg::Clipboard::set_data_text(&self.window, &paths);
+        }
+    }
+
+    fn open_result_folder(&self) {
+        let Some((path1, path2)) = self.last_result.borrow().clone() else {
+            return;
+        };
+        show_in_explorer(&path1);
+        if path1.parent() != path2.parent()
+            && msgbox::quest_msgbox_yesno(text(Text::OpenBothFolders), text(Text::TitleNotice), 0)
+                == 6
+        {
+            show_in_explorer(&path2);
+        }
+    }
+
+    /// Show a swap result in a message box, or as a tray balloon while the
+    /// window is hidden in the tray
+    fn report(&self, msg: &str, title: &str, icon: nwg::TrayNotificationFlags) {
+        if !self.window.visible() {
+            self.tray.show(msg, Some(title), Some(icon), None);
+        } else if icon == nwg::TrayNotificationFlags::INFO_ICON {
+            msgbox::info_msgbox(msg, title, 0);
+        } else if icon == nwg::TrayNotificationFlags::WARNING_ICON {
+            msgbox::warn_msgbox(msg, title, 0);
+        } else {
+            msgbox::error_msgbox(msg, title, 0);
+        }
+    }
+
+    fn record_history(&self, p1: &Path, p2: &Path, code: i32) {
+        let entry = history::HistoryEntry {
+            path1: p1.to_path_buf(),
+            path2: p2.to_path_buf(),
+            code,
+        };
+        self.insert_history_row(&entry);
+
+        let mut history = self.history.borrow_mut();
+        history.push(entry);
+        // History is a convenience, a failed write must not disturb the swap
+        let _ = history.save();
+        drop(history);
+        self.refresh_recent_paths();
+    }
+
+    /// Fill both recent path lists from the history
+    fn refresh_recent_paths(&self) {
+        let recent: Vec<String> = self
+            .history
+            .borrow()
+            .recent_paths()
+            .iter()
+            .map(|path| path.to_string_lossy().to_string())
+            .collect();
+        for combo in [&self.combo_recent1, &self.combo_recent2] {
+            combo.set_collection(recent.clone());
+            combo.set_selection(None);
+            set_dropped_width(combo);
+        }
+    }
+
+    fn pick_recent(&self, second: bool) {
+        let (combo, text, slot) = if second {
+            (&self.combo_recent2, &self.text_path2, &self.path2)
+        } else {
+            (&self.combo_recent1, &self.text_path1, &self.path1)
+        };
+        let Some(path) = combo.selection_string() else {
+            return;
+        };
+        // Keep the box empty so picking the same path again still notifies
+        combo.set_selection(None);
+        text.set_text(&path);
+        *slot.borrow_mut() = Some(PathBuf::from(path));
+    }
+
+    fn insert_history_row(&self, entry: &history::HistoryEntry) {
+        let result = status_text(entry.code);
+
+        // Newest entries go to the top
+        for (column, text) in [
+            result,
+            entry.path1.to_string_lossy().to_string(),
+            entry.path2.to_string_lossy().to_string(),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            if column == 0 {
+                self.history_view.insert_item(nwg::InsertListViewItem {
+                    index: Some(0),
+                    column_index: 0,
+                    text: Some(text),
+                    image: None,
+                });
+            } else {
+                self.history_view.update_item(
+                    0,
+                    nwg::InsertListViewItem {
+                        index: Some(0),
+                        column_index: column as i32,
+                        text: Some(text),
+                        image: None,
+                    },
+                );
+            }
+        }
+    }
+
+    fn queue_pair(&self) {
+        let p1_str = self.text_path1.text();
+        let p2_str = self.text_path2.text();
+        if p1_str.is_empty() || p2_str.is_empty() {
+            msgbox::warn_msgbox(text(Text::NeedTwoPaths), text(Text::TitleNotice), 0);
+            return;
+        }
+        // The queue runs unattended, so nesting is confirmed while adding the pair
+        let (p1, p2) = (
+            resolve_typed(Path::new(&p1_str)),
+            resolve_typed(Path::new(&p2_str)),
+        );
+        if is_nested_rs(&p1, &p2).unwrap_or(false) && !confirm_nested(&p1, &p2) {
+            return;
+        }
+
+        let row = self.queue_view.len();
+        self.queue_view.insert_item(nwg::InsertListViewItem {
+            index: Some(row as i32),
+            column_index: 0,
+            text: Some(text(Text::StatusWaiting).to_string()),
+            image: None,
+        });
+        for (column, text) in [(1, &p1_str), (2, &p2_str)] {
+            self.queue_view.update_item(
+                row,
+                nwg::InsertListViewItem {
+                    index: Some(row as i32),
+                    column_index: column,
+                    text: Some(text.clone()),
+                    image: None,
+                },
+            );
+        }
+        self.queue.borrow_mut().push(QueuedPair {
+            path1: PathBuf::from(p1_str),
+            path2: PathBuf::from(p2_str),
+            code: None,
+        });
+        self.clear_paths();
+    }
+
+    fn clear_paths(&self) {
+        self.text_path1.set_text("");
+        self.text_path2.set_text("");
+        *self.path1.borrow_mut() = None;
+        *self.path2.borrow_mut() = None;
+        self.update_preview();
+    }
+
+    fn unqueue(&self, row: usize) {
+        // Rows of a running batch must keep their positions
+        if self.worker.is_busy() {
+            return;
+        }
+        let mut queue = self.queue.borrow_mut();
+        if row < queue.len() {
+            queue.remove(row);
+            self.queue_view.remove_item(row);
+        }
+    }
+
+    /// Swap every queued pair that has not succeeded yet
+    fn run_queue(&self) {
+        let (rows, pairs): (Vec<usize>, Vec<(PathBuf, PathBuf)>) = self
+            .queue
+            .borrow()
+            .iter()
+            .enumerate()
+            .filter(|(_, item)| item.code != Some(0))
+            .map(|(row, item)| (row, (item.path1.clone(), item.path2.clone())))
+            .unzip();
+
+        if pairs.is_empty() {
+            msgbox::warn_msgbox(text(Text::QueueEmpty), text(Text::TitleNotice), 0);
+            return;
+        }
+        if self.settings.borrow().confirm_swap
+            && msgbox::quest_msgbox_yesno(
+                fill(text(Text::ConfirmQueue), &[&pairs.len()]),
+                text(Text::TitleConfirm),
+                0,
+            ) != 6
+        {
             return;
         }
 
-        let p1 = PathBuf::from(p1_str);
-        let p2 = PathBuf::from(p2_str);
+        self.queue_progress.set_range(0..pairs.len() as u32);
+        self.queue_progress.set_pos(0);
+        let cancel = Arc::new(CancelToken::default());
+        let options = ExchangeOptions {
+            cancel: Some(cancel.clone()),
+            ..self.settings.borrow().exchange_options()
+        };
+        let started = self.start_job(Some(cancel), move |post| {
+            let failed = exchange_many_rs(&pairs, &options, |index, result| {
+                post(WorkerMessage::Queued {
+                    row: rows[index],
+                    path1: pairs[index].0.clone(),
+                    path2: pairs[index].1.clone(),
+                    code: result.as_ref().map_or_else(|e| e.to_code(), |_| 0),
+                });
+            });
+            post(WorkerMessage::QueueDone {
+                total: pairs.len(),
+                failed,
+            });
+        });
+        if !started {
+            msgbox::warn_msgbox(text(Text::StillWorking), text(Text::TitleNotice), 0);
+        }
+    }
+
+    fn finish_queued(&self, row: usize, p1: &Path, p2: &Path, code: i32) {
+        if let Some(item) = self.queue.borrow_mut().get_mut(row) {
+            item.code = Some(code);
+        }
+        self.queue_view.update_item(
+            row,
+            nwg::InsertListViewItem {
+                index: Some(row as i32),
+                column_index: 0,
+                text: Some(status_text(code)),
+                image: None,
+            },
+        );
+        self.record_history(p1, p2, code);
+        self.queue_progress.set_pos(self.queue_progress.pos() + 1);
+    }
+
+    fn finish_queue(&self, total: usize, failed: usize) {
+        self.status_tooltip.set_text(&self.status_label.handle, "");
+        let msg = fill(
+            text(Text::BatchSummary),
+            &[&total, &(total - failed), &failed],
+        );
+        self.status_label.set_text(&msg);
+        *self.last_result.borrow_mut() = None;
+        self.btn_copy_result.set_enabled(false);
+        self.btn_open_folder.set_enabled(false);
+        if failed == 0 {
+            self.report(
+                &msg,
+                text(Text::TitleDone),
+                nwg::TrayNotificationFlags::INFO_ICON,
+            );
+        } else {
+            self.report(
+                &msg,
+                text(Text::TitleDone),
+                nwg::TrayNotificationFlags::WARNING_ICON,
+            );
+        }
+    }
+
+    fn rerun_history(&self, row: usize) {
+        let history = self.history.borrow();
+        // Rows are shown newest first
+        let Some(entry) = history
+            .entries
+            .len()
+            .checked_sub(row + 1)
+            .and_then(|index| history.entries.get(index))
+        else {
+            return;
+        };
+
+        self.text_path1.set_text(&entry.path1.to_string_lossy());
+        self.text_path2.set_text(&entry.path2.to_string_lossy());
+        *self.path1.borrow_mut() = Some(entry.path1.clone());
+        *self.path2.borrow_mut() = Some(entry.path2.clone());
+    }
+
+    fn update_preview(&self) {
+        let p1_str = self.text_path1.text();
+        let p2_str = self.text_path2.text();
+
+        let exists1 = self.refresh_field_status(false, &p1_str);
+        let exists2 = self.refresh_field_status(true, &p2_str);
+
+        if !exists1 || !exists2 {
+            self.set_swap_enabled(false);
+            self.preview_label.set_text("");
+            return;
+        }
 
-        match exchange_rs(&p1, &p2) {
-            Ok(()) => {
-                msgbox::info_msgbox("名称交换成功！", "成功", 0);
-                self.text_path1.set_text("");
-                self.text_path2.set_text("");
-                *self.path1.borrow_mut() = None;
-                *self.path2.borrow_mut() = None;
+        let p1 = resolve_typed(Path::new(&p1_str));
+        let p2 = resolve_typed(Path::new(&p2_str));
+        match preview_rs(&p1, &p2) {
+            Ok((new1, new2)) => {
+                self.set_swap_enabled(true);
+                self.preview_label.set_enabled(true);
+                self.preview_label
+                    .set_text(&describe_swap(&p1, &new1, &p2, &new2));
             }
             Err(e) => {
-                msgbox::error_msgbox(format!("交换失败: {}", e), "错误", 0);
+                self.set_swap_enabled(false);
+                self.preview_label.set_enabled(false);
+                self.preview_label.set_text(&messages::error_message(&e));
             }
         }
     }
 
+    /// Show whether a path field's item exists, with its shell icon and type description
+    ///
+    /// ### Parameters
+    /// * `second` - Update the second field instead of the first
+    /// * `path` - Text of the field
+    ///
+    /// ### Return Value
+    /// The item exists
+    fn refresh_field_status(&self, second: bool, path: &str) -> bool {
+        let (label, caption, icon) = if second {
+            (&self.label_path2, text(Text::LabelPath2), &self.icon_path2)
+        } else {
+            (&self.label_path1, text(Text::LabelPath1), &self.icon_path1)
+        };
+
+        let base_dir = storage::exe_dir().unwrap_or_default();
+        let existing = match resolve_path_rs(Path::new(path), &base_dir) {
+            Ok((true, normalized)) if !path.is_empty() => Some(normalized),
+            _ => None,
+        };
+        let file_type = existing.as_deref().and_then(shell::file_type);
+
+        if path.is_empty() {
+            label.set_text(caption);
+        } else if existing.is_none() {
+            label.set_text(&format!("{} {}", caption, text(Text::PathMissing)));
+        } else if let Some(file_type) = &file_type {
+            label.set_text(&format!(
+                "{} {} · {}",
+                caption,
+                text(Text::PathExists),
+                file_type.name
+            ));
+        } else {
+            label.set_text(&format!("{} {}", caption, text(Text::PathExists)));
+        }
+
+        let mut shown = nwg::Icon::default();
+        if let Some(file_type) = &file_type {
+            shown.handle = file_type.icon as _;
+        }
+        icon.set_icon(file_type.as_ref().map(|_| &shown));
+        // The frame no longer shows the previous icon, so it can be destroyed
+        self.file_types.borrow_mut()[second as usize] = file_type;
+
+        existing.is_some()
+    }
+
     fn drop_files(&self, paths: Vec<PathBuf>) {
         if paths.is_empty() {
             return;
         }
 
+        if paths.len() > 2 {
+            msgbox::warn_msgbox(text(Text::TooManyDropped), text(Text::TitleNotice), 0);
+            return;
+        }
+
         if paths.len() == 1 {
-            if self.path1.borrow().is_none() && self.text_path1.text().is_empty() {
+            // Fill the first empty field, replace the second one when both are filled
+            if self.text_path1.text().is_empty() {
                 self.text_path1.set_text(&paths[0].to_string_lossy());
                 *self.path1.borrow_mut() = Some(paths[0].clone());
             } else {
@@ -131,13 +856,214 @@ impl App {
         }
     }
 
+    fn browse(&self, second: bool, folder: bool) {
+        let dialog = if folder {
+            &self.dir_dialog
+        } else {
+            &self.file_dialog
+        };
+        if let Some(dir) = self.last_dir.borrow().as_ref() {
+            let _ = dialog.set_default_folder(&dir.to_string_lossy());
+        }
+
+        if !dialog.run(Some(&self.window)) {
+            return;
+        }
+        let path = match dialog.get_selected_item() {
+            Ok(selected) => PathBuf::from(selected),
+            Err(_) => return,
+        };
+
+        *self.last_dir.borrow_mut() = path.parent().map(Path::to_path_buf);
+        let (text, slot) = if second {
+            (&self.text_path2, &self.path2)
+        } else {
+            (&self.text_path1, &self.path1)
+        };
+        text.set_text(&path.to_string_lossy());
+        *slot.borrow_mut() = Some(path);
+    }
+
+    fn restore_session(&self) {
+        let settings = self.settings.borrow();
+        for (text, slot, value) in [
+            (&self.text_path1, &self.path1, &settings.path1),
+            (&self.text_path2, &self.path2, &settings.path2),
+        ] {
+            if !value.is_empty() {
+                text.set_text(value);
+                *slot.borrow_mut() = Some(PathBuf::from(value));
+            }
+        }
+
+        if let Some((x, y)) = settings.window_pos {
+            if position_on_screen(x, y) {
+                self.window.set_position(x, y);
+            }
+        }
+        if let (Some((width, height)), Some(hwnd)) =
+            (settings.window_size, self.window.handle.hwnd())
+        {
+            // Outer size, `Window::set_size` would add the hidden frame on top.
+            // The minimum size of `WM_GETMINMAXINFO` still applies
+            unsafe {
+                winapi::um::winuser::SetWindowPos(
+                    hwnd as _,
+                    ptr::null_mut(),
+                    0,
+                    0,
+                    width,
+                    height,
+                    SWP_NOMOVE | SWP_NOZORDER | SWP_NOACTIVATE,
+                );
+            }
+        }
+    }
+
+    fn offer_shortcuts(&self) {
+        let mut settings = self.settings.borrow_mut();
+        if settings.shortcuts_offered {
+            return;
+        }
+        settings.shortcuts_offered = true;
+        let _ = settings.save();
+        drop(settings);
+
+        let answer =
+            msgbox::quest_msgbox_yesno(text(Text::OfferShortcuts), text(Text::TitleShortcuts), 0);
+        if answer == 6 {
+            if let Err(e) = shell::install_shortcuts() {
+                msgbox::error_msgbox(
+                    fill(text(Text::ShortcutsFailed), &[&e]),
+                    text(Text::TitleError),
+                    0,
+                );
+            }
+        }
+    }
+
+    fn save_session(&self) {
+        let mut settings = self.settings.borrow_mut();
+        settings.path1 = self.text_path1.text();
+        settings.path2 = self.text_path2.text();
+        settings.window_pos = Some(self.window.position());
+        if let Some(hwnd) = self.window.handle.hwnd() {
+            let mut rect: winapi::shared::windef::RECT = unsafe { mem::zeroed() };
+            if unsafe { winapi::um::winuser::GetWindowRect(hwnd as _, &mut rect) } != 0 {
+                settings.window_size = Some((rect.right - rect.left, rect.bottom - rect.top));
+            }
+        }
+        // Nothing useful can be done when closing fails to write the file
+        let _ = settings.save();
+    }
+
+    fn show_settings(&self) {
+        let settings = self.settings.borrow().clone();
+        self.settings_dialog
+            .open(&self.window, &settings, &self.small_font.borrow());
+    }
+
+    fn reset_settings_dialog(&self) {
+        let defaults = self.settings.borrow().with_default_options();
+        self.settings_dialog.fill(&defaults);
+    }
+
+    fn save_settings_dialog(&self) {
+        let current = self.settings.borrow().clone();
+        let edited = match self.settings_dialog.read(&current) {
+            Ok(edited) => edited,
+            Err(key) => {
+                msgbox::warn_msgbox(text(key), text(Text::TitleNotice), 0);
+                return;
+            }
+        };
+        self.settings_dialog.close(&self.window);
+
+        // Swaps read the options from the settings when they start, so the
+        // new values apply from the next one
+        *self.settings.borrow_mut() = edited.clone();
+        if let Err(e) = edited.save() {
+            msgbox::warn_msgbox(
+                fill(text(Text::SaveSettingsFailed), &[&e]),
+                text(Text::TitleNotice),
+                0,
+            );
+        }
+        if edited.language != current.language {
+            // Menus and layouts are built once, so the new language applies from the next launch
+            msgbox::info_msgbox(text(Text::LanguageOnRestart), text(Text::TitleNotice), 0);
+        }
+    }
+
+    fn toggle_send_to(&self) {
+        let install = self.check_send_to.check_state() == nwg::CheckBoxState::Checked;
+        let result = if install {
+            shell::install_send_to()
+        } else {
+            shell::uninstall_send_to()
+        };
+        if let Err(e) = result {
+            msgbox::error_msgbox(
+                fill(text(Text::SendToFailed), &[&e]),
+                text(Text::TitleError),
+                0,
+            );
+        }
+        // Reflect what is actually on disk after a failure
+        self.check_send_to
+            .set_check_state(if shell::send_to_installed() {
+                nwg::CheckBoxState::Checked
+            } else {
+                nwg::CheckBoxState::Unchecked
+            });
+    }
+
+    fn show_tray_menu(&self) {
+        let (x, y) = nwg::GlobalCursor::position();
+        self.tray_menu.popup(x, y);
+    }
+
+    fn swap_clipboard_paths(&self) {
+        let clipboard = nwg::Clipboard::data_text(&self.window).unwrap_or_default();
+        let paths: Vec<&str> = clipboard
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .collect();
+
+        if let [p1, p2] = paths.as_slice() {
+            self.swap_pair(Path::new(p1), Path::new(p2));
+        } else {
+            msgbox::warn_msgbox(text(Text::ClipboardNeedsTwo), text(Text::TitleNotice), 0);
+        }
+    }
+
+    fn swap_recent_copies(&self) {
+        let last_two = self.recent_copies.borrow().last_two();
+        if let Some((p1, p2)) = last_two {
+            self.swap_pair(&p1, &p2);
+        } else {
+            msgbox::warn_msgbox(text(Text::RecentCopiesNeedTwo), text(Text::TitleNotice), 0);
+        }
+    }
+
+    /// Fill both fields and swap them, as the tray menu does
+    fn swap_pair(&self, p1: &Path, p2: &Path) {
+        // The result is reported as a tray balloon while the window stays hidden
+        self.text_path1.set_text(&p1.to_string_lossy());
+        self.text_path2.set_text(&p2.to_string_lossy());
+        *self.path1.borrow_mut() = Some(p1.to_path_buf());
+        *self.path2.borrow_mut() = Some(p2.to_path_buf());
+        self.on_exchange();
+    }
+
     fn refresh_pin_button(&self) {
-        let text = if *self.is_topmost.borrow() {
-            "取消置顶"
+        let caption = if *self.is_topmost.borrow() {
+            text(Text::Unpin)
         } else {
-            "置顶"
+            text(Text::Pin)
         };
-        self.btn_pin.set_text(text);
+        self.btn_pin.set_text(caption);
     }
 
     fn apply_topmost(&self, enabled: bool) {
@@ -168,11 +1094,12 @@ impl App {
     }
 
     fn show_help(&self) {
-        msgbox::info_msgbox(
-            "拖入文件或文件夹即可使用；点击“置顶”切换置顶状态；点击“-”最小化到任务栏，点击“X”隐藏到托盘；左键单击托盘图标可显示/隐藏窗口，右键托盘图标退出。",
-            "使用提示",
-            0,
-        );
+        let mut help = text(Text::HelpBody).to_string();
+        if let Some(dir) = storage::data_dir() {
+            help.push_str("\n\n");
+            help.push_str(&fill(text(Text::SettingsStoredAt), &[&dir.display()]));
+        }
+        msgbox::info_msgbox(help, text(Text::TitleHelp), 0);
     }
 
     fn minimize_window(&self) {
@@ -187,6 +1114,15 @@ impl App {
         self.window.set_visible(false);
     }
 
+    fn close_window(&self) {
+        self.save_session();
+        if self.settings.borrow().close_to_tray {
+            self.hide_window();
+        } else {
+            nwg::stop_thread_dispatch();
+        }
+    }
+
     fn show_window(&self) {
         self.window.set_visible(true);
         if let Some(hwnd) = self.window.handle.hwnd() {
@@ -204,10 +1140,379 @@ impl App {
             self.show_window();
         }
     }
+
+    /// Lay out every control at the current DPI
+    ///
+    /// Called again when the window moves to a monitor with another scale,
+    /// building a layout again replaces the previous one
+    fn build_layouts(&self) -> Result<(), nwg::NwgError> {
+        use nwg::stretch::{
+            geometry::{Rect, Size},
+            style::{AlignItems, Dimension as D, FlexDirection, JustifyContent},
+        };
+
+        // Sizes are given at `DEFAULT_DPI`
+        let pt = |value: f32| D::Points(scaled_f32(value));
+        let margin = Rect {
+            start: pt(5.0),
+            end: pt(5.0),
+            top: pt(5.0),
+            bottom: pt(5.0),
+        };
+        let padding = Rect {
+            start: pt(10.0),
+            end: pt(10.0),
+            top: pt(10.0),
+            bottom: pt(10.0),
+        };
+
+        let title_padding = Rect {
+            start: pt(10.0),
+            end: pt(10.0),
+            top: pt(8.0),
+            bottom: pt(8.0),
+        };
+
+        nwg::FlexboxLayout::builder()
+            .parent(&self.title_bar)
+            .flex_direction(FlexDirection::Row)
+            .justify_content(JustifyContent::FlexEnd)
+            .align_items(AlignItems::Center)
+            .padding(title_padding)
+            .child(&self.title_label)
+            .child_flex_grow(1.0)
+            .child_size(Size {
+                width: D::Auto,
+                height: D::Auto,
+            })
+            .child(&self.btn_pin)
+            .child_margin(margin)
+            .child_size(Size {
+                width: pt(70.0),
+                height: D::Percent(1.0),
+            })
+            .child(&self.btn_help)
+            .child_margin(margin)
+            .child_size(Size {
+                width: pt(70.0),
+                height: D::Percent(1.0),
+            })
+            .child(&self.btn_minimize)
+            .child_margin(margin)
+            .child_size(Size {
+                width: pt(50.0),
+                height: D::Percent(1.0),
+            })
+            .child(&self.btn_close)
+            .child_margin(margin)
+            .child_size(Size {
+                width: pt(50.0),
+                height: D::Percent(1.0),
+            })
+            .build(&self.title_layout)?;
+
+        for (layout, icon, text, combo_recent, btn_file, btn_dir) in [
+            (
+                &self.row1_layout,
+                &self.icon_path1,
+                &self.text_path1,
+                &self.combo_recent1,
+                &self.btn_file1,
+                &self.btn_dir1,
+            ),
+            (
+                &self.row2_layout,
+                &self.icon_path2,
+                &self.text_path2,
+                &self.combo_recent2,
+                &self.btn_file2,
+                &self.btn_dir2,
+            ),
+        ] {
+            nwg::FlexboxLayout::builder()
+                .parent(&self.content)
+                .flex_direction(FlexDirection::Row)
+                .child(icon)
+                .child_size(Size {
+                    width: pt(22.0),
+                    height: D::Percent(1.0),
+                })
+                .child(text)
+                .child_flex_grow(1.0)
+                .child_size(Size {
+                    width: D::Auto,
+                    height: D::Percent(1.0),
+                })
+                .child(combo_recent)
+                .child_size(Size {
+                    width: pt(22.0),
+                    height: D::Percent(1.0),
+                })
+                .child(btn_file)
+                .child_margin(Rect {
+                    start: pt(5.0),
+                    ..Default::default()
+                })
+                .child_size(Size {
+                    width: pt(50.0),
+                    height: D::Percent(1.0),
+                })
+                .child(btn_dir)
+                .child_margin(Rect {
+                    start: pt(5.0),
+                    ..Default::default()
+                })
+                .child_size(Size {
+                    width: pt(60.0),
+                    height: D::Percent(1.0),
+                })
+                .build_partial(layout)?;
+        }
+
+        nwg::FlexboxLayout::builder()
+            .parent(&self.content)
+            .flex_direction(FlexDirection::Row)
+            .child(&self.check_send_to)
+            .child_flex_grow(1.0)
+            .child_size(Size {
+                width: D::Auto,
+                height: D::Percent(1.0),
+            })
+            .child(&self.btn_settings)
+            .child_size(Size {
+                width: pt(90.0),
+                height: D::Percent(1.0),
+            })
+            .build_partial(&self.options_layout)?;
+
+        nwg::FlexboxLayout::builder()
+            .parent(&self.content)
+            .flex_direction(FlexDirection::Row)
+            .child(&self.btn_queue_add)
+            .child_flex_grow(1.0)
+            .child_size(Size {
+                width: D::Auto,
+                height: D::Percent(1.0),
+            })
+            .child(&self.btn_queue_run)
+            .child_flex_grow(1.0)
+            .child_margin(Rect {
+                start: pt(5.0),
+                ..Default::default()
+            })
+            .child_size(Size {
+                width: D::Auto,
+                height: D::Percent(1.0),
+            })
+            .build_partial(&self.queue_layout)?;
+
+        nwg::FlexboxLayout::builder()
+            .parent(&self.content)
+            .flex_direction(FlexDirection::Row)
+            .child(&self.status_label)
+            .child_flex_grow(1.0)
+            .child_size(Size {
+                width: D::Auto,
+                height: D::Percent(1.0),
+            })
+            .child(&self.btn_copy_result)
+            .child_margin(Rect {
+                start: pt(5.0),
+                ..Default::default()
+            })
+            .child_size(Size {
+                width: pt(60.0),
+                height: D::Percent(1.0),
+            })
+            .child(&self.btn_open_folder)
+            .child_margin(Rect {
+                start: pt(5.0),
+                ..Default::default()
+            })
+            .child_size(Size {
+                width: pt(80.0),
+                height: D::Percent(1.0),
+            })
+            .build_partial(&self.status_layout)?;
+
+        nwg::FlexboxLayout::builder()
+            .parent(&self.content)
+            .flex_direction(FlexDirection::Column)
+            .padding(padding)
+            .child(&self.label_path1)
+            .child_margin(margin)
+            .child_size(Size {
+                width: D::Percent(1.0),
+                height: pt(20.0),
+            })
+            .child_layout(&self.row1_layout)
+            .child_margin(margin)
+            .child_size(Size {
+                width: D::Percent(1.0),
+                height: pt(30.0),
+            })
+            .child(&self.label_path2)
+            .child_margin(margin)
+            .child_size(Size {
+                width: D::Percent(1.0),
+                height: pt(20.0),
+            })
+            .child_layout(&self.row2_layout)
+            .child_margin(margin)
+            .child_size(Size {
+                width: D::Percent(1.0),
+                height: pt(30.0),
+            })
+            .child(&self.preview_label)
+            .child_margin(margin)
+            .child_size(Size {
+                width: D::Percent(1.0),
+                height: pt(40.0),
+            })
+            .child_layout(&self.options_layout)
+            .child_margin(margin)
+            .child_size(Size {
+                width: D::Percent(1.0),
+                height: pt(20.0),
+            })
+            .child(&self.btn_exchange)
+            .child_margin(margin)
+            .child_size(Size {
+                width: D::Percent(1.0),
+                height: pt(40.0),
+            })
+            .child(&self.copy_progress)
+            .child_margin(margin)
+            .child_size(Size {
+                width: D::Percent(1.0),
+                height: pt(6.0),
+            })
+            .child_layout(&self.queue_layout)
+            .child_margin(margin)
+            .child_size(Size {
+                width: D::Percent(1.0),
+                height: pt(28.0),
+            })
+            .child(&self.queue_view)
+            .child_margin(margin)
+            .child_flex_grow(1.0)
+            .child_size(Size {
+                width: D::Percent(1.0),
+                height: D::Auto,
+            })
+            .child(&self.queue_progress)
+            .child_margin(margin)
+            .child_size(Size {
+                width: D::Percent(1.0),
+                height: pt(6.0),
+            })
+            .child(&self.history_view)
+            .child_margin(margin)
+            .child_flex_grow(1.0)
+            .child_size(Size {
+                width: D::Percent(1.0),
+                height: D::Auto,
+            })
+            .child_layout(&self.status_layout)
+            .child_margin(margin)
+            .child_size(Size {
+                width: D::Percent(1.0),
+                height: pt(24.0),
+            })
+            .build(&self.content_layout)?;
+
+        nwg::FlexboxLayout::builder()
+            .parent(&self.window)
+            .flex_direction(FlexDirection::Column)
+            .child(&self.title_bar)
+            .child_size(Size {
+                width: D::Percent(1.0),
+                height: pt(TITLE_BAR_HEIGHT as f32),
+            })
+            .child(&self.content)
+            .child_flex_grow(1.0)
+            .child_size(Size {
+                width: D::Percent(1.0),
+                height: D::Auto,
+            })
+            .build(&self.main_layout)?;
+
+        Ok(())
+    }
+
+    /// Rescale fonts, list columns and the layout after a DPI change
+    ///
+    /// ### Parameters
+    /// * `dpi` - New DPI of the monitor showing the window
+    fn apply_dpi(&self, dpi: u32) {
+        DPI.store(dpi, Ordering::Relaxed);
+
+        let old_fonts = [self.font.borrow().handle, self.small_font.borrow().handle];
+        if build_font(&mut self.font.borrow_mut(), FONT_SIZE).is_err()
+            || build_font(&mut self.small_font.borrow_mut(), SMALL_FONT_SIZE).is_err()
+        {
+            return;
+        }
+        let font = self.font.borrow();
+        let small_font = self.small_font.borrow();
+        self.title_label.set_font(Some(&font));
+        for label in [&self.label_path1, &self.label_path2] {
+            label.set_font(Some(&font));
+        }
+        for input in [&self.text_path1, &self.text_path2] {
+            input.set_font(Some(&font));
+        }
+        for combo in [&self.combo_recent1, &self.combo_recent2] {
+            combo.set_font(Some(&small_font));
+            set_dropped_width(combo);
+        }
+        self.btn_exchange.set_font(Some(&font));
+        for button in [
+            &self.btn_pin,
+            &self.btn_help,
+            &self.btn_minimize,
+            &self.btn_close,
+            &self.btn_file1,
+            &self.btn_dir1,
+            &self.btn_file2,
+            &self.btn_dir2,
+            &self.btn_queue_add,
+            &self.btn_queue_run,
+            &self.btn_copy_result,
+            &self.btn_open_folder,
+            &self.btn_settings,
+        ] {
+            button.set_font(Some(&small_font));
+        }
+        for label in [&self.preview_label, &self.status_label] {
+            label.set_font(Some(&small_font));
+        }
+        self.check_send_to.set_font(Some(&small_font));
+        for handle in old_fonts {
+            // Controls no longer use the old fonts, nwg never deletes them itself
+            unsafe {
+                winapi::um::wingdi::DeleteObject(handle as _);
+            }
+        }
+
+        for view in [&self.queue_view, &self.history_view] {
+            for (index, width) in COLUMN_WIDTHS.iter().enumerate() {
+                view.set_column_width(index, scaled(*width) as isize);
+            }
+        }
+        if let Some(hwnd) = self.status_tooltip.handle.hwnd() {
+            set_tooltip_width(hwnd);
+        }
+
+        let _ = self.build_layouts();
+    }
 }
 
 impl Drop for App {
     fn drop(&mut self) {
+        if let Some(hwnd) = self.window.handle.hwnd() {
+            clipboard::stop_listening(hwnd as _);
+        }
         self.tray.handle.destroy();
         self.window.handle.destroy();
     }
@@ -215,50 +1520,21 @@ impl Drop for App {
 
 impl nwg::NativeUi<AppUi> for App {
     fn build_ui(mut data: App) -> Result<AppUi, nwg::NwgError> {
-        use nwg::stretch::{
-            geometry::{Rect, Size},
-            style::{AlignItems, Dimension as D, FlexDirection, JustifyContent},
-        };
         use nwg::Event as E;
 
-        const MARGIN: Rect<D> = Rect {
-            start: D::Points(5.0),
-            end: D::Points(5.0),
-            top: D::Points(5.0),
-            bottom: D::Points(5.0),
-        };
-        const PADDING: Rect<D> = Rect {
-            start: D::Points(10.0),
-            end: D::Points(10.0),
-            top: D::Points(10.0),
-            bottom: D::Points(10.0),
-        };
-
-        const TITLE_PADDING: Rect<D> = Rect {
-            start: D::Points(10.0),
-            end: D::Points(10.0),
-            top: D::Points(8.0),
-            bottom: D::Points(8.0),
-        };
+        DPI.store(unsafe { nwg::dpi() } as u32, Ordering::Relaxed);
 
         nwg::Window::builder()
-            .size((MIN_WIDTH, MIN_HEIGHT))
+            .size((scaled(MIN_WIDTH), scaled(MIN_HEIGHT)))
             .position((0, 0))
-            .title("名称交换器")
-            .flags(nwg::WindowFlags::WINDOW | nwg::WindowFlags::VISIBLE)
+            .title(text(Text::AppTitle))
+            .flags(
+                nwg::WindowFlags::WINDOW | nwg::WindowFlags::VISIBLE | nwg::WindowFlags::RESIZABLE,
+            )
             .build(&mut data.window)?;
 
-        nwg::Font::builder()
-            .family("Microsoft YaHei UI")
-            .size(20)
-            .weight(400)
-            .build(&mut data.font)?;
-
-        nwg::Font::builder()
-            .family("Microsoft YaHei UI")
-            .size(14)
-            .weight(400)
-            .build(&mut data.small_font)?;
+        build_font(data.font.get_mut(), FONT_SIZE)?;
+        build_font(data.small_font.get_mut(), SMALL_FONT_SIZE)?;
 
         nwg::Frame::builder()
             .background_color(Some(THEME_COLOR))
@@ -266,26 +1542,26 @@ impl nwg::NativeUi<AppUi> for App {
             .build(&mut data.title_bar)?;
 
         nwg::Label::builder()
-            .text("名称交换器")
-            .font(Some(&data.font))
+            .text(text(Text::AppTitle))
+            .font(Some(&data.font.borrow()))
             .text_color(Some([255, 255, 255]))
             .parent(&data.title_bar)
             .build(&mut data.title_label)?;
 
         nwg::Button::builder()
-            .text("置顶")
+            .text(text(Text::Pin))
             .background_color(Some(THEME_COLOR))
             .text_color(Some([255, 255, 255]))
             .parent(&data.title_bar)
-            .font(Some(&data.small_font))
+            .font(Some(&data.small_font.borrow()))
             .build(&mut data.btn_pin)?;
 
         nwg::Button::builder()
-            .text("帮助")
+            .text(text(Text::Help))
             .background_color(Some(THEME_COLOR))
             .text_color(Some([255, 255, 255]))
             .parent(&data.title_bar)
-            .font(Some(&data.small_font))
+            .font(Some(&data.small_font.borrow()))
             .build(&mut data.btn_help)?;
 
         nwg::Button::builder()
@@ -293,7 +1569,7 @@ impl nwg::NativeUi<AppUi> for App {
             .background_color(Some(THEME_COLOR))
             .text_color(Some([255, 255, 255]))
             .parent(&data.title_bar)
-            .font(Some(&data.small_font))
+            .font(Some(&data.small_font.borrow()))
             .build(&mut data.btn_minimize)?;
 
         nwg::Button::builder()
@@ -301,7 +1577,7 @@ impl nwg::NativeUi<AppUi> for App {
             .background_color(Some(THEME_COLOR))
             .text_color(Some([255, 255, 255]))
             .parent(&data.title_bar)
-            .font(Some(&data.small_font))
+            .font(Some(&data.small_font.borrow()))
             .build(&mut data.btn_close)?;
 
         nwg::Frame::builder()
@@ -310,37 +1586,216 @@ impl nwg::NativeUi<AppUi> for App {
             .build(&mut data.content)?;
 
         nwg::Label::builder()
-            .text("文件/文件夹 1:")
+            .text(text(Text::LabelPath1))
             .parent(&data.content)
-            .font(Some(&data.font))
+            .font(Some(&data.font.borrow()))
             .build(&mut data.label_path1)?;
 
+        nwg::ImageFrame::builder()
+            .background_color(Some([245, 245, 245]))
+            .parent(&data.content)
+            .build(&mut data.icon_path1)?;
+
         nwg::TextInput::builder()
             .text("")
             .readonly(false)
             .parent(&data.content)
-            .font(Some(&data.font))
+            .font(Some(&data.font.borrow()))
             .build(&mut data.text_path1)?;
 
+        nwg::ComboBox::builder()
+            .parent(&data.content)
+            .font(Some(&data.small_font.borrow()))
+            .build(&mut data.combo_recent1)?;
+
+        nwg::Button::builder()
+            .text(text(Text::File))
+            .parent(&data.content)
+            .font(Some(&data.small_font.borrow()))
+            .build(&mut data.btn_file1)?;
+
+        nwg::Button::builder()
+            .text(text(Text::Folder))
+            .parent(&data.content)
+            .font(Some(&data.small_font.borrow()))
+            .build(&mut data.btn_dir1)?;
+
         nwg::Label::builder()
-            .text("文件/文件夹 2:")
+            .text(text(Text::LabelPath2))
             .parent(&data.content)
-            .font(Some(&data.font))
+            .font(Some(&data.font.borrow()))
             .build(&mut data.label_path2)?;
 
+        nwg::ImageFrame::builder()
+            .background_color(Some([245, 245, 245]))
+            .parent(&data.content)
+            .build(&mut data.icon_path2)?;
+
         nwg::TextInput::builder()
             .text("")
             .readonly(false)
             .parent(&data.content)
-            .font(Some(&data.font))
+            .font(Some(&data.font.borrow()))
             .build(&mut data.text_path2)?;
 
+        nwg::ComboBox::builder()
+            .parent(&data.content)
+            .font(Some(&data.small_font.borrow()))
+            .build(&mut data.combo_recent2)?;
+
+        nwg::Button::builder()
+            .text(text(Text::File))
+            .parent(&data.content)
+            .font(Some(&data.small_font.borrow()))
+            .build(&mut data.btn_file2)?;
+
+        nwg::Button::builder()
+            .text(text(Text::Folder))
+            .parent(&data.content)
+            .font(Some(&data.small_font.borrow()))
+            .build(&mut data.btn_dir2)?;
+
+        nwg::Label::builder()
+            .text("")
+            .parent(&data.content)
+            .font(Some(&data.small_font.borrow()))
+            .build(&mut data.preview_label)?;
+
+        nwg::AnimationTimer::builder()
+            .parent(&data.window)
+            .interval(PREVIEW_DELAY)
+            .max_tick(Some(1))
+            .active(false)
+            .build(&mut data.preview_timer)?;
+
+        nwg::Notice::builder()
+            .parent(&data.window)
+            .build(&mut data.worker.notice)?;
+
+        nwg::FileDialog::builder()
+            .title(text(Text::SelectFile))
+            .action(nwg::FileDialogAction::Open)
+            .build(&mut data.file_dialog)?;
+
+        nwg::FileDialog::builder()
+            .title(text(Text::SelectFolder))
+            .action(nwg::FileDialogAction::OpenDirectory)
+            .build(&mut data.dir_dialog)?;
+
+        nwg::CheckBox::builder()
+            .text(text(Text::AddToSendTo))
+            .check_state(if shell::send_to_installed() {
+                nwg::CheckBoxState::Checked
+            } else {
+                nwg::CheckBoxState::Unchecked
+            })
+            .parent(&data.content)
+            .font(Some(&data.small_font.borrow()))
+            .build(&mut data.check_send_to)?;
+
+        nwg::Button::builder()
+            .text(text(Text::Settings))
+            .parent(&data.content)
+            .font(Some(&data.small_font.borrow()))
+            .build(&mut data.btn_settings)?;
+
         nwg::Button::builder()
-            .text("互换名称")
+            .text(text(Text::SwapNames))
             .parent(&data.content)
-            .font(Some(&data.font))
+            .font(Some(&data.font.borrow()))
             .build(&mut data.btn_exchange)?;
 
+        nwg::ProgressBar::builder()
+            .range(0..100)
+            .parent(&data.content)
+            .build(&mut data.copy_progress)?;
+
+        nwg::Button::builder()
+            .text(text(Text::AddToQueue))
+            .parent(&data.content)
+            .font(Some(&data.small_font.borrow()))
+            .build(&mut data.btn_queue_add)?;
+
+        nwg::Button::builder()
+            .text(text(Text::RunAll))
+            .parent(&data.content)
+            .font(Some(&data.small_font.borrow()))
+            .build(&mut data.btn_queue_run)?;
+
+        nwg::ListView::builder()
+            .parent(&data.content)
+            .list_style(nwg::ListViewStyle::Detailed)
+            .ex_flags(nwg::ListViewExFlags::FULL_ROW_SELECT | nwg::ListViewExFlags::GRID)
+            .build(&mut data.queue_view)?;
+
+        for (title, width) in [Text::ColumnStatus, Text::ColumnPath1, Text::ColumnPath2]
+            .into_iter()
+            .zip(COLUMN_WIDTHS)
+        {
+            data.queue_view.insert_column(nwg::InsertListViewColumn {
+                index: None,
+                fmt: None,
+                width: Some(scaled(width)),
+                text: Some(text(title).to_string()),
+            });
+        }
+        data.queue_view.set_headers_enabled(true);
+
+        nwg::ProgressBar::builder()
+            .range(0..1)
+            .parent(&data.content)
+            .build(&mut data.queue_progress)?;
+
+        nwg::ListView::builder()
+            .parent(&data.content)
+            .list_style(nwg::ListViewStyle::Detailed)
+            .ex_flags(nwg::ListViewExFlags::FULL_ROW_SELECT | nwg::ListViewExFlags::GRID)
+            .build(&mut data.history_view)?;
+
+        nwg::Label::builder()
+            .text(text(Text::StatusReady))
+            .parent(&data.content)
+            .font(Some(&data.small_font.borrow()))
+            .build(&mut data.status_label)?;
+
+        nwg::Tooltip::builder()
+            .register(&data.status_label, "")
+            .build(&mut data.status_tooltip)?;
+        if let Some(hwnd) = data.status_tooltip.handle.hwnd() {
+            set_tooltip_width(hwnd);
+        }
+
+        nwg::Button::builder()
+            .text(text(Text::CopyResult))
+            .enabled(false)
+            .parent(&data.content)
+            .font(Some(&data.small_font.borrow()))
+            .build(&mut data.btn_copy_result)?;
+
+        nwg::Button::builder()
+            .text(text(Text::OpenFolder))
+            .enabled(false)
+            .parent(&data.content)
+            .font(Some(&data.small_font.borrow()))
+            .build(&mut data.btn_open_folder)?;
+
+        for (title, width) in [Text::ColumnResult, Text::ColumnPath1, Text::ColumnPath2]
+            .into_iter()
+            .zip(COLUMN_WIDTHS)
+        {
+            data.history_view.insert_column(nwg::InsertListViewColumn {
+                index: None,
+                fmt: None,
+                width: Some(scaled(width)),
+                text: Some(text(title).to_string()),
+            });
+        }
+        data.history_view.set_headers_enabled(true);
+        for entry in &data.history.borrow().entries {
+            data.insert_history_row(entry);
+        }
+        data.refresh_recent_paths();
+
         nwg::Icon::builder()
             .source_bin(Some(include_bytes!("../res.ico")))
             .build(&mut data.tray_icon)?;
@@ -348,120 +1803,135 @@ impl nwg::NativeUi<AppUi> for App {
         nwg::TrayNotification::builder()
             .parent(&data.window)
             .icon(Some(&data.tray_icon))
-            .tip(Some("名称交换器"))
+            .tip(Some(text(Text::AppTitle)))
             .build(&mut data.tray)?;
 
+        nwg::Menu::builder()
+            .popup(true)
+            .parent(&data.window)
+            .build(&mut data.tray_menu)?;
+
+        nwg::MenuItem::builder()
+            .text(text(Text::TrayShow))
+            .parent(&data.tray_menu)
+            .build(&mut data.tray_show)?;
+
+        nwg::MenuItem::builder()
+            .text(text(Text::TraySwapClipboard))
+            .parent(&data.tray_menu)
+            .build(&mut data.tray_swap_clipboard)?;
+
+        nwg::MenuItem::builder()
+            .text(text(Text::TraySwapRecent))
+            .parent(&data.tray_menu)
+            .build(&mut data.tray_swap_recent)?;
+
+        nwg::MenuItem::builder()
+            .text(text(Text::TrayExit))
+            .parent(&data.tray_menu)
+            .build(&mut data.tray_exit)?;
+
+        data.settings_dialog.build(&data.window)?;
+
         let ui = AppUi {
             inner: Rc::new(data),
             default_handler: RefCell::new(None),
+            dialog_handler: RefCell::new(None),
         };
 
-        nwg::FlexboxLayout::builder()
-            .parent(&ui.inner.title_bar)
-            .flex_direction(FlexDirection::Row)
-            .justify_content(JustifyContent::FlexEnd)
-            .align_items(AlignItems::Center)
-            .padding(TITLE_PADDING)
-            .child(&ui.inner.title_label)
-            .child_flex_grow(1.0)
-            .child_size(Size {
-                width: D::Auto,
-                height: D::Auto,
-            })
-            .child(&ui.inner.btn_pin)
-            .child_margin(MARGIN)
-            .child_size(Size {
-                width: D::Points(70.0),
-                height: D::Percent(1.0),
-            })
-            .child(&ui.inner.btn_help)
-            .child_margin(MARGIN)
-            .child_size(Size {
-                width: D::Points(70.0),
-                height: D::Percent(1.0),
-            })
-            .child(&ui.inner.btn_minimize)
-            .child_margin(MARGIN)
-            .child_size(Size {
-                width: D::Points(50.0),
-                height: D::Percent(1.0),
-            })
-            .child(&ui.inner.btn_close)
-            .child_margin(MARGIN)
-            .child_size(Size {
-                width: D::Points(50.0),
-                height: D::Percent(1.0),
-            })
-            .build(&ui.inner.title_layout)?;
-
-        nwg::FlexboxLayout::builder()
-            .parent(&ui.inner.content)
-            .flex_direction(FlexDirection::Column)
-            .padding(PADDING)
-            .child(&ui.inner.label_path1)
-            .child_margin(MARGIN)
-            .child_size(Size {
-                width: D::Percent(1.0),
-                height: D::Points(20.0),
-            })
-            .child(&ui.inner.text_path1)
-            .child_margin(MARGIN)
-            .child_size(Size {
-                width: D::Percent(1.0),
-                height: D::Points(30.0),
-            })
-            .child(&ui.inner.label_path2)
-            .child_margin(MARGIN)
-            .child_size(Size {
-                width: D::Percent(1.0),
-                height: D::Points(20.0),
-            })
-            .child(&ui.inner.text_path2)
-            .child_margin(MARGIN)
-            .child_size(Size {
-                width: D::Percent(1.0),
-                height: D::Points(30.0),
-            })
-            .child(&ui.inner.btn_exchange)
-            .child_margin(MARGIN)
-            .child_flex_grow(1.0)
-            .child_size(Size {
-                width: D::Percent(1.0),
-                height: D::Auto,
-            })
-            .build(&ui.inner.content_layout)?;
-
-        nwg::FlexboxLayout::builder()
-            .parent(&ui.inner.window)
-            .flex_direction(FlexDirection::Column)
-            .child(&ui.inner.title_bar)
-            .child_size(Size {
-                width: D::Percent(1.0),
-                height: D::Points(TITLE_BAR_HEIGHT as f32),
-            })
-            .child(&ui.inner.content)
-            .child_flex_grow(1.0)
-            .child_size(Size {
-                width: D::Percent(1.0),
-                height: D::Auto,
-            })
-            .build(&ui.inner.main_layout)?;
+        ui.inner.build_layouts()?;
 
         let evt_ui = Rc::downgrade(&ui.inner);
-        let handle_events = move |evt, _evt_data, handle| {
+        let handle_events = move |evt, evt_data: nwg::EventData, handle| {
             if let Some(evt_ui) = evt_ui.upgrade() {
                 match evt {
                     E::OnButtonClick => {
                         if handle == evt_ui.btn_exchange {
-                            evt_ui.on_exchange();
+                            // The button reads "Cancel" while a job runs
+                            if evt_ui.worker.is_busy() {
+                                evt_ui.cancel_job();
+                            } else {
+                                evt_ui.on_exchange();
+                            }
                         } else if handle == evt_ui.btn_minimize {
                             evt_ui.minimize_window();
                         } else if handle == evt_ui.btn_close {
-                            evt_ui.hide_window();
+                            evt_ui.close_window();
                         } else if handle == evt_ui.btn_pin {
                             evt_ui.toggle_topmost();
                         } else if handle == evt_ui.btn_help {
                             evt_ui.show_help();
+                        } else if handle == evt_ui.btn_settings {
+                            evt_ui.show_settings();
+                        } else if handle == evt_ui.check_send_to {
+                            evt_ui.toggle_send_to();
+                        } else if handle == evt_ui.btn_queue_add {
+                            evt_ui.queue_pair();
+                        } else if handle == evt_ui.btn_queue_run {
+                            evt_ui.run_queue();
+                        } else if handle == evt_ui.btn_copy_result {
+                            evt_ui.copy_result();
+                        } else if handle == evt_ui.btn_open_folder {
+                            evt_ui.open_result_folder();
+                        } else if handle == evt_ui.btn_file1 {
+                            evt_ui.browse(false, false);
+                        } else if handle == evt_ui.btn_dir1 {
+                            evt_ui.browse(false, true);
+                        } else if handle == evt_ui.btn_file2 {
+                            evt_ui.browse(true, false);
+                        } else if handle == evt_ui.btn_dir2 {
+                            evt_ui.browse(true, true);
+                        }
+                    }
+                    // Enter and Esc reach the window as IDOK / IDCANCEL from the
+                    // dialog manager, message boxes run their own loop so none of
+                    // these fire while one is open
+                    E::OnKeyEnter => {
+                        if evt_ui.btn_exchange.enabled() && !evt_ui.worker.is_busy() {
+                            evt_ui.on_exchange();
+                        }
+                    }
+                    E::OnKeyEsc => evt_ui.clear_paths(),
+                    E::OnKeyPress => {
+                        // The path fields keep their own Ctrl+Z for editing
+                        if evt_data.on_key() == 'Z' as u32
+                            && ctrl_pressed()
+                            && handle != evt_ui.text_path1
+                            && handle != evt_ui.text_path2
+                        {
+                            evt_ui.undo_swap();
+                        }
+                    }
+                    E::OnComboxBoxSelection => {
+                        if handle == evt_ui.combo_recent1 {
+                            evt_ui.pick_recent(false);
+                        } else if handle == evt_ui.combo_recent2 {
+                            evt_ui.pick_recent(true);
+                        }
+                    }
+                    E::OnTextInput => {
+                        if handle == evt_ui.text_path1 || handle == evt_ui.text_path2 {
+                            // Restarting the timer debounces fast typing
+                            evt_ui.preview_timer.start();
+                        }
+                    }
+                    E::OnListViewDoubleClick => {
+                        if handle == evt_ui.history_view {
+                            let (row, _) = evt_data.on_list_view_item_index();
+                            evt_ui.rerun_history(row);
+                        } else if handle == evt_ui.queue_view {
+                            let (row, _) = evt_data.on_list_view_item_index();
+                            evt_ui.unqueue(row);
+                        }
+                    }
+                    E::OnNotice => {
+                        if handle == evt_ui.worker.notice {
+                            evt_ui.on_worker_notice();
+                        }
+                    }
+                    E::OnTimerTick => {
+                        if handle == evt_ui.preview_timer {
+                            evt_ui.update_preview();
                         }
                     }
                     E::OnMousePress(nwg::MousePressEvent::MousePressLeftUp) => {
@@ -471,18 +1941,34 @@ impl nwg::NativeUi<AppUi> for App {
                     }
                     E::OnContextMenu => {
                         if handle == evt_ui.tray {
+                            evt_ui.show_tray_menu();
+                        }
+                    }
+                    E::OnMenuItemSelected => {
+                        if handle == evt_ui.tray_show {
+                            evt_ui.show_window();
+                        } else if handle == evt_ui.tray_swap_clipboard {
+                            evt_ui.swap_clipboard_paths();
+                        } else if handle == evt_ui.tray_swap_recent {
+                            evt_ui.swap_recent_copies();
+                        } else if handle == evt_ui.tray_exit {
                             nwg::stop_thread_dispatch();
                         }
                     }
                     E::OnWindowClose => {
                         if handle == evt_ui.window {
-                            evt_ui.hide_window();
+                            evt_ui.close_window();
                         }
                     }
                     E::OnInit => {
                         setup_window_style(&evt_ui.window, *evt_ui.is_topmost.borrow());
                         setup_drag_drop(&evt_ui);
+                        setup_copy_progress(&evt_ui);
                         evt_ui.refresh_pin_button();
+                        evt_ui.restore_session();
+                        evt_ui.drop_files(evt_ui.launch_paths.take());
+                        evt_ui.offer_shortcuts();
+                        evt_ui.update_preview();
                     }
                     _ => {}
                 }
@@ -494,17 +1980,214 @@ impl nwg::NativeUi<AppUi> for App {
             handle_events,
         ));
 
+        let evt_ui = Rc::downgrade(&ui.inner);
+        let handle_dialog_events = move |evt, evt_data: nwg::EventData, handle| {
+            if let Some(evt_ui) = evt_ui.upgrade() {
+                let dialog = &evt_ui.settings_dialog;
+                match evt {
+                    E::OnButtonClick => {
+                        if handle == dialog.btn_ok {
+                            evt_ui.save_settings_dialog();
+                        } else if handle == dialog.btn_cancel {
+                            dialog.close(&evt_ui.window);
+                        } else if handle == dialog.btn_reset {
+                            evt_ui.reset_settings_dialog();
+                        } else if handle == dialog.btn_browse {
+                            dialog.browse_backup();
+                        }
+                    }
+                    E::OnWindowClose => {
+                        if handle == dialog.window {
+                            // Kept for the next opening, closing only hides it
+                            if let nwg::EventData::OnWindowClose(close) = &evt_data {
+                                close.close(false);
+                            }
+                            dialog.close(&evt_ui.window);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        };
+
+        *ui.dialog_handler.borrow_mut() = Some(nwg::full_bind_event_handler(
+            &ui.inner.settings_dialog.window.handle,
+            handle_dialog_events,
+        ));
+
         Ok(ui)
     }
 }
 
+/// Ask the user to confirm a swap, warning about directories and nesting
+///
+/// ### Parameters
+/// * `nested` - One item is a folder containing the other
+///
+/// ### Return Value
+/// `true` if the user chose to swap
+fn confirm_swap(p1: &Path, p2: &Path, nested: bool) -> bool {
+    let (p1, p2) = (resolve_typed(p1), resolve_typed(p2));
+
+    let mut msg = fill(
+        text(Text::ConfirmSwap),
+        &[&display_name(&p1), &display_name(&p2)],
+    );
+    if p1.is_dir() || p2.is_dir() {
+        msg.push_str(text(Text::WarnFolderMoves));
+    }
+    if nested {
+        msg.push_str(text(Text::WarnNested));
+    }
+    msg.push_str(text(Text::ConfirmOptOut));
+
+    msgbox::quest_msgbox_yesno(msg, text(Text::TitleConfirm), 0) == 6
+}
+
+/// Ask the user to confirm swapping a folder with an item inside it
+///
+/// Asked even when swaps are not confirmed, since the tree is restructured
+///
+/// ### Return Value
+/// `true` if the user chose to swap
+fn confirm_nested(p1: &Path, p2: &Path) -> bool {
+    let (p1, p2) = (resolve_typed(p1), resolve_typed(p2));
+    let msg = fill(
+        text(Text::ConfirmNested),
+        &[&display_name(&p1), &display_name(&p2)],
+    );
+    msgbox::quest_msgbox_yesno(msg, text(Text::TitleConfirm), 0) == 6
+}
+
+/// Resolve a typed path against the program folder, as `Settings::exchange_options`
+/// does, keeping it as typed when it cannot be resolved
+fn resolve_typed(path: &Path) -> PathBuf {
+    let base_dir = storage::exe_dir().unwrap_or_default();
+    resolve_path_rs(path, &base_dir)
+        .map(|(_, path)| path)
+        .unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Show after a field label whether its path exists
+///
+/// ### Return Value
+/// `true` if the path is filled in and exists
+/// Format the old and new names of both items, one swap per line
+fn describe_swap(old1: &Path, new1: &Path, old2: &Path, new2: &Path) -> String {
+    format!(
+        "{} → {}\n{} → {}",
+        display_name(old1),
+        display_name(new1),
+        display_name(old2),
+        display_name(new2)
+    )
+}
+
+/// Open the folder containing a path in Explorer with the item selected
+fn show_in_explorer(path: &Path) {
+    use std::os::windows::process::CommandExt;
+
+    // Explorer wants the path quoted after the comma, not the whole argument
+    let result = std::process::Command::new("explorer.exe")
+        .raw_arg(format!("/select,\"{}\"", path.display()))
+        .spawn();
+    if let Err(e) = result {
+        msgbox::error_msgbox(e.to_string(), text(Text::TitleError), 0);
+    }
+}
+
+/// Final component of a path, or the whole path for roots
+fn display_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.to_string_lossy().to_string())
+}
+
+fn ctrl_pressed() -> bool {
+    // The high bit of the state is set while the key is down
+    unsafe { winapi::um::winuser::GetKeyState(winapi::um::winuser::VK_CONTROL) < 0 }
+}
+
+/// Check that a saved window position is still on one of the monitors
+fn position_on_screen(x: i32, y: i32) -> bool {
+    use winapi::um::winuser::{
+        GetSystemMetrics, SM_CXVIRTUALSCREEN, SM_CYVIRTUALSCREEN, SM_XVIRTUALSCREEN,
+        SM_YVIRTUALSCREEN,
+    };
+
+    unsafe {
+        let left = GetSystemMetrics(SM_XVIRTUALSCREEN);
+        let top = GetSystemMetrics(SM_YVIRTUALSCREEN);
+        let right = left + GetSystemMetrics(SM_CXVIRTUALSCREEN);
+        let bottom = top + GetSystemMetrics(SM_CYVIRTUALSCREEN);
+        // Keep at least the title bar reachable
+        x >= left
+            && x + scaled(MIN_WIDTH) / 2 <= right
+            && y >= top
+            && y + scaled(TITLE_BAR_HEIGHT) <= bottom
+    }
+}
+
+/// Scale a size given at `DEFAULT_DPI` to the DPI of the window
+fn scaled(value: i32) -> i32 {
+    let dpi = DPI.load(Ordering::Relaxed) as i32;
+    (value * dpi + DEFAULT_DPI as i32 / 2) / DEFAULT_DPI as i32
+}
+
+/// Same as `scaled` for the layout, which works in fractional points
+fn scaled_f32(value: f32) -> f32 {
+    value * DPI.load(Ordering::Relaxed) as f32 / DEFAULT_DPI as f32
+}
+
+/// Build one of the window fonts at the current DPI
+///
+/// ### Parameters
+/// * `font` - Font to (re)build
+/// * `size` - Height at `DEFAULT_DPI`
+fn build_font(font: &mut nwg::Font, size: i32) -> Result<(), nwg::NwgError> {
+    nwg::Font::builder()
+        .family("Microsoft YaHei UI")
+        .size(scaled(size) as u32)
+        .weight(400)
+        .build(font)
+}
+
+/// Widen the open list of a recent path box beyond the box itself, at the current DPI
+fn set_dropped_width(combo: &nwg::ComboBox<String>) {
+    if let Some(hwnd) = combo.handle.hwnd() {
+        unsafe {
+            winapi::um::winuser::SendMessageW(
+                hwnd,
+                CB_SETDROPPEDWIDTH,
+                scaled(RECENT_LIST_WIDTH) as usize,
+                0,
+            );
+        }
+    }
+}
+
+/// Set the maximum width of the status tooltip at the current DPI
+///
+/// A maximum width makes the tooltip wrap and honor line breaks
+fn set_tooltip_width(hwnd: winapi::shared::windef::HWND) {
+    unsafe {
+        winapi::um::winuser::SendMessageW(
+            hwnd,
+            TTM_SETMAXTIPWIDTH,
+            0,
+            scaled(TOOLTIP_WIDTH) as isize,
+        );
+    }
+}
+
 fn setup_window_style(window: &nwg::Window, topmost: bool) {
     if let Some(hwnd) = window.handle.hwnd() {
         unsafe {
             let style = winapi::um::winuser::GetWindowLongPtrW(hwnd as _, GWL_STYLE) as u32;
             let mut new_style = style;
-            new_style &= !(WS_MAXIMIZEBOX | WS_CAPTION | WS_THICKFRAME | WS_SYSMENU);
-            new_style |= WS_MINIMIZEBOX;
+            new_style &= !(WS_MAXIMIZEBOX | WS_CAPTION | WS_SYSMENU);
+            // Kept for resizing from the edges, `WM_NCCALCSIZE` hides the frame it draws
+            new_style |= WS_MINIMIZEBOX | WS_THICKFRAME;
             winapi::um::winuser::SetWindowLongPtrW(hwnd as _, GWL_STYLE, new_style as isize);
 
             let ex_style = winapi::um::winuser::GetWindowLongPtrW(hwnd as _, GWL_EXSTYLE) as u32;
@@ -567,6 +2250,8 @@ fn setup_drag_drop(app: &App) {
                 subclass_wnd_proc as *const () as isize,
             );
             OLD_WND_PROC = Some(old_proc);
+            // Delivered through the subclass procedure installed above
+            clipboard::listen(hwnd as _);
 
             let app_ptr = app as *const App as isize;
             winapi::um::winuser::SetWindowLongPtrW(
@@ -578,31 +2263,134 @@ fn setup_drag_drop(app: &App) {
     }
 }
 
+/// Text of a result code in the history and queue lists
+fn status_text(code: i32) -> String {
+    if code == 0 {
+        text(Text::StatusSuccess).to_string()
+    } else {
+        fill(text(Text::StatusFailed), &[&code])
+    }
+}
+
+/// Show the progress of cross-volume copies, which can take a while for big directories
+fn setup_copy_progress(app: &App) {
+    if let Some(hwnd) = app.copy_progress.handle.hwnd() {
+        unsafe {
+            PROGRESS_HWND = Some(hwnd as isize);
+        }
+        name_exchanger_lib::register_progress_callback(Some(on_copy_progress));
+    }
+}
+
+extern "C" fn on_copy_progress(copied: u64, total: u64) {
+    if total == 0 {
+        return;
+    }
+    let percent = (copied.saturating_mul(100) / total).min(100);
+    unsafe {
+        if let Some(hwnd) = PROGRESS_HWND {
+            // Called on the worker thread, the UI thread applies it when it gets to it
+            winapi::um::winuser::PostMessageW(hwnd as _, PBM_SETPOS, percent as usize, 0);
+        }
+    }
+}
+
 unsafe extern "system" fn subclass_wnd_proc(
     hwnd: winapi::shared::windef::HWND,
     msg: u32,
     wparam: winapi::shared::minwindef::WPARAM,
     lparam: winapi::shared::minwindef::LPARAM,
 ) -> isize {
+    if msg == WM_NCCALCSIZE && wparam != 0 {
+        // The client area covers the whole window, so the sizing frame stays invisible
+        return 0;
+    }
+
     if msg == WM_NCHITTEST {
         let x = (lparam & 0xFFFF) as i16 as i32;
         let y = ((lparam >> 16) & 0xFFFF) as i16 as i32;
         let mut point = winapi::shared::windef::POINT { x, y };
+        let mut client: winapi::shared::windef::RECT = mem::zeroed();
         unsafe {
             winapi::um::winuser::ScreenToClient(hwnd, &mut point);
+            winapi::um::winuser::GetClientRect(hwnd, &mut client);
         }
-        if point.y >= 0 && point.y < TITLE_BAR_HEIGHT {
+        let border = scaled(RESIZE_BORDER);
+        let left = point.x < border;
+        let right = point.x >= client.right - border;
+        let top = point.y < border;
+        let bottom = point.y >= client.bottom - border;
+        match (left, right, top, bottom) {
+            (true, _, true, _) => return HTTOPLEFT,
+            (_, true, true, _) => return HTTOPRIGHT,
+            (true, _, _, true) => return HTBOTTOMLEFT,
+            (_, true, _, true) => return HTBOTTOMRIGHT,
+            (true, ..) => return HTLEFT,
+            (_, true, ..) => return HTRIGHT,
+            (_, _, true, _) => return HTTOP,
+            (.., true) => return HTBOTTOM,
+            _ => {}
+        }
+        if point.y >= 0 && point.y < scaled(TITLE_BAR_HEIGHT) {
             return HTCAPTION;
         }
     }
 
     if msg == WM_GETMINMAXINFO {
         let minmax = lparam as *mut winapi::um::winuser::MINMAXINFO;
-        (*minmax).ptMinTrackSize.x = MIN_WIDTH;
-        (*minmax).ptMinTrackSize.y = MIN_HEIGHT;
+        (*minmax).ptMinTrackSize.x = scaled(MIN_WIDTH);
+        (*minmax).ptMinTrackSize.y = scaled(MIN_HEIGHT);
+        return 0;
+    }
+
+    if msg == WM_DPICHANGED {
+        // Per-monitor aware, so the window rescales itself when dragged to
+        // a monitor with another scale instead of being stretched blurry
+        let dpi = (wparam & 0xFFFF) as u32;
+        let app_ptr =
+            winapi::um::winuser::GetWindowLongPtrW(hwnd, winapi::um::winuser::GWLP_USERDATA);
+        if app_ptr != 0 {
+            let app_ref = &*(app_ptr as *const App);
+            app_ref.apply_dpi(dpi);
+        }
+        // Suggested by Windows to keep the same size on the new monitor
+        let rect = &*(lparam as *const winapi::shared::windef::RECT);
+        winapi::um::winuser::SetWindowPos(
+            hwnd,
+            ptr::null_mut(),
+            rect.left,
+            rect.top,
+            rect.right - rect.left,
+            rect.bottom - rect.top,
+            SWP_NOZORDER | SWP_NOACTIVATE,
+        );
         return 0;
     }
 
+    if msg == clipboard::WM_CLIPBOARDUPDATE {
+        let app_ptr =
+            winapi::um::winuser::GetWindowLongPtrW(hwnd, winapi::um::winuser::GWLP_USERDATA);
+        if app_ptr != 0 {
+            let app_ref = &*(app_ptr as *const App);
+            app_ref.recent_copies.borrow_mut().record(hwnd);
+        }
+        return 0;
+    }
+
+    if msg == WM_COPYDATA {
+        let copy = lparam as *const winapi::um::winuser::COPYDATASTRUCT;
+        if let Some(paths) = instance::read_paths(copy) {
+            let app_ptr =
+                winapi::um::winuser::GetWindowLongPtrW(hwnd, winapi::um::winuser::GWLP_USERDATA);
+            if app_ptr != 0 {
+                let app_ref = &*(app_ptr as *const App);
+                app_ref.show_window();
+                app_ref.drop_files(paths);
+            }
+            return 1;
+        }
+    }
+
     if msg == WM_DROPFILES {
         let hdrop = wparam as winapi::um::shellapi::HDROP;
         let count =
@@ -653,16 +2441,41 @@ unsafe extern "system" fn subclass_wnd_proc(
 }
 
 fn main() {
+    messages::set_language(
+        config::Settings::load()
+            .language
+            .unwrap_or_else(Language::system),
+    );
+
+    let args: Vec<OsString> = std::env::args_os().skip(1).collect();
+    if let Some(code) = cli::run(&args) {
+        std::process::exit(code);
+    }
+    let args: Vec<PathBuf> = args
+        .into_iter()
+        .filter(|arg| arg != cli::GUI_FLAG)
+        .map(PathBuf::from)
+        .collect();
+
+    let _instance = match instance::acquire() {
+        Some(guard) => guard,
+        None => {
+            let titles = Language::ALL.map(|language| text_in(language, Text::AppTitle));
+            instance::forward_to_existing(&titles, &args);
+            return;
+        }
+    };
+
     nwg::init().expect("Failed to init Native Windows GUI");
 
     let default_font = nwg::Font::default();
     nwg::Font::set_global_family("Microsoft YaHei UI").unwrap();
     nwg::Font::set_global_default(Some(default_font));
 
-    let _app = App::build_ui(App {
+    let app = App::build_ui(App {
         window: Default::default(),
-        font: Default::default(),
-        small_font: Default::default(),
+        font: RefCell::new(Default::default()),
+        small_font: RefCell::new(Default::default()),
         title_bar: Default::default(),
         title_label: Default::default(),
         btn_pin: Default::default(),
@@ -672,19 +2485,65 @@ fn main() {
         title_layout: Default::default(),
         content: Default::default(),
         label_path1: Default::default(),
+        icon_path1: Default::default(),
         text_path1: Default::default(),
+        combo_recent1: Default::default(),
+        btn_file1: Default::default(),
+        btn_dir1: Default::default(),
+        row1_layout: Default::default(),
         label_path2: Default::default(),
+        icon_path2: Default::default(),
         text_path2: Default::default(),
+        combo_recent2: Default::default(),
+        btn_file2: Default::default(),
+        btn_dir2: Default::default(),
+        row2_layout: Default::default(),
+        file_dialog: Default::default(),
+        dir_dialog: Default::default(),
+        preview_label: Default::default(),
+        preview_timer: Default::default(),
+        check_send_to: Default::default(),
+        btn_settings: Default::default(),
+        options_layout: Default::default(),
         btn_exchange: Default::default(),
+        copy_progress: Default::default(),
+        btn_queue_add: Default::default(),
+        btn_queue_run: Default::default(),
+        queue_layout: Default::default(),
+        queue_view: Default::default(),
+        queue_progress: Default::default(),
+        history_view: Default::default(),
+        status_label: Default::default(),
+        status_tooltip: Default::default(),
+        btn_copy_result: Default::default(),
+        btn_open_folder: Default::default(),
+        status_layout: Default::default(),
         content_layout: Default::default(),
         main_layout: Default::default(),
         tray_icon: Default::default(),
         tray: Default::default(),
+        tray_menu: Default::default(),
+        tray_show: Default::default(),
+        tray_swap_clipboard: Default::default(),
+        tray_swap_recent: Default::default(),
+        tray_exit: Default::default(),
+        settings_dialog: Default::default(),
         path1: RefCell::new(None),
         path2: RefCell::new(None),
+        file_types: RefCell::new([None, None]),
+        last_dir: RefCell::new(None),
+        launch_paths: RefCell::new(args),
+        settings: RefCell::new(config::Settings::load()),
+        history: RefCell::new(history::History::load()),
+        queue: RefCell::new(Vec::new()),
+        last_result: RefCell::new(None),
+        recent_copies: RefCell::new(Default::default()),
         is_topmost: RefCell::new(true),
+        worker: Default::default(),
+        job_cancel: Default::default(),
     })
     .expect("Failed to build UI");
 
     nwg::dispatch_thread_events();
+    app.inner.save_session();
 }
@@ -0,0 +1,533 @@
+// User-facing strings in every supported language
+//
+// Keeps the wording of every label, dialog and result code in one place,
+// so the window and the command-line mode say the same thing the same way.
+// The language is chosen once at startup.
+
+use std::{
+    fmt::Display,
+    sync::atomic::{AtomicU8, Ordering},
+};
+
+use name_exchanger_lib::RenameError;
+
+/// Display language of the user interface
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    Chinese,
+    English,
+}
+
+impl Language {
+    /// Every supported language, in the order offered to the user
+    pub const ALL: [Language; 2] = [Language::Chinese, Language::English];
+
+    /// Language of the Windows user interface, English unless it is Chinese
+    pub fn system() -> Language {
+        const LANG_CHINESE: u16 = 0x04;
+        // The primary language is the low 10 bits of a LANGID
+        let id = unsafe { winapi::um::winnls::GetUserDefaultUILanguage() };
+        if id & 0x3FF == LANG_CHINESE {
+            Language::Chinese
+        } else {
+            Language::English
+        }
+    }
+
+    /// Name of the language in the config file
+    pub fn key(self) -> &'static str {
+        match self {
+            Language::Chinese => "zh",
+            Language::English => "en",
+        }
+    }
+
+    /// Read a name written by `key`
+    pub fn from_key(key: &str) -> Option<Language> {
+        Language::ALL
+            .into_iter()
+            .find(|language| language.key() == key)
+    }
+
+    /// Name of the language written in that language
+    pub fn native_name(self) -> &'static str {
+        match self {
+            Language::Chinese => "中文",
+            Language::English => "English",
+        }
+    }
+}
+
+static LANGUAGE: AtomicU8 = AtomicU8::new(0);
+
+/// Choose the language of every following string
+pub fn set_language(language: Language) {
+    LANGUAGE.store(language as u8, Ordering::Relaxed);
+}
+
+/// Language currently in use
+pub fn language() -> Language {
+    match LANGUAGE.load(Ordering::Relaxed) {
+        0 => Language::Chinese,
+        _ => Language::English,
+    }
+}
+
+/// Fixed strings of the user interface
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Text {
+    AppTitle,
+    LabelPath1,
+    LabelPath2,
+    Pin,
+    Unpin,
+    Help,
+    File,
+    Folder,
+    SelectFile,
+    SelectFolder,
+    ConfirmBeforeSwap,
+    CloseToTray,
+    AddToSendTo,
+    SwapNames,
+    AddToQueue,
+    RunAll,
+    CopyResult,
+    OpenFolder,
+    ColumnStatus,
+    ColumnResult,
+    ColumnPath1,
+    ColumnPath2,
+    LanguageAuto,
+    Settings,
+    SettingLanguage,
+    SettingRefuseLinks,
+    SettingFollowLinks,
+    SettingBackup,
+    SettingRetries,
+    SettingForce,
+    SettingRecycle,
+    ResetDefaults,
+    Save,
+    Cancel,
+    TrayShow,
+    TraySwapClipboard,
+    TraySwapRecent,
+    TrayExit,
+    TitleNotice,
+    TitleError,
+    TitleSuccess,
+    TitleDone,
+    TitleConfirm,
+    TitleHelp,
+    TitleShortcuts,
+    TitleSettings,
+    HelpBody,
+    /// `{}` is the data directory
+    SettingsStoredAt,
+    NeedTwoPaths,
+    /// `{}` is the number of entries
+    EntriesMoved,
+    /// `{}` is the error message
+    SwapFailed,
+    StatusReady,
+    /// `{}` are the two new names
+    StatusSwapped,
+    StatusUndone,
+    Swapping,
+    Cancelling,
+    StillWorking,
+    NothingToUndo,
+    /// `{}` is the error message
+    UndoFailed,
+    StatusWaiting,
+    StatusSuccess,
+    /// `{}` is the result code
+    StatusFailed,
+    QueueEmpty,
+    /// `{}` is the number of pairs
+    ConfirmQueue,
+    /// `{}` are the total, succeeded and failed counts
+    BatchSummary,
+    TooManyDropped,
+    OfferShortcuts,
+    /// `{}` is the error message
+    ShortcutsFailed,
+    /// `{}` is the error message
+    SaveSettingsFailed,
+    /// `{}` is the error message
+    SendToFailed,
+    ClipboardNeedsTwo,
+    RecentCopiesNeedTwo,
+    OpenBothFolders,
+    LanguageOnRestart,
+    /// `{}` are the two names
+    ConfirmSwap,
+    WarnFolderMoves,
+    WarnNested,
+    ConfirmOptOut,
+    /// `{}` are the two names, asked even when swaps are not confirmed
+    ConfirmNested,
+    /// `{}` is the size in MB
+    ConfirmLargeCopy,
+    PathExists,
+    PathMissing,
+    /// `{}` is the option name
+    CliMissingBackupDir,
+    /// `{}` are the option given and the stdin option name
+    CliOptionUsage,
+    CliMissingTab,
+    /// `{}` is the match option name
+    CliMatchUsage,
+    /// `{}` is the path of the item
+    CliUnmatched,
+    CliNoMatches,
+    /// `{}` are the pairs, the succeeded, failed and unmatched counts
+    MatchSummary,
+    InvalidRetryCount,
+}
+
+/// Look up a fixed string in the current language
+pub fn text(key: Text) -> &'static str {
+    text_in(language(), key)
+}
+
+/// Look up a fixed string in a given language
+pub fn text_in(language: Language, key: Text) -> &'static str {
+    let (zh, en) = match key {
+        Text::AppTitle => ("名称交换器", "Name Exchanger"),
+        Text::LabelPath1 => ("文件/文件夹 1:", "File/folder 1:"),
+        Text::LabelPath2 => ("文件/文件夹 2:", "File/folder 2:"),
+        Text::Pin => ("置顶", "Pin"),
+        Text::Unpin => ("取消置顶", "Unpin"),
+        Text::Help => ("帮助", "Help"),
+        Text::File => ("文件", "File"),
+        Text::Folder => ("文件夹", "Folder"),
+        Text::SelectFile => ("选择文件", "Select a file"),
+        Text::SelectFolder => ("选择文件夹", "Select a folder"),
+        Text::ConfirmBeforeSwap => ("交换前确认", "Confirm swaps"),
+        Text::CloseToTray => ("关闭时最小化到托盘", "Close to tray"),
+        Text::AddToSendTo => ("添加到“发送到”菜单", "Add to \"Send to\""),
+        Text::SwapNames => ("互换名称", "Swap names"),
+        Text::AddToQueue => ("加入队列", "Add to queue"),
+        Text::RunAll => ("全部执行", "Run all"),
+        Text::CopyResult => ("复制", "Copy"),
+        Text::OpenFolder => ("打开位置", "Open folder"),
+        Text::ColumnStatus => ("状态", "Status"),
+        Text::ColumnResult => ("结果", "Result"),
+        Text::ColumnPath1 => ("路径 1", "Path 1"),
+        Text::ColumnPath2 => ("路径 2", "Path 2"),
+        Text::LanguageAuto => ("跟随系统", "System"),
+        Text::Settings => ("设置", "Settings"),
+        Text::SettingLanguage => ("界面语言", "Language"),
+        Text::SettingRefuseLinks => (
+            "拒绝交换链接与其指向的项目",
+            "Refuse to swap a link with its target",
+        ),
+        Text::SettingFollowLinks => (
+            "交换链接指向的项目的名称，而非链接本身",
+            "Swap the items links point at, not the links",
+        ),
+        Text::SettingBackup => (
+            "交换前备份到文件夹（留空则不备份）：",
+            "Back up both items to this folder first (empty for none):",
+        ),
+        Text::SettingRetries => (
+            "被占用时的重命名尝试次数",
+            "Rename attempts while an item is in use",
+        ),
+        Text::SettingForce => (
+            "删除占用目标名称的无关文件",
+            "Replace unrelated files using a target name",
+        ),
+        Text::SettingRecycle => (
+            "将被替换的文件移至回收站",
+            "Move replaced files to the Recycle Bin",
+        ),
+        Text::ResetDefaults => ("恢复默认", "Reset to defaults"),
+        Text::Save => ("确定", "OK"),
+        Text::Cancel => ("取消", "Cancel"),
+        Text::TrayShow => ("显示窗口", "Show window"),
+        Text::TraySwapClipboard => ("交换剪贴板中的路径", "Swap paths in clipboard"),
+        Text::TraySwapRecent => ("交换最近复制的两项", "Swap last two copied items"),
+        Text::TrayExit => ("退出", "Exit"),
+        Text::TitleNotice => ("提示", "Notice"),
+        Text::TitleError => ("错误", "Error"),
+        Text::TitleSuccess => ("成功", "Success"),
+        Text::TitleDone => ("完成", "Done"),
+        Text::TitleConfirm => ("确认交换", "Confirm swap"),
+        Text::TitleHelp => ("使用提示", "Tips"),
+        Text::TitleShortcuts => ("创建快捷方式", "Create shortcuts"),
+        Text::TitleSettings => ("设置", "Settings"),
+        Text::HelpBody => (
+            "拖入文件或文件夹，或点击“文件”/“文件夹”按钮选择路径即可使用；点击“置顶”切换置顶状态；点击“-”最小化到任务栏，点击“X”隐藏到托盘（可在“设置”中取消勾选“关闭时最小化到托盘”改为直接退出）；左键单击托盘图标可显示/隐藏窗口，右键托盘图标打开菜单；双击历史记录可重新填入路径，再次交换即可还原；路径框右侧的下拉箭头列出最近使用的路径。点击“加入队列”可暂存多对路径，再点击“全部执行”依次交换，双击队列中的项目可将其移除。点击底部的“复制”可复制上次交换后的两个路径，点击“打开位置”可在资源管理器中查看。快捷键：Enter 执行交换，Esc 清空两个路径，Ctrl+Z 撤销上次交换。",
+            "Drop files or folders, or pick paths with the \"File\"/\"Folder\" buttons. \"Pin\" keeps the window on top. \"-\" minimizes to the taskbar and \"X\" hides to the tray (uncheck \"Close to tray\" in \"Settings\" to exit instead). Left-click the tray icon to show or hide the window, right-click it for the menu. Double-click a history entry to fill its paths in again, swapping again restores the names. The arrow next to each path field lists recently used paths. \"Add to queue\" collects several pairs, \"Run all\" swaps them one after another, and double-clicking a queued pair removes it. \"Copy\" at the bottom puts the two paths of the last swap on the clipboard, \"Open folder\" shows them in Explorer. Shortcuts: Enter swaps, Esc clears both paths, Ctrl+Z undoes the last swap.",
+        ),
+        Text::SettingsStoredAt => ("设置与历史记录保存在：{}", "Settings and history are stored in: {}"),
+        Text::NeedTwoPaths => (
+            "请输入两个完整的文件或文件夹路径！",
+            "Please enter two complete file or folder paths!",
+        ),
+        Text::EntriesMoved => ("（共涉及 {} 个项目）", "({} items involved)"),
+        Text::SwapFailed => ("交换失败: {}", "Swap failed: {}"),
+        Text::StatusReady => ("就绪", "Ready"),
+        Text::StatusSwapped => ("已交换: {} ↔ {}", "Swapped: {} ↔ {}"),
+        Text::StatusUndone => ("已撤销上次交换", "Last swap undone"),
+        Text::Swapping => ("正在交换…", "Swapping…"),
+        Text::Cancelling => ("正在取消…", "Cancelling…"),
+        Text::StillWorking => (
+            "仍有交换正在进行，请稍候！",
+            "A swap is still running, please wait!",
+        ),
+        Text::NothingToUndo => (
+            "没有可撤销的交换，或文件已被移动",
+            "Nothing to undo, or the items have moved since",
+        ),
+        Text::UndoFailed => ("撤销失败: {}", "Undo failed: {}"),
+        Text::StatusWaiting => ("等待", "Waiting"),
+        Text::StatusSuccess => ("成功", "Done"),
+        Text::StatusFailed => ("失败 ({})", "Failed ({})"),
+        Text::QueueEmpty => (
+            "队列中没有待执行的项目！\n双击队列中的项目可将其移除。",
+            "Nothing left to run in the queue!\nDouble-click a queued pair to remove it.",
+        ),
+        Text::ConfirmQueue => (
+            "确定要依次交换队列中的 {} 对路径吗？",
+            "Swap the {} queued pairs one after another?",
+        ),
+        Text::BatchSummary => (
+            "共 {} 对，成功 {} 对，失败 {} 对",
+            "{} pairs, {} succeeded, {} failed",
+        ),
+        Text::TooManyDropped => (
+            "一次最多只能拖入两个文件或文件夹！",
+            "Drop at most two files or folders at a time!",
+        ),
+        Text::OfferShortcuts => (
+            "是否在桌面和开始菜单创建快捷方式？\n（此提示只显示一次）",
+            "Create shortcuts on the desktop and in the Start menu?\n(You will only be asked once)",
+        ),
+        Text::ShortcutsFailed => ("创建快捷方式失败: {}", "Could not create the shortcuts: {}"),
+        Text::SaveSettingsFailed => ("无法保存设置: {}", "Could not save the settings: {}"),
+        Text::SendToFailed => (
+            "无法修改“发送到”菜单: {}",
+            "Could not change the \"Send to\" menu: {}",
+        ),
+        Text::OpenBothFolders => (
+            "两个项目位于不同的文件夹，是否同时打开第二个文件夹？",
+            "The items are in different folders. Open the second folder as well?",
+        ),
+        Text::ClipboardNeedsTwo => (
+            "剪贴板中需要恰好两个路径，每行一个！",
+            "The clipboard must hold exactly two paths, one per line!",
+        ),
+        Text::RecentCopiesNeedTwo => (
+            "程序运行期间尚未复制过两个仍存在的文件、文件夹或其路径！",
+            "Copy two existing files or folders, or their paths, while the program is running first!",
+        ),
+        Text::LanguageOnRestart => (
+            "语言设置将在下次启动时生效。",
+            "The language takes effect on the next launch.",
+        ),
+        Text::ConfirmSwap => (
+            "确定要交换以下名称吗？\n{} ↔ {}",
+            "Swap these names?\n{} ↔ {}",
+        ),
+        Text::WarnFolderMoves => (
+            "\n\n注意：将重命名文件夹，其中的所有内容都会随之移动。",
+            "\n\nNote: a folder is renamed, everything inside it moves along.",
+        ),
+        Text::WarnNested => (
+            "\n注意：其中一个文件夹位于另一项之内，交换将改变目录结构。",
+            "\nNote: one folder is inside the other, swapping will restructure the tree.",
+        ),
+        Text::ConfirmOptOut => (
+            "\n\n（在“设置”中取消勾选“交换前确认”可不再询问）",
+            "\n\n(Uncheck \"Confirm swaps\" in \"Settings\" to stop asking)",
+        ),
+        Text::ConfirmNested => (
+            "其中一个文件夹位于另一项之内，交换将改变目录结构：\n{} ↔ {}\n\n仍要交换吗？",
+            "One folder is inside the other, swapping will restructure the tree:\n{} ↔ {}\n\nSwap anyway?",
+        ),
+        Text::ConfirmLargeCopy => (
+            "此次交换需要跨卷复制约 {} MB 的数据，可能需要较长时间。是否继续？",
+            "This swap copies about {} MB across volumes, which may take a while. Continue?",
+        ),
+        Text::PathExists => ("✔ 存在", "✔ exists"),
+        Text::PathMissing => ("✘ 不存在", "✘ missing"),
+        Text::CliMissingBackupDir => ("{} 后缺少备份目录", "Missing backup directory after {}"),
+        Text::CliOptionUsage => (
+            "{} 需要配合两个路径或 {} 使用",
+            "{} needs two paths or {}",
+        ),
+        Text::CliMissingTab => (
+            "缺少制表符分隔的第二个路径",
+            "Missing the tab-separated second path",
+        ),
+        Text::CliMatchUsage => (
+            "{} 后需要恰好两个通配符模式",
+            "{} needs exactly two wildcard patterns",
+        ),
+        Text::CliUnmatched => ("未配对: {}", "Unmatched: {}"),
+        Text::CliNoMatches => (
+            "没有可配对的项目",
+            "No items could be paired",
+        ),
+        Text::MatchSummary => (
+            "共 {} 对，成功 {} 对，失败 {} 对，未配对 {} 项",
+            "{} pairs, {} succeeded, {} failed, {} unmatched",
+        ),
+        Text::InvalidRetryCount => (
+            "重命名尝试次数必须是 1 到 10 之间的整数！",
+            "The rename attempts must be a whole number from 1 to 10!",
+        ),
+    };
+    match language {
+        Language::Chinese => zh,
+        Language::English => en,
+    }
+}
+
+/// Replace every `{}` of a template with the next argument
+///
+/// ### Parameters
+/// * `template` - String from `text` containing `{}` placeholders
+/// * `args` - Values for the placeholders, in order
+///
+/// ### Return Value
+/// Filled string, surplus placeholders are left as they are
+pub fn fill(template: &str, args: &[&dyn Display]) -> String {
+    let mut result = String::with_capacity(template.len());
+    let mut rest = template;
+    for arg in args {
+        let Some(index) = rest.find("{}") else {
+            break;
+        };
+        result.push_str(&rest[..index]);
+        result.push_str(&arg.to_string());
+        rest = &rest[index + 2..];
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Describe a result code returned by `exchange` and friends
+///
+/// ### Parameters
+/// * `code` - Result code, `0` for success
+///
+/// ### Return Value
+/// Message shown to the user
+pub fn code_message(code: i32) -> &'static str {
+    let (zh, en) = match code {
+        0 => ("名称交换成功！", "Names swapped!"),
+        1 => ("其中一个路径不存在！", "One of the paths does not exist!"),
+        2 => (
+            "权限不足，文件可能正被其他程序占用！",
+            "Permission denied, the file may be in use by another program!",
+        ),
+        3 => (
+            "已存在与目标名称相同的文件或文件夹！",
+            "A file or folder with the target name already exists!",
+        ),
+        4 => (
+            "两个路径指向同一个文件或文件夹！",
+            "Both paths point to the same file or folder!",
+        ),
+        5 => ("路径无效！", "Invalid path!"),
+        6 => ("路径为空！", "Empty path!"),
+        7 => (
+            "缓冲区不足，无法返回结果！",
+            "Buffer too small for the result!",
+        ),
+        8 => (
+            "跨磁盘复制校验失败，原文件未被改动！",
+            "Cross-drive copy could not be verified, the originals are untouched!",
+        ),
+        9 => (
+            "目标名称是 Windows 保留的设备名！",
+            "The target name is a reserved Windows device name!",
+        ),
+        10 => (
+            "两者名称相同，无需交换！",
+            "Both names are equal, nothing to swap!",
+        ),
+        11 => (
+            "备份失败，未执行交换！",
+            "Backup failed, nothing was swapped!",
+        ),
+        12 => (
+            "交换后校验失败，磁盘上的名称与预期不符！",
+            "Verification failed, the names on disk are not as expected!",
+        ),
+        13 => (
+            "已取消，名称已恢复原状。",
+            "Cancelled, the names were restored.",
+        ),
+        14 => (
+            "目标名称包含非法字符或过长！",
+            "The target name contains illegal characters or is too long!",
+        ),
+        15 => (
+            "文件仅存储在云端，或云同步程序阻止了此操作！",
+            "The file is only stored online, or the cloud sync app blocked the operation!",
+        ),
+        16 => (
+            "磁盘或卷的根目录无法重命名！",
+            "The root of a drive or volume cannot be renamed!",
+        ),
+        17 => ("路径中包含空字符！", "The path contains a NUL character!"),
+        18 => (
+            "目标磁盘空间不足，无法跨卷复制！",
+            "Not enough space on the target drive to copy across volumes!",
+        ),
+        19 => (
+            "包含未知或冲突的选项！",
+            "Unknown or conflicting options were requested!",
+        ),
+        20 => (
+            "目标位于其他卷，且未允许跨卷复制！",
+            "The target is on another volume and copying across volumes is off!",
+        ),
+        21 => (
+            "其中一个文件夹位于另一项之内，交换将改变目录结构！",
+            "One folder is inside the other, swapping would restructure the tree!",
+        ),
+        22 => (
+            "其中一项在计划交换后被替换为其他类型，未执行交换！",
+            "An item was replaced on disk after the swap was planned, nothing was swapped!",
+        ),
+        _ => ("未知错误！", "Unknown error!"),
+    };
+    match language() {
+        Language::Chinese => zh,
+        Language::English => en,
+    }
+}
+
+/// Describe an exchange error, appending its details when there are any
+///
+/// ### Parameters
+/// * `err` - Error returned by the library
+///
+/// ### Return Value
+/// Message shown to the user
+pub fn error_message(err: &RenameError) -> String {
+    let summary = code_message(err.to_code());
+    match err {
+        RenameError::InvalidPath(detail)
+        | RenameError::ReservedName(detail)
+        | RenameError::IllegalName(detail)
+        | RenameError::CloudFile(detail)
+        | RenameError::VolumeRoot(detail)
+        | RenameError::EmbeddedNul(detail)
+        | RenameError::InsufficientSpace(detail)
+        | RenameError::CrossesVolumes(detail)
+        | RenameError::NestedItems(detail)
+        | RenameError::ItemChanged(detail)
+        | RenameError::BackupFailed(detail)
+        | RenameError::VerifyFailed(detail)
+        | RenameError::Unknown(detail) => format!("{}\n{}", summary, detail),
+        _ => summary.to_string(),
+    }
+}
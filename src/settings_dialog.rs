@@ -0,0 +1,396 @@
+// Settings dialog
+//
+// Modal window editing the preferences and swap options stored by `config`,
+// the same settings the command line starts from. Confirming saves and
+// applies them at once, except the language, which menus and layouts only
+// pick up on the next launch. "Reset to defaults" only fills in the defaults
+// so they can be reviewed before saving.
+
+use std::path::PathBuf;
+
+use name_exchanger_lib::MAX_RENAME_ATTEMPTS;
+use native_windows_gui as nwg;
+
+use crate::config::Settings;
+use crate::messages::{text, Language, Text};
+use crate::{scaled, scaled_f32};
+
+const WIDTH: i32 = 400;
+const HEIGHT: i32 = 450;
+
+/// Controls of the settings dialog, built hidden once and shown on demand
+#[derive(Default)]
+pub struct SettingsDialog {
+    pub window: nwg::Window,
+    label_language: nwg::Label,
+    combo_language: nwg::ComboBox<&'static str>,
+    check_tray: nwg::CheckBox,
+    check_confirm: nwg::CheckBox,
+    check_refuse_links: nwg::CheckBox,
+    check_follow_links: nwg::CheckBox,
+    label_backup: nwg::Label,
+    text_backup: nwg::TextInput,
+    pub btn_browse: nwg::Button,
+    label_retries: nwg::Label,
+    text_retries: nwg::TextInput,
+    check_force: nwg::CheckBox,
+    check_recycle: nwg::CheckBox,
+    pub btn_reset: nwg::Button,
+    pub btn_ok: nwg::Button,
+    pub btn_cancel: nwg::Button,
+    dir_dialog: nwg::FileDialog,
+    language_layout: nwg::FlexboxLayout,
+    backup_layout: nwg::FlexboxLayout,
+    retries_layout: nwg::FlexboxLayout,
+    buttons_layout: nwg::FlexboxLayout,
+    layout: nwg::FlexboxLayout,
+}
+
+impl SettingsDialog {
+    /// Create the hidden dialog
+    ///
+    /// ### Parameters
+    /// * `owner` - Main window, kept below the dialog and disabled while it is open
+    pub fn build(&mut self, owner: &nwg::Window) -> Result<(), nwg::NwgError> {
+        nwg::Window::builder()
+            .size((scaled(WIDTH), scaled(HEIGHT)))
+            .title(text(Text::TitleSettings))
+            .flags(nwg::WindowFlags::WINDOW)
+            .parent(Some(owner))
+            .build(&mut self.window)?;
+
+        let mut languages = vec![text(Text::LanguageAuto)];
+        languages.extend(Language::ALL.map(Language::native_name));
+        nwg::Label::builder()
+            .text(text(Text::SettingLanguage))
+            .parent(&self.window)
+            .build(&mut self.label_language)?;
+        nwg::ComboBox::builder()
+            .collection(languages)
+            .parent(&self.window)
+            .build(&mut self.combo_language)?;
+
+        for (check, key) in [
+            (&mut self.check_tray, Text::CloseToTray),
+            (&mut self.check_confirm, Text::ConfirmBeforeSwap),
+            (&mut self.check_refuse_links, Text::SettingRefuseLinks),
+            (&mut self.check_follow_links, Text::SettingFollowLinks),
+            (&mut self.check_force, Text::SettingForce),
+            (&mut self.check_recycle, Text::SettingRecycle),
+        ] {
+            nwg::CheckBox::builder()
+                .text(text(key))
+                .parent(&self.window)
+                .build(check)?;
+        }
+
+        nwg::Label::builder()
+            .text(text(Text::SettingBackup))
+            .parent(&self.window)
+            .build(&mut self.label_backup)?;
+        nwg::TextInput::builder()
+            .parent(&self.window)
+            .build(&mut self.text_backup)?;
+        nwg::Label::builder()
+            .text(text(Text::SettingRetries))
+            .parent(&self.window)
+            .build(&mut self.label_retries)?;
+        nwg::TextInput::builder()
+            .flags(nwg::TextInputFlags::VISIBLE | nwg::TextInputFlags::NUMBER)
+            .parent(&self.window)
+            .build(&mut self.text_retries)?;
+
+        for (button, key) in [
+            (&mut self.btn_browse, Text::Folder),
+            (&mut self.btn_reset, Text::ResetDefaults),
+            (&mut self.btn_ok, Text::Save),
+            (&mut self.btn_cancel, Text::Cancel),
+        ] {
+            nwg::Button::builder()
+                .text(text(key))
+                .parent(&self.window)
+                .build(button)?;
+        }
+
+        nwg::FileDialog::builder()
+            .title(text(Text::SelectFolder))
+            .action(nwg::FileDialogAction::OpenDirectory)
+            .build(&mut self.dir_dialog)?;
+
+        Ok(())
+    }
+
+    /// Show the dialog with the current settings and disable the main window
+    ///
+    /// ### Parameters
+    /// * `owner` - Main window to disable until the dialog closes
+    /// * `settings` - Settings shown in the controls
+    /// * `font` - Font of the controls, at the current DPI
+    pub fn open(&self, owner: &nwg::Window, settings: &Settings, font: &nwg::Font) {
+        self.set_font(font);
+        self.fill(settings);
+        // Laid out again on every opening, the DPI may have changed meanwhile
+        let _ = self.build_layouts();
+
+        let (x, y) = owner.position();
+        let (width, _) = owner.size();
+        let offset = (width as i32 - scaled(WIDTH)).max(0) / 2;
+        self.window
+            .set_size(scaled(WIDTH) as u32, scaled(HEIGHT) as u32);
+        self.window.set_position(x + offset, y + scaled(40));
+
+        owner.set_enabled(false);
+        self.window.set_visible(true);
+        self.window.set_focus();
+    }
+
+    /// Hide the dialog and give the focus back to the main window
+    pub fn close(&self, owner: &nwg::Window) {
+        // Enabled before hiding, otherwise Windows activates another program
+        owner.set_enabled(true);
+        self.window.set_visible(false);
+        owner.set_focus();
+    }
+
+    /// Show the values of `settings` in the controls
+    pub fn fill(&self, settings: &Settings) {
+        // Entry 0 follows the system, the rest are `Language::ALL` in order
+        let selected = settings
+            .language
+            .and_then(|language| Language::ALL.iter().position(|l| *l == language))
+            .map_or(0, |index| index + 1);
+        self.combo_language.set_selection(Some(selected));
+
+        let state = |checked| {
+            if checked {
+                nwg::CheckBoxState::Checked
+            } else {
+                nwg::CheckBoxState::Unchecked
+            }
+        };
+        self.check_tray
+            .set_check_state(state(settings.close_to_tray));
+        self.check_confirm
+            .set_check_state(state(settings.confirm_swap));
+        self.check_refuse_links
+            .set_check_state(state(settings.refuse_linked_pair));
+        self.check_follow_links
+            .set_check_state(state(settings.follow_symlinks));
+        self.check_force.set_check_state(state(settings.force));
+        self.check_recycle
+            .set_check_state(state(!settings.delete_permanently));
+
+        let backup = settings
+            .backup
+            .as_ref()
+            .map_or(String::new(), |dir| dir.to_string_lossy().to_string());
+        self.text_backup.set_text(&backup);
+        self.text_retries
+            .set_text(&settings.retry_count.to_string());
+    }
+
+    /// Apply the values of the controls to a copy of `settings`
+    ///
+    /// ### Return Value
+    /// * `Ok(Settings)` - Updated settings
+    /// * `Err(Text)` - Message describing an invalid value
+    pub fn read(&self, settings: &Settings) -> Result<Settings, Text> {
+        let checked = |check: &nwg::CheckBox| check.check_state() == nwg::CheckBoxState::Checked;
+        let retry_count = self
+            .text_retries
+            .text()
+            .trim()
+            .parse()
+            .ok()
+            .filter(|count| (1..=MAX_RENAME_ATTEMPTS).contains(count))
+            .ok_or(Text::InvalidRetryCount)?;
+        let backup = self.text_backup.text();
+        let backup = backup.trim();
+
+        Ok(Settings {
+            language: match self.combo_language.selection() {
+                Some(0) | None => None,
+                Some(index) => Language::ALL.get(index - 1).copied(),
+            },
+            close_to_tray: checked(&self.check_tray),
+            confirm_swap: checked(&self.check_confirm),
+            refuse_linked_pair: checked(&self.check_refuse_links),
+            follow_symlinks: checked(&self.check_follow_links),
+            force: checked(&self.check_force),
+            delete_permanently: !checked(&self.check_recycle),
+            backup: (!backup.is_empty()).then(|| PathBuf::from(backup)),
+            retry_count,
+            ..settings.clone()
+        })
+    }
+
+    /// Pick the backup folder with a folder dialog
+    pub fn browse_backup(&self) {
+        let current = self.text_backup.text();
+        if !current.trim().is_empty() {
+            let _ = self.dir_dialog.set_default_folder(current.trim());
+        }
+        if self.dir_dialog.run(Some(&self.window)) {
+            if let Ok(selected) = self.dir_dialog.get_selected_item() {
+                self.text_backup.set_text(&selected.to_string_lossy());
+            }
+        }
+    }
+
+    fn set_font(&self, font: &nwg::Font) {
+        for label in [
+            &self.label_language,
+            &self.label_backup,
+            &self.label_retries,
+        ] {
+            label.set_font(Some(font));
+        }
+        for check in [
+            &self.check_tray,
+            &self.check_confirm,
+            &self.check_refuse_links,
+            &self.check_follow_links,
+            &self.check_force,
+            &self.check_recycle,
+        ] {
+            check.set_font(Some(font));
+        }
+        for input in [&self.text_backup, &self.text_retries] {
+            input.set_font(Some(font));
+        }
+        for button in [
+            &self.btn_browse,
+            &self.btn_reset,
+            &self.btn_ok,
+            &self.btn_cancel,
+        ] {
+            button.set_font(Some(font));
+        }
+        self.combo_language.set_font(Some(font));
+    }
+
+    fn build_layouts(&self) -> Result<(), nwg::NwgError> {
+        use nwg::stretch::{
+            geometry::{Rect, Size},
+            style::{Dimension as D, FlexDirection},
+        };
+
+        // Sizes are given at `DEFAULT_DPI`
+        let pt = |value: f32| D::Points(scaled_f32(value));
+        let margin = Rect {
+            start: pt(5.0),
+            end: pt(5.0),
+            top: pt(4.0),
+            bottom: pt(4.0),
+        };
+        let gap = Rect {
+            start: pt(5.0),
+            ..Default::default()
+        };
+        let row = |height: f32| Size {
+            width: D::Percent(1.0),
+            height: pt(height),
+        };
+        let fill = Size {
+            width: D::Auto,
+            height: D::Percent(1.0),
+        };
+        let fixed = |width: f32| Size {
+            width: pt(width),
+            height: D::Percent(1.0),
+        };
+
+        nwg::FlexboxLayout::builder()
+            .parent(&self.window)
+            .flex_direction(FlexDirection::Row)
+            .child(&self.label_language)
+            .child_flex_grow(1.0)
+            .child_size(fill)
+            .child(&self.combo_language)
+            .child_size(fixed(140.0))
+            .build_partial(&self.language_layout)?;
+
+        nwg::FlexboxLayout::builder()
+            .parent(&self.window)
+            .flex_direction(FlexDirection::Row)
+            .child(&self.text_backup)
+            .child_flex_grow(1.0)
+            .child_size(fill)
+            .child(&self.btn_browse)
+            .child_margin(gap)
+            .child_size(fixed(70.0))
+            .build_partial(&self.backup_layout)?;
+
+        nwg::FlexboxLayout::builder()
+            .parent(&self.window)
+            .flex_direction(FlexDirection::Row)
+            .child(&self.label_retries)
+            .child_flex_grow(1.0)
+            .child_size(fill)
+            .child(&self.text_retries)
+            .child_size(fixed(60.0))
+            .build_partial(&self.retries_layout)?;
+
+        nwg::FlexboxLayout::builder()
+            .parent(&self.window)
+            .flex_direction(FlexDirection::Row)
+            .child(&self.btn_reset)
+            .child_flex_grow(1.0)
+            .child_size(fill)
+            .child(&self.btn_ok)
+            .child_margin(gap)
+            .child_size(fixed(80.0))
+            .child(&self.btn_cancel)
+            .child_margin(gap)
+            .child_size(fixed(80.0))
+            .build_partial(&self.buttons_layout)?;
+
+        nwg::FlexboxLayout::builder()
+            .parent(&self.window)
+            .flex_direction(FlexDirection::Column)
+            .padding(Rect {
+                start: pt(10.0),
+                end: pt(10.0),
+                top: pt(10.0),
+                bottom: pt(10.0),
+            })
+            .child_layout(&self.language_layout)
+            .child_margin(margin)
+            .child_size(row(26.0))
+            .child(&self.check_tray)
+            .child_margin(margin)
+            .child_size(row(22.0))
+            .child(&self.check_confirm)
+            .child_margin(margin)
+            .child_size(row(22.0))
+            .child(&self.check_refuse_links)
+            .child_margin(margin)
+            .child_size(row(22.0))
+            .child(&self.check_follow_links)
+            .child_margin(margin)
+            .child_size(row(22.0))
+            .child(&self.label_backup)
+            .child_margin(margin)
+            .child_size(row(20.0))
+            .child_layout(&self.backup_layout)
+            .child_margin(margin)
+            .child_size(row(26.0))
+            .child_layout(&self.retries_layout)
+            .child_margin(margin)
+            .child_size(row(26.0))
+            .child(&self.check_force)
+            .child_margin(margin)
+            .child_size(row(22.0))
+            .child(&self.check_recycle)
+            .child_margin(margin)
+            .child_size(row(22.0))
+            .child_layout(&self.buttons_layout)
+            .child_margin(margin)
+            .child_flex_grow(1.0)
+            .child_size(Size {
+                width: D::Percent(1.0),
+                height: D::Auto,
+            })
+            .build(&self.layout)
+    }
+}
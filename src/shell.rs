@@ -0,0 +1,232 @@
+// Windows shell integration
+//
+// Creates desktop, Start menu and Send To shortcuts to the running
+// executable through the IShellLinkW COM interface, and looks up the
+// icon and type Explorer shows for an item
+
+use std::{
+    ffi::{OsStr, OsString},
+    io,
+    iter::once,
+    os::windows::ffi::{OsStrExt, OsStringExt},
+    path::{Path, PathBuf},
+    ptr,
+};
+
+use winapi::shared::guiddef::GUID;
+use winapi::shared::windef::HICON;
+use winapi::shared::winerror::FAILED;
+use winapi::shared::wtypesbase::CLSCTX_INPROC_SERVER;
+use winapi::um::combaseapi::{CoCreateInstance, CoInitializeEx, CoTaskMemFree, CoUninitialize};
+use winapi::um::knownfolders::{FOLDERID_Desktop, FOLDERID_Programs, FOLDERID_SendTo};
+use winapi::um::objbase::COINIT_APARTMENTTHREADED;
+use winapi::um::objidl::IPersistFile;
+use winapi::um::shellapi::{
+    SHGetFileInfoW, SHFILEINFOW, SHGFI_ICON, SHGFI_SMALLICON, SHGFI_TYPENAME,
+};
+use winapi::um::shlobj::SHGetKnownFolderPath;
+use winapi::um::shobjidl_core::IShellLinkW;
+use winapi::um::winuser::DestroyIcon;
+use winapi::Interface;
+
+use crate::cli::GUI_FLAG;
+use crate::storage;
+
+/// {00021401-0000-0000-C000-000000000046}, not exported by winapi
+#[allow(non_upper_case_globals)]
+const CLSID_ShellLink: GUID = GUID {
+    Data1: 0x0002_1401,
+    Data2: 0x0000,
+    Data3: 0x0000,
+    Data4: [0xC0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x46],
+};
+
+const SHORTCUT_NAME: &str = "名称交换器.lnk";
+
+/// Create shortcuts to the running executable on the desktop and, for an
+/// installed copy, in the Start menu
+///
+/// A portable copy may be moved or deleted along with its folder, so it is
+/// not added to the Start menu
+///
+/// ### Return Value
+/// * `Ok(())` - Both shortcuts were written
+/// * `Err(io::Error)` - The executable or a folder could not be located, or COM failed
+pub fn install_shortcuts() -> io::Result<()> {
+    let exe = std::env::current_exe()?;
+    let folders: &[&GUID] = if storage::is_portable() {
+        &[&FOLDERID_Desktop]
+    } else {
+        &[&FOLDERID_Desktop, &FOLDERID_Programs]
+    };
+    for folder in folders {
+        let dir = known_folder(folder).ok_or(io::ErrorKind::NotFound)?;
+        create_shortcut(&dir.join(SHORTCUT_NAME), &exe, Some(GUI_FLAG))?;
+    }
+    Ok(())
+}
+
+/// Add the program to the Explorer "Send To" menu
+///
+/// Explorer passes every selected item to a Send To target, so sending two
+/// items swaps them at once through the command-line mode. Sending a single
+/// item while the program is running fills the first empty field of the open window.
+///
+/// ### Return Value
+/// * `Ok(())` - Send To entry was written
+/// * `Err(io::Error)` - The executable or the folder could not be located, or COM failed
+pub fn install_send_to() -> io::Result<()> {
+    let exe = std::env::current_exe()?;
+    create_shortcut(&send_to_link()?, &exe, None)
+}
+
+/// Remove the Send To entry, doing nothing when it does not exist
+pub fn uninstall_send_to() -> io::Result<()> {
+    match std::fs::remove_file(send_to_link()?) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Whether the Send To entry is currently present
+pub fn send_to_installed() -> bool {
+    send_to_link().is_ok_and(|link| link.exists())
+}
+
+fn send_to_link() -> io::Result<PathBuf> {
+    let dir = known_folder(&FOLDERID_SendTo).ok_or(io::ErrorKind::NotFound)?;
+    Ok(dir.join(SHORTCUT_NAME))
+}
+
+/// Write a `.lnk` file pointing at `target` with optional arguments, using the icon embedded in it
+fn create_shortcut(link: &Path, target: &Path, args: Option<&str>) -> io::Result<()> {
+    let check = |hr: i32| {
+        if FAILED(hr) {
+            Err(io::Error::from_raw_os_error(hr))
+        } else {
+            Ok(())
+        }
+    };
+
+    unsafe {
+        // S_FALSE means COM was already initialized on this thread, which is fine
+        let init = CoInitializeEx(ptr::null_mut(), COINIT_APARTMENTTHREADED);
+
+        let result = (|| {
+            let mut link_obj: *mut IShellLinkW = ptr::null_mut();
+            check(CoCreateInstance(
+                &CLSID_ShellLink,
+                ptr::null_mut(),
+                CLSCTX_INPROC_SERVER,
+                &IShellLinkW::uuidof(),
+                &mut link_obj as *mut _ as *mut _,
+            ))?;
+            let link_obj = &*link_obj;
+
+            let target_w = wide(target.as_os_str());
+            let result = (|| {
+                check(link_obj.SetPath(target_w.as_ptr()))?;
+                if let Some(args) = args {
+                    check(link_obj.SetArguments(wide(OsStr::new(args)).as_ptr()))?;
+                }
+                check(link_obj.SetIconLocation(target_w.as_ptr(), 0))?;
+                if let Some(dir) = target.parent() {
+                    check(link_obj.SetWorkingDirectory(wide(dir.as_os_str()).as_ptr()))?;
+                }
+
+                let mut file: *mut IPersistFile = ptr::null_mut();
+                check(
+                    link_obj.QueryInterface(&IPersistFile::uuidof(), &mut file as *mut _ as *mut _),
+                )?;
+                let file = &*file;
+                let saved = check(file.Save(wide(link.as_os_str()).as_ptr(), 1));
+                file.Release();
+                saved
+            })();
+
+            link_obj.Release();
+            result
+        })();
+
+        if !FAILED(init) {
+            CoUninitialize();
+        }
+        result
+    }
+}
+
+/// Resolve a known folder such as the desktop
+/// Locate a known folder such as the desktop or `%APPDATA%`
+pub fn known_folder(id: &GUID) -> Option<PathBuf> {
+    unsafe {
+        let mut raw = ptr::null_mut();
+        let hr = SHGetKnownFolderPath(id, 0, ptr::null_mut(), &mut raw);
+        let path = if FAILED(hr) || raw.is_null() {
+            None
+        } else {
+            let len = (0..).take_while(|&i| *raw.add(i) != 0).count();
+            let slice = std::slice::from_raw_parts(raw, len);
+            Some(PathBuf::from(OsString::from_wide(slice)))
+        };
+        CoTaskMemFree(raw as *mut _);
+        path
+    }
+}
+
+/// Icon and type description Explorer shows for an item
+pub struct FileType {
+    /// Small shell icon, destroyed along with the value
+    pub icon: HICON,
+    /// Type description such as "Text Document" or "File folder"
+    pub name: String,
+}
+
+impl Drop for FileType {
+    fn drop(&mut self) {
+        if !self.icon.is_null() {
+            unsafe {
+                DestroyIcon(self.icon);
+            }
+        }
+    }
+}
+
+/// Look up the shell icon and type description of an existing item
+///
+/// Directories get the folder icon, files the icon of their type or their own
+/// embedded icon, exactly as in Explorer
+///
+/// ### Parameters
+/// * `path` - Path of the item, without the extended-length prefix
+///
+/// ### Return Value
+/// `None` when the shell knows nothing about the path
+pub fn file_type(path: &Path) -> Option<FileType> {
+    let path_w = wide(path.as_os_str());
+    unsafe {
+        let mut info: SHFILEINFOW = std::mem::zeroed();
+        let found = SHGetFileInfoW(
+            path_w.as_ptr(),
+            0,
+            &mut info,
+            std::mem::size_of::<SHFILEINFOW>() as u32,
+            SHGFI_ICON | SHGFI_SMALLICON | SHGFI_TYPENAME,
+        );
+        if found == 0 {
+            return None;
+        }
+        let len = info
+            .szTypeName
+            .iter()
+            .position(|&c| c == 0)
+            .unwrap_or(info.szTypeName.len());
+        Some(FileType {
+            icon: info.hIcon,
+            name: String::from_utf16_lossy(&info.szTypeName[..len]),
+        })
+    }
+}
+
+fn wide(text: &OsStr) -> Vec<u16> {
+    text.encode_wide().chain(once(0)).collect()
+}
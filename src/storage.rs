@@ -0,0 +1,91 @@
+// Location of the settings and history files
+//
+// A portable copy keeps them next to the executable. When that directory is
+// not writable, as under Program Files, the copy counts as installed and
+// they go to `%APPDATA%\rs-NameExchanger` instead. The choice is made once
+// per launch.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process,
+    sync::OnceLock,
+};
+
+use winapi::um::knownfolders::FOLDERID_RoamingAppData;
+
+use crate::shell::known_folder;
+
+const APP_DIR: &str = "rs-NameExchanger";
+
+struct Location {
+    dir: PathBuf,
+    portable: bool,
+}
+
+/// Directory holding the settings and history files
+///
+/// ### Return Value
+/// * `Some(&Path)` - Executable directory when portable, otherwise the folder below `%APPDATA%`
+/// * `None` - Neither could be located
+pub fn data_dir() -> Option<&'static Path> {
+    location().map(|location| location.dir.as_path())
+}
+
+/// Whether the data files are stored next to the executable
+pub fn is_portable() -> bool {
+    location().is_some_and(|location| location.portable)
+}
+
+/// Path of a data file, creating the data directory when it is missing
+///
+/// ### Parameters
+/// * `name` - File name inside the data directory
+pub fn file(name: &str) -> Option<PathBuf> {
+    let dir = data_dir()?;
+    if !dir.exists() {
+        fs::create_dir_all(dir).ok()?;
+    }
+    Some(dir.join(name))
+}
+
+fn location() -> Option<&'static Location> {
+    static LOCATION: OnceLock<Option<Location>> = OnceLock::new();
+    LOCATION.get_or_init(detect).as_ref()
+}
+
+/// Directory of the running executable
+pub fn exe_dir() -> Option<PathBuf> {
+    Some(env::current_exe().ok()?.parent()?.to_path_buf())
+}
+
+fn detect() -> Option<Location> {
+    let exe_dir = exe_dir()?;
+    if is_writable(&exe_dir) {
+        return Some(Location {
+            dir: exe_dir,
+            portable: true,
+        });
+    }
+    Some(Location {
+        dir: known_folder(&FOLDERID_RoamingAppData)?.join(APP_DIR),
+        portable: false,
+    })
+}
+
+/// Check writability by creating a probe file, attributes alone miss ACLs
+fn is_writable(dir: &Path) -> bool {
+    let probe = dir.join(format!(".NameExchanger-{}.tmp", process::id()));
+    match fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&probe)
+    {
+        Ok(file) => {
+            drop(file);
+            let _ = fs::remove_file(&probe);
+            true
+        }
+        Err(_) => false,
+    }
+}
@@ -0,0 +1,138 @@
+// Background runner for swaps
+//
+// Swapping big directories across volumes copies every file, so it runs on a
+// worker thread instead of freezing the window. The worker posts its
+// messages through a channel and wakes the message loop with an
+// `nwg::Notice`, the UI thread then drains them in its `OnNotice` handler.
+//
+// A single long-lived thread runs every job, so the library's per-thread
+// undo record stays valid between a swap and the following undo.
+
+use std::{
+    cell::{Cell, RefCell},
+    panic::{self, AssertUnwindSafe},
+    sync::mpsc::{self, Receiver, Sender, TryRecvError},
+    thread::{self, JoinHandle},
+};
+
+use native_windows_gui as nwg;
+
+type Job<M> = Box<dyn FnOnce(&dyn Fn(M)) + Send>;
+
+enum Event<M> {
+    Message(M),
+    Done,
+}
+
+/// Runs one job at a time on a worker thread, reporting back to the UI thread
+pub struct Worker<M> {
+    pub notice: nwg::Notice,
+    jobs: RefCell<Option<Sender<Job<M>>>>,
+    events: RefCell<Option<Receiver<Event<M>>>>,
+    thread: RefCell<Option<JoinHandle<()>>>,
+    busy: Cell<bool>,
+}
+
+impl<M> Default for Worker<M> {
+    fn default() -> Self {
+        Self {
+            notice: Default::default(),
+            jobs: RefCell::new(None),
+            events: RefCell::new(None),
+            thread: RefCell::new(None),
+            busy: Cell::new(false),
+        }
+    }
+}
+
+impl<M: Send + 'static> Worker<M> {
+    /// Whether a job is still running
+    pub fn is_busy(&self) -> bool {
+        self.busy.get()
+    }
+
+    /// Run a job on the worker thread
+    ///
+    /// The job receives a function posting messages to the UI thread
+    ///
+    /// ### Parameters
+    /// * `job` - Work to run, must not touch any control
+    ///
+    /// ### Return Value
+    /// * `true` - Job was started
+    /// * `false` - Another job is still running, nothing was started
+    pub fn start(&self, job: impl FnOnce(&dyn Fn(M)) + Send + 'static) -> bool {
+        if self.busy.get() {
+            return false;
+        }
+        if self.jobs.borrow().is_none() {
+            self.spawn();
+        }
+        let sent = match &*self.jobs.borrow() {
+            Some(jobs) => jobs.send(Box::new(job)).is_ok(),
+            None => false,
+        };
+        self.busy.set(sent);
+        sent
+    }
+
+    /// Take the messages posted so far, call from the `OnNotice` handler
+    ///
+    /// ### Return Value
+    /// Returns tuple `(messages, finished)`, `finished` is `true` once the job
+    /// has returned and the worker accepts a new one
+    pub fn receive(&self) -> (Vec<M>, bool) {
+        let mut messages = Vec::new();
+        let mut finished = false;
+        if let Some(events) = &*self.events.borrow() {
+            loop {
+                match events.try_recv() {
+                    Ok(Event::Message(message)) => messages.push(message),
+                    Ok(Event::Done) => finished = true,
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => {
+                        finished = true;
+                        break;
+                    }
+                }
+            }
+        }
+        if finished {
+            self.busy.set(false);
+        }
+        (messages, finished)
+    }
+
+    fn spawn(&self) {
+        let (job_sender, jobs) = mpsc::channel::<Job<M>>();
+        let (event_sender, events) = mpsc::channel();
+        let notice = self.notice.sender();
+
+        let thread = thread::spawn(move || {
+            for job in jobs {
+                let post = |message| {
+                    let _ = event_sender.send(Event::Message(message));
+                    notice.notice();
+                };
+                // A panicking job must not leave the UI waiting forever
+                let _ = panic::catch_unwind(AssertUnwindSafe(|| job(&post)));
+                let _ = event_sender.send(Event::Done);
+                notice.notice();
+            }
+        });
+
+        *self.jobs.borrow_mut() = Some(job_sender);
+        *self.events.borrow_mut() = Some(events);
+        *self.thread.borrow_mut() = Some(thread);
+    }
+}
+
+impl<M> Drop for Worker<M> {
+    fn drop(&mut self) {
+        // Let a running swap finish, or roll back, before the process exits
+        self.jobs.get_mut().take();
+        if let Some(thread) = self.thread.get_mut().take() {
+            let _ = thread.join();
+        }
+    }
+}